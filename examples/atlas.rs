@@ -3,8 +3,9 @@
 // space possible. Writes the result to "atlas.png".
 // Note that the underlying problem is NP-hard, so the algorithm is a
 // best-effort one.
+// Requires the "image" feature.
 
-use std::collections::HashMap;
+#[allow(dead_code)]
 mod common;
 use common::*;
 use rust_text as rt;
@@ -27,23 +28,12 @@ fn main() {
     // Scale the face to 24 pts on a 96 DPI display.
     let mut scaled_face = font_face.scale(24.0, 96.0).expect("Failed to scale font!");
 
-    // Rasterize each required glyph and create a map from character to rendered glyph.
-    let glyph_lut: HashMap<_, _> = characters.chars()
-        .map(|c| (c, scaled_face.rasterize_glyph(c).expect("Failed to rasterize glyph!")))
-        .collect();
-
-    // Pack the glyphs into the tightest space possible.
+    // Rasterize the glyphs and pack them into the tightest space possible.
     // Note: NP-hard, best effort algorithm.
-    let pack = rt::pack_glyphs(glyph_lut.values());
-    // We create the bitmap that we will write the result to. Not part of the API.
-    let mut bitmap = Bitmap::new(pack.width(), pack.height());
-    // Go through each packed element.
-    for (character, rect) in &pack {
-        // Look up the rendered glyph.
-        let glyph = glyph_lut.get(character).expect("Could not find glyph!");
-        // Draw the glyph to the packed position
-        bitmap.blit(rect.x, rect.y, glyph);
-    }
-    // Saves the bitmap. Not part of the API.
-    bitmap.to_file(&out_path);
+    let atlas = scaled_face.build_atlas(characters, rt::AtlasOptions::default())
+        .expect("Failed to build atlas!");
+    // Saves the image.
+    let image = image::GrayImage::from_raw(atlas.width as u32, atlas.height as u32, atlas.data.to_vec())
+        .expect("Atlas data does not match its dimensions!");
+    image.save(&out_path).expect("Failed to write the image!");
 }
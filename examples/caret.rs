@@ -32,37 +32,43 @@ fn main() {
     // Rasterize a caret, which is the character '_' for simplicity.
     glyph_lut.insert('_', scaled_face.rasterize_glyph('_').expect("Failed to rasterize glyph!"));
 
+    // Shape the text once, the layout keeps the positioned glyphs.
+    let layout = rt::TextLayout::new(&scaled_face, text, rt::ShapeOptions::default())
+        .expect("Failed to shape text!");
     // Measure the text dimensions so we can pre-allocate the required bitmap.
-    let (width, text_height) = scaled_face.shape_text(text, rt::ShapeOptions::default(), |_| {});
-    // We want to put a caret under each character, so we need chars().count() times
-    // the height.
-    let full_height = text_height * (text.chars().count() as i32);
+    let (width, text_height) = layout.size();
+    // We want to put a caret before each grapheme cluster (a character with its combining
+    // marks), so we need as many times the height as there are clusters.
+    let clusters = layout.glyphs().last().map_or(0, |info| info.cluster + 1);
+    let full_height = text_height * (clusters as i32);
     // We create the bitmap that we will write the result to. Not part of the API.
     let mut bitmap = Bitmap::new(width as usize, full_height as usize);
-    // Loop for each character.
-    for i in 0..text.chars().count() {
+    // Loop for each cluster.
+    for i in 0..clusters {
         // Vertical offset for the current text instance.
-        let y_offset = i * (text_height as usize);
-        // Invoke shape_text again, this time to actually position the glyphs.
-        scaled_face.shape_text(text, rt::ShapeOptions::default(), |info| {
+        let y_offset = i as i32 * text_height;
+        // Draw the glyphs at the positions stored in the layout.
+        let mut prev_cluster = None;
+        for info in layout.glyphs() {
             // Look up the rendered glyph.
             let glyph = glyph_lut.get(&info.character).expect("Could not find glyph!");
             // Calculate the exact character placement position.
-            let xp = (info.x + glyph.x_offset) as usize;
-            let yp = (info.y + glyph.y_offset) as usize + y_offset;
+            let xp = info.x + glyph.x_offset;
+            let yp = info.y + glyph.y_offset + y_offset;
             // Draw the glyph to the given position. Not part of the API.
             bitmap.blit(xp, yp, glyph);
-            // If this position is a caret position (info.index == i) then draw the caret.
-            if i == info.index {
+            // If this is the first character of the cluster then draw the caret.
+            if i == info.cluster && prev_cluster != Some(i) {
                 // Look up the caret glyph.
                 let glyph = glyph_lut.get(&'_').expect("Could not find glyph!");
                 // Calculate the exact caret placement position.
-                let xp = (info.caret_x + glyph.x_offset) as usize;
-                let yp = (info.caret_y + glyph.y_offset) as usize + y_offset;
+                let xp = info.caret_x + glyph.x_offset;
+                let yp = info.caret_y + glyph.y_offset + y_offset;
                 // Draw the glyph to the given position. Not part of the API.
                 bitmap.blit(xp, yp, glyph);
             }
-        });
+            prev_cluster = Some(info.cluster);
+        }
     }
 
     // Saves the bitmap. Not part of the API.
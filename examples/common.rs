@@ -1,14 +1,11 @@
 
 // Common utilities for the examples.
 
-use std::io::prelude::*;
-use std::fs::File;
 use rust_text as rt;
 
 /// Utility to load a file in byte representation.
 pub(crate) fn load_bytes(path: &str) -> Box<[u8]> {
-    let file = File::open(path).expect("couldn't find font file");
-    file.bytes().map(|b| b.unwrap()).collect::<Vec<_>>().into_boxed_slice()
+    std::fs::read(path).expect("couldn't find font file").into_boxed_slice()
 }
 
 /// Represents a grayscale bitmap.
@@ -28,18 +25,10 @@ impl Bitmap {
         }
     }
 
-    /// Draws a rasterized glyph to the given position.
-    pub(crate) fn blit(&mut self, x0: usize, y0: usize, glyph: &rt::RasterizedGlyph) {
-        for y in 0..glyph.height {
-            let yoff_buff = (y0 + y) * self.width;
-            let yoff_glyph = y * glyph.width;
-            for x in 0..glyph.width {
-                let buff_offs = yoff_buff + x0 + x;
-                if buff_offs < self.data.len() {
-                    self.data[buff_offs] = glyph.data[yoff_glyph + x];
-                }
-            }
-        }
+    /// Draws a rasterized glyph to the given position. The parts outside of
+    /// the bitmap are clipped, so the position can be negative.
+    pub(crate) fn blit(&mut self, x0: i32, y0: i32, glyph: &rt::RasterizedGlyph) {
+        glyph.blit_into(&mut self.data, self.width, self.height, x0, y0);
     }
 
     /// Writes the bitmap to file.
@@ -40,7 +40,8 @@ fn main() {
     // Note: In a game you probably don't need to allocate a buffer , so you would only
     // call once and draw. In this case we need to allocate a pixel buffer in advance, so
     // we invoke shape_text twice.
-    let (width, height) = scaled_face.shape_text(text, rt::ShapeOptions::default(), |_| {});
+    let (width, height) = scaled_face.shape_text(text, rt::ShapeOptions::default(), |_| {})
+        .expect("Failed to shape text!");
     // We create the bitmap that we will write the result to. Not part of the API.
     let mut bitmap = Bitmap::new(width as usize, height as usize);
     // Invoke shape_text again, this time to actually position the glyphs.
@@ -50,11 +51,11 @@ fn main() {
         // Calculate the exact character placement position. The reason we need to fiddle
         // with offsets is because the buffer is always trimmed to the minimum, so the
         // rasterized glyph needs to know how much has been trimmed from it.
-        let xp = (info.x + glyph.x_offset) as usize;
-        let yp = (info.y + glyph.y_offset) as usize;
+        let xp = info.x + glyph.x_offset;
+        let yp = info.y + glyph.y_offset;
         // Draw the glyph to the given position. Not part of the API.
         bitmap.blit(xp, yp, glyph);
-    });
+    }).expect("Failed to shape text!");
 
     // Saves the bitmap. Not part of the API.
     bitmap.to_file(&out_path);
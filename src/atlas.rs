@@ -0,0 +1,75 @@
+
+// Composing rasterized glyphs into a single atlas bitmap, the usual way text
+// gets uploaded to the GPU.
+
+use std::collections::HashMap;
+use crate::{RasterizedGlyph, BitDepth, Rect, PackOptions, pack, pack_coverage};
+
+/// Options for building a glyph atlas, see `ScaledFontFace::build_atlas`.
+#[derive(Debug, Clone, Default)]
+pub struct AtlasOptions {
+    /// The number of empty pixels left around each glyph, so sampling with
+    /// filtering doesn't bleed the neighbouring glyphs in.
+    pub padding: usize,
+    /// The number of bits each pixel of the bitmap takes.
+    pub bit_depth: BitDepth,
+    /// The options of packing the glyphs into the bitmap.
+    pub pack: PackOptions,
+}
+
+/// A grayscale bitmap with glyphs packed into it.
+#[derive(Debug, Clone)]
+pub struct Atlas {
+    /// Width of the bitmap in pixels.
+    pub width: usize,
+    /// Height of the bitmap in pixels.
+    pub height: usize,
+    /// The number of bits each pixel of `data` takes.
+    pub bit_depth: BitDepth,
+    /// The bitmap data itself (row-major, grayscale). Pixels are packed as
+    /// described by `bit_depth`, each row starts on a new byte.
+    pub data: Box<[u8]>,
+    /// The position of each glyph in the bitmap, without the padding.
+    pub glyphs: HashMap<char, Rect>,
+    /// The rasterized glyphs, for their offsets. These are always 8-bit.
+    pub rasterized: HashMap<char, RasterizedGlyph>,
+}
+
+/// Packs the glyphs and draws them into a single bitmap.
+pub(crate) fn compose(glyphs: HashMap<char, RasterizedGlyph>, options: &AtlasOptions) -> Atlas {
+    use std::cmp::max;
+    let pad = options.padding;
+    let pack = pack::bin_pack(glyphs.values(),
+        |e| (e.width + 2 * pad, e.height + 2 * pad),
+        |(w1, h1), (w2, h2)| max(w1, h1).cmp(max(w2, h2)),
+        |e| e.character,
+        &options.pack);
+    let width = pack.width();
+    let height = pack.height();
+    let mut data = vec![0u8; width * height].into_boxed_slice();
+    let mut rects = HashMap::with_capacity(glyphs.len());
+    for (c, rect) in pack {
+        let glyph = &glyphs[&c];
+        let x0 = rect.x + pad;
+        let y0 = rect.y + pad;
+        for y in 0..glyph.height {
+            let src = &glyph.data[(y * glyph.width)..((y + 1) * glyph.width)];
+            let dst_offs = (y0 + y) * width + x0;
+            data[dst_offs..(dst_offs + glyph.width)].copy_from_slice(src);
+        }
+        rects.insert(c, Rect{
+            x: x0,
+            y: y0,
+            width: glyph.width,
+            height: glyph.height,
+        });
+    }
+    Atlas{
+        width,
+        height,
+        bit_depth: options.bit_depth,
+        data: pack_coverage(&data, width, height, options.bit_depth),
+        glyphs: rects,
+        rasterized: glyphs,
+    }
+}
@@ -0,0 +1,107 @@
+
+// The interface of the rendering backends. The platform-independent wrapper
+// types are generic over it, so custom backends can be plugged in, like a
+// deterministic one to test layout code with.
+
+use crate::{FontInfo, RasterizedGlyph, GlyphBox, GlyphPositioning, ShapeOptions, RenderQuality, RenderColors, Result, Error};
+
+/// A loaded font file resource of a rendering backend.
+pub trait FontBackend: Sized {
+    /// A font face selected from the font file.
+    type Face: Clone;
+    /// A font face scaled to a given size.
+    type Scaled: ScaledFaceBackend;
+    /// Rendering resources scaled font faces can share, see `RenderContext`.
+    type Context: Clone;
+
+    /// Loads the binary contents of a font file.
+    fn from_bytes(bytes: &[u8]) -> Result<Self>;
+
+    /// Returns the information parsed from the font file.
+    fn info(&self) -> &FontInfo;
+
+    /// Selects a face based on a face name or alias.
+    fn face(&self, name: &str) -> Result<Self::Face>;
+
+    /// Creates rendering resources to share between scaled font faces.
+    fn create_context() -> Result<Self::Context>;
+
+    /// Scales a font face with the given settings. The face uses the
+    /// resources of `context` if given, its own otherwise.
+    fn scale(face: &Self::Face, settings: &ScaleSettings, context: Option<&Self::Context>) -> Result<Self::Scaled>;
+}
+
+/// The settings a rendering backend scales a font face with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScaleSettings {
+    /// The size of the face in points.
+    pub pts: f64,
+    /// The resolution of the device in dots per inch.
+    pub dpi: f64,
+    /// The antialiasing of the glyphs.
+    pub quality: RenderQuality,
+    /// The colors glyphs are drawn with, if the backend draws in color.
+    pub colors: RenderColors,
+    /// The counterclockwise rotation of the text in tenths of a degree.
+    pub escapement: i32,
+    /// The horizontal scale of the glyphs.
+    pub stretch: f64,
+    /// Whether the rows of rasterized bitmaps are stored bottom-up.
+    pub flip_y: bool,
+    /// The coordinates on the design axes of a variable font, by the tags of
+    /// the axes. Axes at their default coordinates are left out, so this is
+    /// empty unless the face needs to be varied.
+    pub variations: Vec<(String, f32)>,
+}
+
+/// A font face of a rendering backend, scaled to a given size.
+pub trait ScaledFaceBackend {
+    /// Returns the size of the em square in pixels.
+    fn em_size(&self) -> f64;
+
+    /// Returns the distance between the baselines of consecutive lines in
+    /// pixels.
+    fn line_height(&self) -> i32;
+
+    /// Returns the distance of the baseline from the top of a line in pixels.
+    fn ascent(&self) -> i32;
+
+    /// Rasterizes the given character to an 8-bit grayscale bitmap, with the
+    /// rows in the order the face got scaled with. The `cell` and `baseline`
+    /// of the result don't need to be filled in, they're computed from the
+    /// font metrics.
+    fn rasterize_glyph(&mut self, codepoint: char) -> Result<RasterizedGlyph>;
+
+    /// Rasterizes the glyph with the given index, like `rasterize_glyph`.
+    fn rasterize_glyph_index(&mut self, glyph_id: u16) -> Result<RasterizedGlyph>;
+
+    /// Changes the size of the face in place, keeping the rendering resources
+    /// that don't depend on it. The quality might change with the size too.
+    /// Unsupported by default, then the face gets scaled again instead.
+    fn set_size(&mut self, _pts: f64, _dpi: f64, _quality: RenderQuality) -> Result<()> {
+        Err(Error::Unsupported("Resizing a scaled face in place"))
+    }
+
+    /// Rasterizes the given character like `rasterize_glyph`, straight into
+    /// an 8-bit grayscale bitmap `dst_width` pixels wide, with the top left
+    /// corner of the glyph bitmap at `(x, y)`. Returns the box of the glyph
+    /// bitmap relative to the drawing position. By default the glyph is
+    /// rasterized with `rasterize_glyph`, then copied.
+    fn rasterize_glyph_into(&mut self, codepoint: char, dst: &mut [u8], dst_width: usize,
+        x: usize, y: usize) -> Result<GlyphBox> {
+        let glyph = self.rasterize_glyph(codepoint)?;
+        crate::copy_into(&glyph, dst, dst_width, x, y)?;
+        Ok(glyph.ink_box())
+    }
+
+    /// Lays out the text horizontally, reporting each character to `f`, and
+    /// returns the size of the laid out text. Newlines are reported too, at
+    /// the end of their lines.
+    fn shape_text<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions, f: F) -> Result<(i32, i32)>;
+
+    /// Lays out UTF-16 text like `shape_text`, with unpaired surrogates
+    /// replaced by U+FFFD. By default the text is converted to UTF-8 first.
+    fn shape_text_utf16<F: FnMut(GlyphPositioning)>(&self, units: &[u16], options: ShapeOptions, f: F) -> Result<(i32, i32)> {
+        self.shape_text(&String::from_utf16_lossy(units), options, f)
+    }
+}
@@ -0,0 +1,66 @@
+
+// Blurring coverage bitmaps, for soft shadows and glows behind text. A
+// Gaussian blur is approximated by repeated box blurs, each of them separable
+// into a horizontal and a vertical pass.
+
+use crate::{RasterizedGlyph, BitDepth};
+
+/// The number of box blurs approximating the Gaussian one.
+const PASSES: usize = 3;
+
+/// Blurs the values of a line with a box of `2 * radius + 1` pixels, treating
+/// the values outside of the line as zero. `stride` is the distance between
+/// consecutive values of the line in `data`.
+fn blur_line(data: &mut [u32], scratch: &mut Vec<u32>, start: usize, len: usize, stride: usize, radius: usize) {
+    scratch.clear();
+    scratch.extend((0..len).map(|i| data[start + i * stride]));
+    let size = (2 * radius + 1) as u32;
+    // The sum of the values under the box
+    let mut sum: u32 = scratch.iter().take(radius).sum();
+    for i in 0..len {
+        if i + radius < len {
+            sum += scratch[i + radius];
+        }
+        data[start + i * stride] = (sum + size / 2) / size;
+        if i >= radius {
+            sum -= scratch[i - radius];
+        }
+    }
+}
+
+/// Blurs the glyph, see `RasterizedGlyph::blur`.
+pub(crate) fn blur(glyph: &RasterizedGlyph, radius: f32) -> RasterizedGlyph {
+    // Each box covers its share of the radius
+    let box_radius = if radius > 0.0 { ((radius / PASSES as f32).round() as usize).max(1) } else { 0 };
+    let pad = box_radius * PASSES;
+    let width = glyph.width + 2 * pad;
+    let height = glyph.height + 2 * pad;
+    let mut data = vec![0u32; width * height];
+    for y in 0..glyph.height {
+        for x in 0..glyph.width {
+            data[(y + pad) * width + x + pad] = glyph.coverage(x, y) as u32;
+        }
+    }
+    if box_radius > 0 {
+        let mut scratch = Vec::new();
+        for _ in 0..PASSES {
+            for y in 0..height {
+                blur_line(&mut data, &mut scratch, y * width, width, 1, box_radius);
+            }
+            for x in 0..width {
+                blur_line(&mut data, &mut scratch, x, height, width, box_radius);
+            }
+        }
+    }
+    RasterizedGlyph{
+        character: glyph.character,
+        x_offset: glyph.x_offset - pad as i32,
+        y_offset: glyph.y_offset - pad as i32,
+        width,
+        height,
+        bit_depth: BitDepth::Eight,
+        cell: glyph.cell,
+        baseline: glyph.baseline,
+        data: data.into_iter().map(|c| c as u8).collect(),
+    }
+}
@@ -0,0 +1,45 @@
+// Export of glyph packs as BMFont descriptors, the text format that most game
+// engines and bitmap font tools understand.
+// The format is described at: http://www.angelcode.com/products/bmfont/doc/file_format.html
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use crate::{GlyphPack, GlyphMetrics, ScaledFontFace, FontBackend, ScaledFaceBackend, RenderQuality};
+
+impl GlyphPack {
+    /// Creates a BMFont text descriptor for the glyphs packed from `face`,
+    /// describing a single page stored in `page_file`. The `info` line names
+    /// the face `face_name`. Glyphs missing from `metrics` are placed with
+    /// zero offsets and advanced by the width of their bitmap.
+    pub fn to_bmfont<B: FontBackend>(&self, face: &ScaledFontFace<B>, face_name: &str,
+        metrics: &HashMap<char, GlyphMetrics>, page_file: &str) -> String {
+        // Sort, so the output is deterministic
+        let mut chars: Vec<_> = self.into_iter().collect();
+        chars.sort_by_key(|(c, _)| **c);
+
+        let style = face.face.style_flags();
+        let smooth = face.quality != RenderQuality::Aliased;
+
+        let mut result = String::new();
+        // Writing to a String can't fail
+        let _ = writeln!(result,
+            "info face=\"{}\" size={} bold={} italic={} charset=\"\" unicode=1 stretchH={} smooth={} aa=1 padding=0,0,0,0 spacing=0,0",
+            face_name, face.pixels_per_em().round() as i32, style.bold as u8, style.italic as u8,
+            (face.stretch * 100.0).round() as i32, smooth as u8);
+        let _ = writeln!(result, "common lineHeight={} base={} scaleW={} scaleH={} pages=1 packed=0",
+            face.line_height(), face.inner.ascent(), self.width(), self.height());
+        let _ = writeln!(result, "page id=0 file=\"{}\"", page_file);
+        let _ = writeln!(result, "chars count={}", chars.len());
+        for (c, rect) in chars {
+            let m = metrics.get(c).cloned().unwrap_or(GlyphMetrics{
+                x_offset: 0,
+                y_offset: 0,
+                x_advance: rect.width as i32,
+            });
+            let _ = writeln!(result,
+                "char id={} x={} y={} width={} height={} xoffset={} yoffset={} xadvance={} page=0 chnl=15",
+                *c as u32, rect.x, rect.y, rect.width, rect.height, m.x_offset, m.y_offset, m.x_advance);
+        }
+        result
+    }
+}
@@ -0,0 +1,301 @@
+
+// Minimalistic CoreFoundation, CoreGraphics and CoreText bindings.
+
+#![cfg(target_os = "macos")]
+#![allow(non_upper_case_globals)]
+
+// Type definitions
+
+pub type CFIndex      = isize;
+pub type CFNumberType = CFIndex;
+pub type CGFloat      = f64;
+pub type CGGlyph      = u16;
+pub type UniChar      = u16;
+pub type UInt32       = u32;
+
+pub type VOID         = std::ffi::c_void;
+
+pub type CFTypeRef             = *const VOID;
+pub type CFAllocatorRef        = CFTypeRef;
+pub type CFDataRef             = CFTypeRef;
+pub type CFStringRef           = CFTypeRef;
+pub type CFArrayRef            = CFTypeRef;
+pub type CFDictionaryRef       = CFTypeRef;
+pub type CFNumberRef           = CFTypeRef;
+pub type CFAttributedStringRef = CFTypeRef;
+pub type CGColorSpaceRef       = CFTypeRef;
+pub type CGContextRef          = CFTypeRef;
+pub type CTFontRef             = CFTypeRef;
+pub type CTFontDescriptorRef   = CFTypeRef;
+pub type CTLineRef             = CFTypeRef;
+pub type CTRunRef              = CFTypeRef;
+
+// Constants
+
+pub const kCFNumberSInt32Type      : CFNumberType = 3;
+pub const kCFNumberFloat64Type     : CFNumberType = 6;
+pub const kCGImageAlphaNone        : UInt32       = 0;
+pub const kCTFontOrientationDefault: UInt32       = 0;
+
+// Structures
+
+// https://developer.apple.com/documentation/corefoundation/cfrange
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct CFRange {
+    pub location: CFIndex,
+    pub length  : CFIndex,
+}
+
+// https://developer.apple.com/documentation/coregraphics/cgpoint
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct CGPoint {
+    pub x: CGFloat,
+    pub y: CGFloat,
+}
+
+// https://developer.apple.com/documentation/coregraphics/cgsize
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct CGSize {
+    pub width : CGFloat,
+    pub height: CGFloat,
+}
+
+// https://developer.apple.com/documentation/coregraphics/cgrect
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct CGRect {
+    pub origin: CGPoint,
+    pub size  : CGSize ,
+}
+
+// We only ever pass the predefined callbacks by address.
+#[repr(C)]
+pub struct CFDictionaryKeyCallBacks {
+    _private: [u8; 0],
+}
+
+#[repr(C)]
+pub struct CFDictionaryValueCallBacks {
+    _private: [u8; 0],
+}
+
+/// CoreFoundation bindings.
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    pub static kCFTypeDictionaryKeyCallBacks  : CFDictionaryKeyCallBacks;
+    pub static kCFTypeDictionaryValueCallBacks: CFDictionaryValueCallBacks;
+
+    // https://developer.apple.com/documentation/corefoundation/1521153-cfrelease
+    pub fn CFRelease(
+        cf: CFTypeRef,
+    );
+
+    // https://developer.apple.com/documentation/corefoundation/1521269-cfretain
+    pub fn CFRetain(
+        cf: CFTypeRef,
+    ) -> CFTypeRef;
+
+    // https://developer.apple.com/documentation/corefoundation/1542359-cfdatacreate
+    pub fn CFDataCreate(
+        allocator: CFAllocatorRef,
+        bytes    : *const u8     ,
+        length   : CFIndex       ,
+    ) -> CFDataRef;
+
+    // https://developer.apple.com/documentation/corefoundation/1542730-cfstringcreatewithcharacters
+    pub fn CFStringCreateWithCharacters(
+        alloc   : CFAllocatorRef,
+        chars   : *const UniChar,
+        numChars: CFIndex       ,
+    ) -> CFStringRef;
+
+    // https://developer.apple.com/documentation/corefoundation/1388772-cfarraygetcount
+    pub fn CFArrayGetCount(
+        theArray: CFArrayRef,
+    ) -> CFIndex;
+
+    // https://developer.apple.com/documentation/corefoundation/1388767-cfarraygetvalueatindex
+    pub fn CFArrayGetValueAtIndex(
+        theArray: CFArrayRef,
+        idx     : CFIndex   ,
+    ) -> *const VOID;
+
+    // https://developer.apple.com/documentation/corefoundation/1516782-cfdictionarycreate
+    pub fn CFDictionaryCreate(
+        allocator     : CFAllocatorRef                    ,
+        keys          : *const *const VOID                ,
+        values        : *const *const VOID                ,
+        numValues     : CFIndex                           ,
+        keyCallBacks  : *const CFDictionaryKeyCallBacks   ,
+        valueCallBacks: *const CFDictionaryValueCallBacks ,
+    ) -> CFDictionaryRef;
+
+    // https://developer.apple.com/documentation/corefoundation/1542182-cfnumbercreate
+    pub fn CFNumberCreate(
+        allocator: CFAllocatorRef,
+        theType  : CFNumberType  ,
+        valuePtr : *const VOID   ,
+    ) -> CFNumberRef;
+
+    // https://developer.apple.com/documentation/corefoundation/1542898-cfattributedstringcreate
+    pub fn CFAttributedStringCreate(
+        alloc     : CFAllocatorRef ,
+        str       : CFStringRef    ,
+        attributes: CFDictionaryRef,
+    ) -> CFAttributedStringRef;
+}
+
+/// CoreGraphics bindings.
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    // https://developer.apple.com/documentation/coregraphics/1408837-cgcolorspacecreatedevicegray
+    pub fn CGColorSpaceCreateDeviceGray() -> CGColorSpaceRef;
+
+    // https://developer.apple.com/documentation/coregraphics/1455939-cgbitmapcontextcreate
+    pub fn CGBitmapContextCreate(
+        data            : *mut VOID      ,
+        width           : usize          ,
+        height          : usize          ,
+        bitsPerComponent: usize          ,
+        bytesPerRow     : usize          ,
+        space           : CGColorSpaceRef,
+        bitmapInfo      : UInt32         ,
+    ) -> CGContextRef;
+
+    // https://developer.apple.com/documentation/coregraphics/1455070-cgcontextsetgrayfillcolor
+    pub fn CGContextSetGrayFillColor(
+        c    : CGContextRef,
+        gray : CGFloat     ,
+        alpha: CGFloat     ,
+    );
+
+    // https://developer.apple.com/documentation/coregraphics/1454478-cgcontextsetshouldantialias
+    pub fn CGContextSetShouldAntialias(
+        c              : CGContextRef,
+        shouldAntialias: bool        ,
+    );
+}
+
+/// CoreText bindings.
+#[link(name = "CoreText", kind = "framework")]
+extern "C" {
+    pub static kCTFontAttributeName    : CFStringRef;
+    pub static kCTKernAttributeName    : CFStringRef;
+    pub static kCTLigatureAttributeName: CFStringRef;
+
+    // https://developer.apple.com/documentation/coretext/1499487-ctfontmanagercreatefontdescripto
+    pub fn CTFontManagerCreateFontDescriptorsFromData(
+        data: CFDataRef,
+    ) -> CFArrayRef;
+
+    // https://developer.apple.com/documentation/coretext/1509225-ctfontcreatewithfontdescriptor
+    pub fn CTFontCreateWithFontDescriptor(
+        descriptor: CTFontDescriptorRef,
+        size      : CGFloat            ,
+        matrix    : *const VOID        ,
+    ) -> CTFontRef;
+
+    // https://developer.apple.com/documentation/coretext/1509011-ctfontgetascent
+    pub fn CTFontGetAscent(
+        font: CTFontRef,
+    ) -> CGFloat;
+
+    // https://developer.apple.com/documentation/coretext/1510962-ctfontgetdescent
+    pub fn CTFontGetDescent(
+        font: CTFontRef,
+    ) -> CGFloat;
+
+    // https://developer.apple.com/documentation/coretext/1511150-ctfontgetleading
+    pub fn CTFontGetLeading(
+        font: CTFontRef,
+    ) -> CGFloat;
+
+    // https://developer.apple.com/documentation/coretext/1510825-ctfontgetglyphsforcharacters
+    pub fn CTFontGetGlyphsForCharacters(
+        font      : CTFontRef     ,
+        characters: *const UniChar,
+        glyphs    : *mut CGGlyph  ,
+        count     : CFIndex       ,
+    ) -> bool;
+
+    // https://developer.apple.com/documentation/coretext/1509163-ctfontgetboundingrectsforglyphs
+    pub fn CTFontGetBoundingRectsForGlyphs(
+        font         : CTFontRef     ,
+        orientation  : UInt32        ,
+        glyphs       : *const CGGlyph,
+        boundingRects: *mut CGRect   ,
+        count        : CFIndex       ,
+    ) -> CGRect;
+
+    // https://developer.apple.com/documentation/coretext/1508876-ctfontgetadvancesforglyphs
+    pub fn CTFontGetAdvancesForGlyphs(
+        font       : CTFontRef     ,
+        orientation: UInt32        ,
+        glyphs     : *const CGGlyph,
+        advances   : *mut CGSize   ,
+        count      : CFIndex       ,
+    ) -> f64;
+
+    // https://developer.apple.com/documentation/coretext/1510941-ctfontdrawglyphs
+    pub fn CTFontDrawGlyphs(
+        font     : CTFontRef      ,
+        glyphs   : *const CGGlyph ,
+        positions: *const CGPoint ,
+        count    : usize          ,
+        context  : CGContextRef   ,
+    );
+
+    // https://developer.apple.com/documentation/coretext/1511131-ctlinecreatewithattributedstring
+    pub fn CTLineCreateWithAttributedString(
+        attrString: CFAttributedStringRef,
+    ) -> CTLineRef;
+
+    // https://developer.apple.com/documentation/coretext/1509262-ctlinegetglyphruns
+    pub fn CTLineGetGlyphRuns(
+        line: CTLineRef,
+    ) -> CFArrayRef;
+
+    // https://developer.apple.com/documentation/coretext/1510273-ctlinegettypographicbounds
+    pub fn CTLineGetTypographicBounds(
+        line   : CTLineRef   ,
+        ascent : *mut CGFloat,
+        descent: *mut CGFloat,
+        leading: *mut CGFloat,
+    ) -> f64;
+
+    // https://developer.apple.com/documentation/coretext/1509629-ctlinegetoffsetforstringindex
+    pub fn CTLineGetOffsetForStringIndex(
+        line           : CTLineRef   ,
+        charIndex      : CFIndex     ,
+        secondaryOffset: *mut CGFloat,
+    ) -> CGFloat;
+
+    // https://developer.apple.com/documentation/coretext/1510591-ctrungetglyphcount
+    pub fn CTRunGetGlyphCount(
+        run: CTRunRef,
+    ) -> CFIndex;
+
+    // https://developer.apple.com/documentation/coretext/1511351-ctrungetglyphs
+    pub fn CTRunGetGlyphs(
+        run   : CTRunRef    ,
+        range : CFRange     ,
+        buffer: *mut CGGlyph,
+    );
+
+    // https://developer.apple.com/documentation/coretext/1509352-ctrungetpositions
+    pub fn CTRunGetPositions(
+        run   : CTRunRef    ,
+        range : CFRange     ,
+        buffer: *mut CGPoint,
+    );
+
+    // https://developer.apple.com/documentation/coretext/1509875-ctrungetstringindices
+    pub fn CTRunGetStringIndices(
+        run   : CTRunRef    ,
+        range : CFRange     ,
+        buffer: *mut CFIndex,
+    );
+}
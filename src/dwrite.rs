@@ -0,0 +1,231 @@
+
+// Minimalistic DirectWrite bindings, only what's needed for text shaping.
+// COM interfaces are laid out as a pointer to their vtable. Vtable slots that
+// we don't call are just padded out.
+
+#![cfg(all(target_os = "windows", feature = "directwrite"))]
+#![allow(non_snake_case, non_camel_case_types, non_upper_case_globals)]
+
+use crate::winapi::*;
+
+pub type HRESULT   = i32;
+pub type ULONG     = u32;
+pub type UINT16    = u16;
+pub type UINT32    = u32;
+pub type FLOAT     = f32;
+
+pub const S_OK                        : HRESULT = 0;
+pub const E_NOINTERFACE               : HRESULT = 0x80004002u32 as HRESULT;
+pub const DWRITE_FACTORY_TYPE_SHARED  : UINT32  = 0;
+pub const DWRITE_READING_DIRECTION_LTR: UINT32  = 0;
+
+/// Creates the tag of an OpenType feature from it's 4 characters.
+pub const fn dwrite_make_tag(tag: [u8; 4]) -> UINT32 {
+    (tag[0] as UINT32) | ((tag[1] as UINT32) << 8) | ((tag[2] as UINT32) << 16) | ((tag[3] as UINT32) << 24)
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/guiddef/ns-guiddef-guid
+#[repr(C)]
+pub struct GUID {
+    pub Data1: u32    ,
+    pub Data2: u16    ,
+    pub Data3: u16    ,
+    pub Data4: [u8; 8],
+}
+
+// {b859ee5a-d838-4b5b-a2e8-1adc7d93db48}
+pub const IID_IDWriteFactory: GUID = GUID{
+    Data1: 0xb859ee5a,
+    Data2: 0xd838,
+    Data3: 0x4b5b,
+    Data4: [0xa2, 0xe8, 0x1a, 0xdc, 0x7d, 0x93, 0xdb, 0x48],
+};
+
+// DWrite bindings.
+#[link(name = "dwrite")]
+extern "system" {
+    // https://docs.microsoft.com/en-us/windows/win32/api/dwrite/nf-dwrite-dwritecreatefactory
+    pub fn DWriteCreateFactory(
+        factoryType: UINT32          ,
+        iid        : *const GUID     ,
+        factory    : *mut *mut IUnknown,
+    ) -> HRESULT;
+}
+
+// Structures
+
+// https://docs.microsoft.com/en-us/windows/win32/api/dwrite/ns-dwrite-dwrite_script_analysis
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct DWRITE_SCRIPT_ANALYSIS {
+    pub script: UINT16,
+    pub shapes: UINT32,
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/dwrite/ns-dwrite-dwrite_font_feature
+#[repr(C)]
+pub struct DWRITE_FONT_FEATURE {
+    pub nameTag  : UINT32,
+    pub parameter: UINT32,
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/dwrite/ns-dwrite-dwrite_typographic_features
+#[repr(C)]
+pub struct DWRITE_TYPOGRAPHIC_FEATURES {
+    pub features    : *mut DWRITE_FONT_FEATURE,
+    pub featureCount: UINT32                  ,
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/dwrite/ns-dwrite-dwrite_glyph_offset
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+pub struct DWRITE_GLYPH_OFFSET {
+    pub advanceOffset : FLOAT,
+    pub ascenderOffset: FLOAT,
+}
+
+// Both of these are bitfields that we only pass around.
+pub type DWRITE_SHAPING_TEXT_PROPERTIES  = UINT16;
+pub type DWRITE_SHAPING_GLYPH_PROPERTIES = UINT16;
+
+// Interfaces
+
+// https://docs.microsoft.com/en-us/windows/win32/api/unknwn/nn-unknwn-iunknown
+#[repr(C)]
+pub struct IUnknown {
+    pub vtbl: *const IUnknownVtbl,
+}
+
+#[repr(C)]
+pub struct IUnknownVtbl {
+    pub QueryInterface: unsafe extern "system" fn(*mut IUnknown, *const GUID, *mut PVOID) -> HRESULT,
+    pub AddRef        : unsafe extern "system" fn(*mut IUnknown) -> ULONG,
+    pub Release       : unsafe extern "system" fn(*mut IUnknown) -> ULONG,
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/dwrite/nn-dwrite-idwritefactory
+#[repr(C)]
+pub struct IDWriteFactory {
+    pub vtbl: *const IDWriteFactoryVtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteFactoryVtbl {
+    pub base              : IUnknownVtbl,
+    _unused0              : [PVOID; 14] ,
+    pub GetGdiInterop     : unsafe extern "system" fn(*mut IDWriteFactory, *mut *mut IDWriteGdiInterop) -> HRESULT,
+    _unused1              : [PVOID; 3]  ,
+    pub CreateTextAnalyzer: unsafe extern "system" fn(*mut IDWriteFactory, *mut *mut IDWriteTextAnalyzer) -> HRESULT,
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/dwrite/nn-dwrite-idwritegdiinterop
+#[repr(C)]
+pub struct IDWriteGdiInterop {
+    pub vtbl: *const IDWriteGdiInteropVtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteGdiInteropVtbl {
+    pub base                 : IUnknownVtbl,
+    _unused0                 : [PVOID; 3]  ,
+    pub CreateFontFaceFromHdc: unsafe extern "system" fn(*mut IDWriteGdiInterop, HDC, *mut *mut IDWriteFontFace) -> HRESULT,
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/dwrite/nn-dwrite-idwritefontface
+#[repr(C)]
+pub struct IDWriteFontFace {
+    pub vtbl: *const IUnknownVtbl,
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/dwrite/nn-dwrite-idwritetextanalyzer
+#[repr(C)]
+pub struct IDWriteTextAnalyzer {
+    pub vtbl: *const IDWriteTextAnalyzerVtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteTextAnalyzerVtbl {
+    pub base: IUnknownVtbl,
+    pub AnalyzeScript: unsafe extern "system" fn(
+        this        : *mut IDWriteTextAnalyzer       ,
+        source      : *mut IDWriteTextAnalysisSource ,
+        textPosition: UINT32                         ,
+        textLength  : UINT32                         ,
+        sink        : *mut IDWriteTextAnalysisSink   ,
+    ) -> HRESULT,
+    _unused0: [PVOID; 3],
+    pub GetGlyphs: unsafe extern "system" fn(
+        this               : *mut IDWriteTextAnalyzer                   ,
+        textString         : *const WCHAR                               ,
+        textLength         : UINT32                                     ,
+        fontFace           : *mut IDWriteFontFace                       ,
+        isSideways         : BOOL                                       ,
+        isRightToLeft      : BOOL                                       ,
+        scriptAnalysis     : *const DWRITE_SCRIPT_ANALYSIS              ,
+        localeName         : *const WCHAR                               ,
+        numberSubstitution : *mut IUnknown                              ,
+        features           : *const *const DWRITE_TYPOGRAPHIC_FEATURES  ,
+        featureRangeLengths: *const UINT32                              ,
+        featureRanges      : UINT32                                     ,
+        maxGlyphCount      : UINT32                                     ,
+        clusterMap         : *mut UINT16                                ,
+        textProps          : *mut DWRITE_SHAPING_TEXT_PROPERTIES        ,
+        glyphIndices       : *mut UINT16                                ,
+        glyphProps         : *mut DWRITE_SHAPING_GLYPH_PROPERTIES       ,
+        actualGlyphCount   : *mut UINT32                                ,
+    ) -> HRESULT,
+    pub GetGlyphPlacements: unsafe extern "system" fn(
+        this               : *mut IDWriteTextAnalyzer                   ,
+        textString         : *const WCHAR                               ,
+        clusterMap         : *const UINT16                              ,
+        textProps          : *mut DWRITE_SHAPING_TEXT_PROPERTIES        ,
+        textLength         : UINT32                                     ,
+        glyphIndices       : *const UINT16                              ,
+        glyphProps         : *const DWRITE_SHAPING_GLYPH_PROPERTIES     ,
+        glyphCount         : UINT32                                     ,
+        fontFace           : *mut IDWriteFontFace                       ,
+        fontEmSize         : FLOAT                                      ,
+        isSideways         : BOOL                                       ,
+        isRightToLeft      : BOOL                                       ,
+        scriptAnalysis     : *const DWRITE_SCRIPT_ANALYSIS              ,
+        localeName         : *const WCHAR                               ,
+        features           : *const *const DWRITE_TYPOGRAPHIC_FEATURES  ,
+        featureRangeLengths: *const UINT32                              ,
+        featureRanges      : UINT32                                     ,
+        glyphAdvances      : *mut FLOAT                                 ,
+        glyphOffsets       : *mut DWRITE_GLYPH_OFFSET                   ,
+    ) -> HRESULT,
+}
+
+// Interfaces implemented on our side, passed to the analyzer.
+
+// https://docs.microsoft.com/en-us/windows/win32/api/dwrite/nn-dwrite-idwritetextanalysissource
+#[repr(C)]
+pub struct IDWriteTextAnalysisSource {
+    pub vtbl: *const IDWriteTextAnalysisSourceVtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteTextAnalysisSourceVtbl {
+    pub base                        : IUnknownVtbl,
+    pub GetTextAtPosition           : unsafe extern "system" fn(*mut IDWriteTextAnalysisSource, UINT32, *mut *const WCHAR, *mut UINT32) -> HRESULT,
+    pub GetTextBeforePosition       : unsafe extern "system" fn(*mut IDWriteTextAnalysisSource, UINT32, *mut *const WCHAR, *mut UINT32) -> HRESULT,
+    pub GetParagraphReadingDirection: unsafe extern "system" fn(*mut IDWriteTextAnalysisSource) -> UINT32,
+    pub GetLocaleName               : unsafe extern "system" fn(*mut IDWriteTextAnalysisSource, UINT32, *mut UINT32, *mut *const WCHAR) -> HRESULT,
+    pub GetNumberSubstitution       : unsafe extern "system" fn(*mut IDWriteTextAnalysisSource, UINT32, *mut UINT32, *mut *mut IUnknown) -> HRESULT,
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/dwrite/nn-dwrite-idwritetextanalysissink
+#[repr(C)]
+pub struct IDWriteTextAnalysisSink {
+    pub vtbl: *const IDWriteTextAnalysisSinkVtbl,
+}
+
+#[repr(C)]
+pub struct IDWriteTextAnalysisSinkVtbl {
+    pub base                 : IUnknownVtbl,
+    pub SetScriptAnalysis    : unsafe extern "system" fn(*mut IDWriteTextAnalysisSink, UINT32, UINT32, *const DWRITE_SCRIPT_ANALYSIS) -> HRESULT,
+    pub SetLineBreakpoints   : unsafe extern "system" fn(*mut IDWriteTextAnalysisSink, UINT32, UINT32, PVOID) -> HRESULT,
+    pub SetBidiLevel         : unsafe extern "system" fn(*mut IDWriteTextAnalysisSink, UINT32, UINT32, u8, u8) -> HRESULT,
+    pub SetNumberSubstitution: unsafe extern "system" fn(*mut IDWriteTextAnalysisSink, UINT32, UINT32, *mut IUnknown) -> HRESULT,
+}
@@ -1,10 +1,13 @@
 
 // Errors thrown by the library.
 
+use alloc::string::String;
+
 /// All the different possible errors.
 #[derive(Debug)]
 pub enum Error {
     /// Standard Rust IO error.
+    #[cfg(feature = "std")]
     IoError(std::io::Error),
     /// Something's wrong with the passed in format.
     FormatError(String),
@@ -14,4 +17,7 @@ pub enum Error {
     UserError(String),
     /// The glyph could not be found.
     GlyphNotFound(char),
+    /// The input is valid, but uses a feature that isn't implemented yet,
+    /// like a font format or table the parser doesn't understand.
+    Unsupported(&'static str),
 }
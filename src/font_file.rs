@@ -1,38 +1,153 @@
 
 // Common font abstraction between font file types.
 
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use alloc::format;
+use alloc::vec;
 use crate::ttf::TtfFile;
 use crate::{Result, Error};
 
+/// The file formats fonts come in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FontFormat {
+    /// TrueType font, with TrueType outlines.
+    Ttf,
+    /// OpenType font, with CFF outlines.
+    Otf,
+    /// TrueType collection, multiple fonts in a single file.
+    Ttc,
+    /// Web Open Font Format, a compressed TrueType or OpenType font.
+    Woff,
+    /// Web Open Font Format 2.0, a better compressed version of WOFF.
+    Woff2,
+}
+
+impl FontFormat {
+    /// Guesses the format from the tag at the start of the file.
+    fn detect(bytes: &[u8]) -> Option<Self> {
+        match bytes.get(0..4)? {
+            [0x00, 0x01, 0x00, 0x00] | b"true" => Some(Self::Ttf),
+            b"OTTO" => Some(Self::Otf),
+            b"ttcf" => Some(Self::Ttc),
+            b"wOFF" => Some(Self::Woff),
+            b"wOF2" => Some(Self::Woff2),
+            _ => None,
+        }
+    }
+
+    /// Returns the usual extension of files in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Ttf => "ttf",
+            Self::Otf => "otf",
+            Self::Ttc => "ttc",
+            Self::Woff => "woff",
+            Self::Woff2 => "woff2",
+        }
+    }
+}
+
 /// Represents font file metadata in a platform-independent way.
 pub(crate) struct FontFile {
-    pub(crate) extension : String     ,
-    pub(crate) face_names: Vec<String>,
+    pub(crate) format      : FontFormat  ,
+    pub(crate) face_names  : Vec<String> ,
+    // Every full name of the faces, including the localized ones
+    pub(crate) face_aliases: Vec<String> ,
+    pub(crate) ttf         : Arc<TtfFile>,
 }
 
 impl FontFile {
     /// Creates the metadata by parsing a slice of bytes. The parser tries to
     /// guess the correct format.
     pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let format = match FontFormat::detect(bytes) {
+            // The tag is not checked strictly, anything unknown is tried as TTF
+            None | Some(FontFormat::Ttf) => FontFormat::Ttf,
+            Some(FontFormat::Otf) => FontFormat::Otf,
+            Some(FontFormat::Ttc) => return Err(Error::Unsupported("TrueType collections")),
+            Some(FontFormat::Woff) => return Err(Error::Unsupported("WOFF fonts")),
+            Some(FontFormat::Woff2) => return Err(Error::Unsupported("WOFF2 fonts")),
+        };
         // Try TTF
         if let Ok(ttf) = TtfFile::parse(bytes) {
-            if let Some(names) = ttf.name(4) {
-                return Ok(Self{
-                    extension: "ttf".into(),
-                    face_names: names.iter().cloned().collect(),
-                });
-            }
+            // A TTF describes a single face, named by its family and
+            // subfamily, the full names are localized variants of the same name
+            let face_name = match Self::typographic_name(&ttf) {
+                Some(name) => name,
+                // Subset fonts might not have a family name, or any names at all
+                None => ttf.preferred_name(4)
+                    .or_else(|| ttf.preferred_name(6))
+                    .map(String::from)
+                    .unwrap_or_else(|| "Unknown-0".into()),
+            };
+            let face_aliases = ttf.name(4)
+                .map(|aliases| aliases.iter().cloned().collect())
+                .unwrap_or_default();
+            return Ok(Self{
+                format,
+                face_names: vec![face_name],
+                face_aliases,
+                ttf: Arc::new(ttf),
+            });
         }
         Err(Error::FormatError("Unrecognized format of byte sequence!".into()))
     }
 
+    /// Verifies the integrity of the font file using the checksums stored in
+    /// it.
+    #[cfg(feature = "std")]
+    pub(crate) fn verify_checksums(bytes: &[u8]) -> Result<()> {
+        TtfFile::verify_checksums(bytes).map_err(Error::FormatError)
+    }
+
+    /// Returns the name of the face from its typographic family and
+    /// subfamily names, falling back to the legacy ones.
+    fn typographic_name(ttf: &TtfFile) -> Option<String> {
+        let family = ttf.preferred_name(16).or_else(|| ttf.preferred_name(1))?;
+        let subfamily = ttf.preferred_name(17).or_else(|| ttf.preferred_name(2));
+        Some(match subfamily {
+            Some(subfamily) => format!("{} {}", family, subfamily),
+            None => family.into(),
+        })
+    }
+
+    /// Returns the face name that the given face name or alias refers to.
+    #[cfg(feature = "std")]
+    pub(crate) fn resolve_face(&self, name: &str) -> Option<&str> {
+        if self.face_names.iter().any(|n| n == name) || self.face_aliases.iter().any(|n| n == name) {
+            // There's only a single face
+            return self.face_names.first().map(|n| n.as_str());
+        }
+        None
+    }
+
+    /// Returns the detected format of the file.
+    pub(crate) fn format(&self) -> FontFormat {
+        self.format
+    }
+
     /// Returns the appropriate extension name for this font type.
+    #[cfg(all(feature = "std", target_os = "windows"))]
     pub(crate) fn extension(&self) -> &str {
-        &self.extension
+        self.format.extension()
     }
 
     /// Returns the font face names stored in this font.
     pub(crate) fn face_names(&self) -> &[String] {
         &self.face_names
     }
+
+    /// Returns every full name of the faces stored in this font, including the
+    /// localized ones.
+    pub(crate) fn face_aliases(&self) -> &[String] {
+        &self.face_aliases
+    }
+
+    /// Returns the parsed font tables, shared with the faces of this font.
+    pub(crate) fn ttf(&self) -> &Arc<TtfFile> {
+        &self.ttf
+    }
 }
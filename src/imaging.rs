@@ -0,0 +1,86 @@
+
+// Integration with the image crate, to dump rasterized glyphs and atlases
+// directly.
+
+#![cfg(feature = "image")]
+
+use std::collections::HashMap;
+use image::{GrayImage, RgbaImage, Luma, ImageFormat, FilterType};
+use crate::{RasterizedGlyph, ColoredGlyph, GlyphPack, ScaledFontFace, FontBackend, ScaledFaceBackend, Result, Error, flip_rows};
+
+impl RasterizedGlyph {
+    /// Copies the bitmap of the glyph into a grayscale image.
+    pub fn to_image_buffer(&self) -> GrayImage {
+        GrayImage::from_fn(self.width as u32, self.height as u32,
+            |x, y| Luma([self.coverage(x as usize, y as usize)]))
+    }
+}
+
+impl ColoredGlyph {
+    /// Copies the bitmap of the glyph into an RGBA image.
+    pub fn to_image_buffer(&self) -> RgbaImage {
+        RgbaImage::from_raw(self.width as u32, self.height as u32, self.data.to_vec())
+            .expect("Glyph data does not match its dimensions!")
+    }
+}
+
+impl <B: FontBackend> ScaledFontFace<B> {
+    /// Rasterizes the given character from the color bitmaps of the font, see
+    /// `FontFace::color_bitmap_sizes`. The bitmap made for the closest size is
+    /// decoded and scaled to the size of the face.
+    pub fn rasterize_color_glyph(&mut self, c: char) -> Result<ColoredGlyph> {
+        let ttf = &self.face.ttf;
+        let glyph_id = ttf.glyph_index(c).ok_or(Error::GlyphNotFound(c))?;
+        let em_size = self.inner.em_size();
+        let (ppem, bitmap) = ttf.color_bitmap(glyph_id, em_size).ok_or(Error::GlyphNotFound(c))?;
+        let format = match &bitmap.format {
+            b"png " => ImageFormat::PNG,
+            b"jpg " => ImageFormat::JPEG,
+            b"tiff" => ImageFormat::TIFF,
+            _ => return Err(Error::Unsupported("Color bitmaps other than PNG, JPEG and TIFF")),
+        };
+        let image = image::load_from_memory_with_format(bitmap.data, format)
+            .map_err(|e| Error::FormatError(format!("Malformed color bitmap for glyph {}: {}!", glyph_id, e)))?
+            .to_rgba();
+        let scale = em_size / ppem as f64;
+        let width = ((image.width() as f64 * scale).round() as u32).max(1);
+        let height = ((image.height() as f64 * scale).round() as u32).max(1);
+        let image = image::imageops::resize(&image, width, height, FilterType::Triangle);
+        // The origin is relative to the baseline and grows upwards
+        let x_offset = (bitmap.origin.0 as f64 * scale).round() as i32;
+        let bottom = (bitmap.origin.1 as f64 * scale).round() as i32;
+        let mut data = image.into_raw();
+        if self.flip_y {
+            flip_rows(&mut data, width as usize * 4);
+        }
+        Ok(ColoredGlyph{
+            character: c,
+            x_offset,
+            y_offset: self.inner.ascent() - bottom - height as i32,
+            width: width as usize,
+            height: height as usize,
+            cell: self.cell(Some(glyph_id)),
+            data: data.into_boxed_slice(),
+        })
+    }
+}
+
+impl GlyphPack {
+    /// Composes the packed glyphs into a single grayscale image, with each
+    /// glyph drawn to its packed position. Glyphs missing from `glyphs` are
+    /// left blank.
+    pub fn to_image_buffer(&self, glyphs: &HashMap<char, RasterizedGlyph>) -> GrayImage {
+        let mut result = GrayImage::new(self.width() as u32, self.height() as u32);
+        for (c, rect) in self {
+            if let Some(glyph) = glyphs.get(c) {
+                for y in 0..glyph.height {
+                    for x in 0..glyph.width {
+                        let pixel = glyph.coverage(x, y);
+                        result.put_pixel((rect.x + x) as u32, (rect.y + y) as u32, Luma([pixel]));
+                    }
+                }
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,73 @@
+
+// Text shaped once and kept around, so it can be measured, hit-tested and
+// drawn repeatedly without shaping it again.
+
+use crate::{FontBackend, ScaledFaceBackend, ScaledFontFace, GlyphPositioning, ShapeOptions, DefaultBackend, Result};
+
+/// Text laid out with a scaled font face, see `shape_text`.
+pub struct TextLayout<'a, B: FontBackend = DefaultBackend> {
+    face  : &'a ScaledFontFace<B>,
+    glyphs: Vec<GlyphPositioning>,
+    // The caret position after the last character
+    end   : (usize, i32, i32)    ,
+    size  : (i32, i32)           ,
+}
+
+impl <'a, B: FontBackend> TextLayout<'a, B> {
+    /// Shapes the text with the given face.
+    pub fn new(face: &'a ScaledFontFace<B>, text: &str, options: ShapeOptions) -> Result<Self> {
+        // The extra newline reports the caret position at the end of the text
+        let mut glyphs = Vec::new();
+        face.shape_text_horizontal(&format!("{}\n", text), options, |g| glyphs.push(g))?;
+        let last = glyphs.pop().expect("The newline must be reported!");
+        let width = glyphs.iter()
+            .filter(|g| g.character == '\n')
+            .map(|g| g.x)
+            .chain(Some(last.x))
+            .max()
+            .unwrap_or(0);
+        let height = if text.is_empty() { 0 } else { last.caret_y + face.inner.line_height() };
+        Ok(Self{
+            face,
+            glyphs,
+            end: (last.index, last.caret_x, last.caret_y),
+            size: (width, height),
+        })
+    }
+
+    /// Returns the face the text got laid out with.
+    pub fn face(&self) -> &'a ScaledFontFace<B> {
+        self.face
+    }
+
+    /// Returns the width and height of the laid out text.
+    pub fn size(&self) -> (i32, i32) {
+        self.size
+    }
+
+    /// Returns the positioned glyphs, in the order `shape_text` reports them.
+    pub fn glyphs(&self) -> &[GlyphPositioning] {
+        &self.glyphs
+    }
+
+    /// Returns the index of the character the caret should be placed before
+    /// for the given point, which is the number of characters if it should be
+    /// placed after the last one. The line is picked by `y`, then the closest
+    /// caret position on it by `x`.
+    pub fn hit_test(&self, x: i32, y: i32) -> usize {
+        let carets = self.glyphs.iter()
+            .map(|g| (g.index, g.caret_x, g.caret_y))
+            .chain(Some(self.end));
+        // The last line starting above the point, or the first one
+        let line_y = carets.clone()
+            .map(|(_, _, cy)| cy)
+            .filter(|cy| *cy <= y)
+            .max()
+            .unwrap_or(0);
+        carets
+            .filter(|(_, _, cy)| *cy == line_y)
+            .min_by_key(|(_, cx, _)| (cx - x).abs())
+            .map(|(index, _, _)| index)
+            .unwrap_or(self.end.0)
+    }
+}
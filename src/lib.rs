@@ -1,79 +1,2300 @@
 
+// The font parsing and packing core only needs an allocator, the platform
+// rendering layer is only available with the "std" feature.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 mod error;
 #[macro_use] mod parse;
-mod ttf;
+// Without the rendering layer only the names of the faces are read from the
+// tables
+#[cfg_attr(not(feature = "std"), allow(dead_code))] mod ttf;
 mod font_file;
-mod winapi;
-mod win32;
+#[cfg(feature = "std")] mod backend;
+#[cfg(feature = "std")] mod winapi;
+#[cfg(all(feature = "std", feature = "directwrite"))] mod dwrite;
+#[cfg(feature = "std")] mod win32;
+#[cfg(feature = "std")] mod web;
+#[cfg(feature = "std")] mod coretext;
+#[cfg(feature = "std")] mod macos;
 mod pack;
-use std::ops::{BitOr, BitOrAssign, BitAnd, BitAndAssign, BitXor, BitXorAssign, Not};
-use pack::PackResult;
+mod outline;
+#[cfg(feature = "std")] mod bmfont;
+#[cfg(feature = "std")] mod imaging;
+#[cfg(feature = "std")] mod sdf;
+#[cfg(feature = "std")] mod blur;
+#[cfg(feature = "std")] mod atlas;
+#[cfg(feature = "std")] mod layout;
+use core::ops::{BitOr, BitOrAssign, BitAnd, BitAndAssign, BitXor, BitXorAssign, Not};
+#[cfg(feature = "std")]
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+#[cfg(feature = "std")]
+use std::collections::hash_map::Entry;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
 
 pub use error::Error;
-pub type Result<T> = std::result::Result<T, Error>;
-pub use pack::Rect;
+pub use font_file::FontFormat;
+#[cfg(feature = "std")]
+pub use backend::{FontBackend, ScaledFaceBackend, ScaleSettings};
+pub type Result<T> = core::result::Result<T, Error>;
+pub use pack::{Rect, PackResult, PackOptions, PackStrategy};
+pub use outline::{Outline, Contour, PathSegment};
 pub type GlyphPack = PackResult<char>;
+#[cfg(feature = "std")]
+pub use atlas::{Atlas, AtlasOptions};
+#[cfg(feature = "std")]
+pub use layout::TextLayout;
 
 // Import underlying types.
-#[cfg(target_os = "windows")]
+#[cfg(all(feature = "std", target_os = "windows"))]
 mod itypes {
     use crate::win32;
 
-    pub type FontImpl           = win32::Win32Font;
-    pub type FontFaceImpl       = win32::Win32FontFace;
-    pub type ScaledFontFaceImpl = win32::Win32ScaledFontFace;
+    pub type FontImpl = win32::Win32Font;
+}
+
+#[cfg(all(feature = "std", target_arch = "wasm32"))]
+mod itypes {
+    use crate::web;
+
+    pub type FontImpl = web::WebFont;
+}
+
+#[cfg(all(feature = "std", target_os = "macos"))]
+mod itypes {
+    use crate::macos;
+
+    pub type FontImpl = macos::CoreTextFont;
+}
+
+/// The rendering backend of the current platform.
+#[cfg(feature = "std")]
+pub type DefaultBackend = itypes::FontImpl;
+
+// Here we lay out a platform-independent wrapper-type over the backends, that
+// implements everything that doesn't need the platform.
+//
+// Thread-safety is also decided here instead of in the backends: `Font` and
+// `FontFace` only refer to process-wide resources, so they can be shared
+// between threads, but `ScaledFontFace` owns system rendering resources (like
+// a GDI Device Context) that must stay on the thread that created them.
+
+/// Font information parsed from a font file, without any of the platform
+/// rendering resources. Unlike `Font`, this is available without the "std"
+/// feature.
+pub struct FontInfo(font_file::FontFile);
+
+impl FontInfo {
+    /// Parses the binary contents of a font file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        Ok(Self(font_file::FontFile::from_bytes(bytes)?))
+    }
+
+    /// Returns list of face names contained in this file, one for each
+    /// face, made of its typographic family and subfamily names.
+    pub fn face_names(&self) -> &[String] {
+        self.0.face_names()
+    }
+
+    /// Returns every full name of the faces in this file, including the
+    /// localized ones. These are accepted as face names too.
+    pub fn face_aliases(&self) -> &[String] {
+        self.0.face_aliases()
+    }
+
+    /// Returns the format the font file was detected to be in.
+    pub fn format(&self) -> FontFormat {
+        self.0.format()
+    }
+
+    /// Returns the number of font design units in an em.
+    pub fn units_per_em(&self) -> u16 {
+        self.0.ttf().units_per_em()
+    }
+
+    /// Returns the parsed font file.
+    #[cfg(feature = "std")]
+    pub(crate) fn file(&self) -> &font_file::FontFile {
+        &self.0
+    }
+}
+
+/// A font installed on the system, see `system_fonts`.
+#[cfg(all(feature = "std", target_os = "windows"))]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SystemFontInfo {
+    /// The name of the font family, that `Font::from_system_name` accepts.
+    pub family: String,
+    /// The name of the style within the family, like "Bold Italic".
+    pub style: String,
 }
 
-// Here we lay out a platform-independent wrapper-type just to make sure all
-// interfaces match.
+/// Lists the TrueType and OpenType fonts installed on the system, sorted by
+/// family and style.
+#[cfg(all(feature = "std", target_os = "windows"))]
+pub fn system_fonts() -> Result<Vec<SystemFontInfo>> {
+    win32::system_fonts()
+}
+
+/// Marker to opt out of `Send` and `Sync`.
+#[cfg(feature = "std")]
+type NotThreadSafe = PhantomData<*mut ()>;
 
 /// Represents a loaded font file resource that contains one or more font faces.
-pub struct Font(itypes::FontImpl);
+///
+/// Fonts of the default backend are `Send` and `Sync`. Clones share the
+/// loaded resource, so cloning is cheap and the font file is only released
+/// when the last clone is dropped.
+#[cfg(feature = "std")]
+pub struct Font<B: FontBackend = DefaultBackend>(Arc<B>);
 
+#[cfg(feature = "std")]
+impl <B: FontBackend> Clone for Font<B> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+#[cfg(feature = "std")]
 impl Font {
     /// Parses the binary contents of a font file.
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        Ok(Self(itypes::FontImpl::from_bytes(bytes)?))
+        Ok(Self::from_backend(DefaultBackend::from_bytes(bytes)?))
+    }
+
+    /// Parses the binary contents of a font file like `from_bytes`, but first
+    /// verifies the checksums of the font tables and the whole file. This is
+    /// opt-in, as plenty of fonts in use have sloppy checksums.
+    pub fn from_bytes_verified(bytes: &[u8]) -> Result<Self> {
+        font_file::FontFile::verify_checksums(bytes)?;
+        Self::from_bytes(bytes)
+    }
+
+    /// Reads the whole stream and parses it as a font file.
+    pub fn from_reader<R: std::io::Read>(mut reader: R) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(Error::IoError)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Loads the font file at the given path.
+    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(Error::IoError)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Loads the font file of the regular style of an installed font family,
+    /// see `system_fonts`. The family name is matched case-insensitively. If
+    /// the family is part of a font collection, only its face is loaded.
+    #[cfg(target_os = "windows")]
+    pub fn from_system_name(family: &str) -> Result<Self> {
+        Self::from_bytes(&win32::system_font_bytes(family)?)
+    }
+}
+
+#[cfg(feature = "std")]
+impl <B: FontBackend> Font<B> {
+    /// Wraps a font loaded by a custom backend.
+    pub fn from_backend(backend: B) -> Self {
+        Self(Arc::new(backend))
     }
 
-    /// Returns list of face names contained in this file.
+    /// Returns list of face names contained in this file, one for each
+    /// face, made of its typographic family and subfamily names. Faces of
+    /// fonts without names get a made-up one, like "Unknown-0", which the
+    /// Windows backend can't select the font by, so scaling them fails there.
     pub fn face_names(&self) -> &[String] {
-        self.0.face_names()
+        self.0.info().face_names()
+    }
+
+    /// Returns every full name of the faces in this file, including the
+    /// localized ones. These are accepted as face names too.
+    pub fn face_aliases(&self) -> &[String] {
+        self.0.info().face_aliases()
+    }
+
+    /// Returns the format the font file was detected to be in.
+    pub fn format(&self) -> FontFormat {
+        self.0.info().format()
     }
 
     /// Returns a face object based on a face name.
-    pub fn face(&self, name: &str) -> Result<FontFace> {
-        Ok(FontFace(self.0.face(name)?))
+    pub fn face(&self, name: &str) -> Result<FontFace<B>> {
+        Ok(FontFace{
+            inner: self.0.face(name)?,
+            ttf: self.0.info().file().ttf().clone(),
+        })
     }
 }
 
 /// Represents a single font face selected from a font file.
-pub struct FontFace(itypes::FontFaceImpl);
+///
+/// Font faces of the default backend are `Send` and `Sync`, so a face can be
+/// scaled on any thread. Clones are cheap, and faces of the default backend
+/// keep their font file loaded, so they can outlive the `Font` they came
+/// from.
+#[cfg(feature = "std")]
+pub struct FontFace<B: FontBackend = DefaultBackend> {
+    inner: B::Face          ,
+    ttf  : Arc<ttf::TtfFile>,
+}
+
+#[cfg(feature = "std")]
+impl <B: FontBackend> Clone for FontFace<B> {
+    fn clone(&self) -> Self {
+        Self{
+            inner: self.inner.clone(),
+            ttf: self.ttf.clone(),
+        }
+    }
+}
 
-impl FontFace {
+#[cfg(feature = "std")]
+impl <B: FontBackend> FontFace<B> {
     /// Scales the font face to a given size.
-    pub fn scale(&self, pts: f64, dpi: f64) -> Result<ScaledFontFace> {
-        Ok(ScaledFontFace(self.0.scale(pts, dpi)?))
+    pub fn scale(&self, pts: f64, dpi: f64) -> Result<ScaledFontFace<B>> {
+        self.scaler(pts, dpi).build()
+    }
+
+    /// Scales the font face to a given size for the device described by the
+    /// context.
+    pub fn scale_in(&self, pts: f64, ctx: &ScaleContext) -> Result<ScaledFontFace<B>> {
+        self.scale(pts, ctx.dpi)
+    }
+
+    /// Scales the font face so its em square is `pixels` pixels tall, for
+    /// callers that think in pixel sizes instead of points, like games and
+    /// UIs.
+    pub fn scale_px(&self, pixels: f64) -> Result<ScaledFontFace<B>> {
+        self.scaler_px(pixels).build()
+    }
+
+    /// Returns a builder to scale the font face to a pixel size, like
+    /// `scale_px`, with non-default rendering settings.
+    pub fn scaler_px(&self, pixels: f64) -> ScaleBuilder<'_, B> {
+        // A point is a pixel at this resolution
+        const POINTS_PER_INCH: f64 = 72.0;
+        self.scaler(pixels, POINTS_PER_INCH)
+    }
+
+    /// Returns a builder to scale the font face to a given size with
+    /// non-default rendering settings.
+    pub fn scaler(&self, pts: f64, dpi: f64) -> ScaleBuilder<'_, B> {
+        ScaleBuilder{
+            face: self,
+            pts,
+            dpi,
+            quality: RenderQuality::default(),
+            colors: RenderColors::default(),
+            gamma: 1.0,
+            trim: true,
+            flip_y: false,
+            escapement: 0,
+            stretch: 1.0,
+            gasp: false,
+            variations: Vec::new(),
+            context: None,
+        }
+    }
+
+    /// Returns true if the font is fixed-pitch, meaning that every glyph has
+    /// the same advance width.
+    pub fn is_monospace(&self) -> bool {
+        self.ttf.is_fixed_pitch()
+    }
+
+    /// Returns the horizontal advance of the given character in font design
+    /// units, or None if the character has no glyph.
+    pub fn horizontal_advance(&self, c: char) -> Option<u16> {
+        let ttf = &self.ttf;
+        ttf.horizontal_advance(ttf.glyph_index(c)?)
+    }
+
+    /// Returns the number of glyphs in the font face. Valid glyph indices are
+    /// below this.
+    pub fn glyph_count(&self) -> u16 {
+        self.ttf.glyph_count()
+    }
+
+    /// Returns the byte range of the outline data of the glyph with the given
+    /// index in the 'glyf' table, or None if the glyph doesn't exist or the
+    /// font has no TrueType outlines. An empty range means the glyph has no
+    /// outline, like the space, so there's nothing to rasterize.
+    pub fn glyph_data_range(&self, glyph_id: u16) -> Option<core::ops::Range<usize>> {
+        self.ttf.glyph_data_range(glyph_id)
+    }
+
+    /// Returns the bytes of the table with the given tag, like "GSUB", so tables
+    /// the crate doesn't interpret can be read too. Tags are always 4
+    /// characters, including trailing spaces like "cvt ".
+    pub fn raw_table(&self, tag: &str) -> Option<&[u8]> {
+        self.ttf.raw_table(tag)
+    }
+
+    /// Returns the kerning between two characters in font design units, the
+    /// adjustment of the advance of `left` when followed by `right`. Kerning
+    /// is looked up in the 'GPOS' table, then the legacy 'kern' table, and is
+    /// 0 if neither has the pair.
+    pub fn kerning(&self, left: char, right: char) -> i16 {
+        let ttf = &self.ttf;
+        match (ttf.glyph_index(left), ttf.glyph_index(right)) {
+            (Some(left), Some(right)) => ttf.kerning(left, right),
+            _ => 0,
+        }
+    }
+
+    /// Returns the sizes of the color bitmaps of the font in pixels per em,
+    /// like the emoji in the 'sbix' table of Apple Color Emoji. Empty if the
+    /// font has none.
+    pub fn color_bitmap_sizes(&self) -> Vec<u16> {
+        self.ttf.color_bitmap_sizes()
+    }
+
+    /// Returns the style of the face as the font designer flagged it, from
+    /// the 'OS/2' and 'head' tables, like for grouping faces into families in
+    /// a font picker. Where the tables disagree, the 'OS/2' table wins.
+    pub fn style_flags(&self) -> StyleFlags {
+        let ttf = &self.ttf;
+        let mac_style = ttf.mac_style();
+        let mac = |bit: u16| mac_style & bit != 0;
+        match ttf.fs_selection() {
+            Some(fs_selection) => {
+                let os2 = |bit: u16| fs_selection & bit != 0;
+                StyleFlags{
+                    bold: os2(0x20),
+                    italic: os2(0x1),
+                    oblique: os2(0x200),
+                    underline: os2(0x2),
+                    outline: os2(0x8),
+                    strikeout: os2(0x10),
+                    // Only the 'head' table has these
+                    shadow: mac(0x10),
+                    condensed: mac(0x20),
+                    extended: mac(0x40),
+                }
+            },
+            None => StyleFlags{
+                bold: mac(0x1),
+                italic: mac(0x2),
+                oblique: false,
+                underline: mac(0x4),
+                outline: mac(0x8),
+                strikeout: false,
+                shadow: mac(0x10),
+                condensed: mac(0x20),
+                extended: mac(0x40),
+            },
+        }
+    }
+
+    /// Returns the size ranges of the 'gasp' table, telling how the font
+    /// should be rendered up to each size. Empty if the font has none.
+    pub fn gasp_ranges(&self) -> Vec<GaspRange> {
+        self.ttf.gasp_ranges().iter()
+            .map(|(max_ppem, behavior)| GaspRange{
+                max_ppem: *max_ppem,
+                gridfit: behavior & 0x1 != 0,
+                grayscale: behavior & 0x2 != 0,
+                symmetric_gridfit: behavior & 0x4 != 0,
+                symmetric_smoothing: behavior & 0x8 != 0,
+            })
+            .collect()
+    }
+
+    /// Returns the antialiasing the 'gasp' table asks for at the given size in
+    /// pixels per em. Sizes the font wants without grayscale smoothing are
+    /// rendered aliased, otherwise the given quality is kept.
+    pub(crate) fn gasp_quality(&self, ppem: f64, quality: RenderQuality) -> RenderQuality {
+        let ppem = ppem.round();
+        let range = self.gasp_ranges().into_iter().find(|r| ppem <= r.max_ppem as f64);
+        match range {
+            Some(range) if !range.grayscale => RenderQuality::Aliased,
+            _ => quality,
+        }
+    }
+
+    /// Returns the design axes of a variable font from the 'fvar' table, like
+    /// the weight or the width, with their mapping from the 'avar' table.
+    /// Empty if the font isn't variable. Faces are scaled to other points of
+    /// the design space with `ScaleBuilder::variations`.
+    pub fn variation_axes(&self) -> Vec<VariationAxis> {
+        let ttf = &self.ttf;
+        ttf.variation_axes().iter()
+            .map(|a| VariationAxis{
+                tag: a.tag.iter().map(|c| *c as char).collect(),
+                name: ttf.preferred_name(a.name_id).map(String::from),
+                min: a.min,
+                default: a.default,
+                max: a.max,
+                hidden: a.flags & 0x1 != 0,
+                mapping: a.mapping.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns the named instances of a variable font from the 'fvar' table,
+    /// like "Semibold". Empty if the font isn't variable.
+    pub fn named_instances(&self) -> Vec<NamedInstance> {
+        let ttf = &self.ttf;
+        ttf.named_instances().iter()
+            .map(|i| NamedInstance{
+                name: ttf.preferred_name(i.name_id).map(String::from),
+                coordinates: i.coordinates.clone(),
+            })
+            .collect()
+    }
+
+    /// Returns the control values of the TrueType hinting instructions in
+    /// font design units, from the 'cvt ' table. Empty if the font has none.
+    pub fn control_values(&self) -> Vec<i16> {
+        self.ttf.control_values()
+    }
+
+    /// Returns the TrueType instructions of the font program, from the 'fpgm'
+    /// table. Empty if the font has none.
+    pub fn font_program(&self) -> &[u8] {
+        self.ttf.font_program()
+    }
+
+    /// Returns the TrueType instructions of the control value program, from
+    /// the 'prep' table. Empty if the font has none.
+    pub fn control_value_program(&self) -> &[u8] {
+        self.ttf.control_value_program()
+    }
+
+    /// Returns the vertical advance of the given character in font design
+    /// units, or None if the font has no vertical metrics or the character has
+    /// no glyph.
+    pub fn vertical_advance(&self, c: char) -> Option<u16> {
+        let ttf = &self.ttf;
+        ttf.vertical_advance(ttf.glyph_index(c)?)
+    }
+}
+
+/// Describes the device that font faces get scaled for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScaleContext {
+    /// The resolution of the device in dots per inch.
+    pub dpi: f64,
+}
+
+/// Rendering resources shared between scaled font faces, see
+/// `ScaleBuilder::render_context`. On Windows this is a GDI Device Context,
+/// which is a limited resource, so applications scaling many faces to many
+/// sizes should share one. Other backends have nothing to share. Clones refer
+/// to the same resources.
+///
+/// Like scaled font faces, render contexts are neither `Send` nor `Sync`.
+#[cfg(feature = "std")]
+pub struct RenderContext<B: FontBackend = DefaultBackend> {
+    inner  : B::Context   ,
+    _marker: NotThreadSafe,
+}
+
+#[cfg(feature = "std")]
+impl <B: FontBackend> Clone for RenderContext<B> {
+    fn clone(&self) -> Self {
+        Self{
+            inner: self.inner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl <B: FontBackend> RenderContext<B> {
+    /// Creates the rendering resources.
+    pub fn new() -> Result<Self> {
+        Ok(Self{
+            inner: B::create_context()?,
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// Builder for scaling a font face, see `FontFace::scaler`.
+#[cfg(feature = "std")]
+pub struct ScaleBuilder<'a, B: FontBackend = DefaultBackend> {
+    face      : &'a FontFace<B>             ,
+    pts       : f64                         ,
+    dpi       : f64                         ,
+    quality   : RenderQuality               ,
+    colors    : RenderColors                ,
+    gamma     : f64                         ,
+    trim      : bool                        ,
+    flip_y    : bool                        ,
+    escapement: i32                         ,
+    stretch   : f64                         ,
+    gasp      : bool                        ,
+    variations: Vec<(String, f32)>          ,
+    context   : Option<&'a RenderContext<B>>,
+}
+
+#[cfg(feature = "std")]
+impl <'a, B: FontBackend> ScaleBuilder<'a, B> {
+    /// Sets the antialiasing of the rasterized glyphs.
+    pub fn quality(mut self, quality: RenderQuality) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Sets the colors glyphs get drawn with before the coverage is read back.
+    /// The coverage doesn't depend on them, but ClearType filtering does, so
+    /// for example text meant to be shown dark on light should be rasterized
+    /// that way.
+    pub fn colors(mut self, colors: RenderColors) -> Self {
+        self.colors = colors;
+        self
+    }
+
+    /// Sets the gamma correction applied to the coverage of the rasterized
+    /// glyphs. Values above 1.0 make the edges heavier, values below make them
+    /// thinner. The default of 1.0 leaves the coverage unchanged.
+    pub fn gamma(mut self, gamma: f64) -> Self {
+        self.gamma = gamma;
+        self
+    }
+
+    /// Sets whether rasterized glyphs get cropped to the bounds of their ink,
+    /// which is the default. Untrimmed glyphs fill their whole `cell` and
+    /// have zero offsets, so in fixed-pitch rendering every bitmap can be
+    /// drawn at its cell as is. Ink outside of the cell is cut off.
+    pub fn trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    /// Sets whether the rows of rasterized bitmaps are stored bottom-up, for
+    /// pipelines with the origin at the bottom left, like OpenGL textures.
+    /// Only the order of the rows changes, the offsets and cells still point
+    /// downwards. Atlases are built from the flipped glyphs, so they can be
+    /// uploaded the same way, with their rects measured from the bottom.
+    pub fn flip_y(mut self, flip_y: bool) -> Self {
+        self.flip_y = flip_y;
+        self
+    }
+
+    /// Sets the angle text gets rotated by counterclockwise, in tenths of a
+    /// degree, like 900 for text going upwards. Rasterized glyphs come out
+    /// rotated, with their offsets still relative to the drawing position,
+    /// and shaped text gets positioned along the rotated baseline, moved into
+    /// the bounding box of the rotated text, which `shape_text` returns the
+    /// size of. Other metrics, like the line height and the cells of glyphs,
+    /// are the ones of unrotated text. Only supported on Windows for now.
+    pub fn escapement(mut self, escapement: i32) -> Self {
+        self.escapement = escapement;
+        self
+    }
+
+    /// Sets the horizontal scale of the glyphs, like 0.8 for a condensed or
+    /// 1.2 for an expanded variant of a font that only ships in the regular
+    /// width. The metrics, the cells of glyphs and the positions of shaped
+    /// text reflect the stretched width. The default of 1.0 leaves the glyphs
+    /// unchanged. Only supported on Windows for now.
+    pub fn stretch(mut self, stretch: f64) -> Self {
+        self.stretch = stretch;
+        self
+    }
+
+    /// Sets whether the antialiasing follows the 'gasp' table of the font,
+    /// which tells the sizes the font is meant to be rendered without
+    /// smoothing at. At those sizes the glyphs are rendered aliased instead
+    /// of with the set quality. Off by default.
+    pub fn gasp(mut self, gasp: bool) -> Self {
+        self.gasp = gasp;
+        self
+    }
+
+    /// Sets the coordinates on the design axes of a variable font, like
+    /// `("wght", 600.0)`, see `FontFace::variation_axes`. Axes left out stay
+    /// at their defaults, and coordinates outside of their axis are clamped
+    /// to it. Building fails with `Error::Unsupported` on backends that can't
+    /// render variations, which are all the built-in ones for now, unless
+    /// every coordinate is the default of its axis.
+    pub fn variations(mut self, coordinates: &[(&str, f32)]) -> Self {
+        self.variations = coordinates.iter()
+            .map(|(tag, value)| ((*tag).into(), *value))
+            .collect();
+        self
+    }
+
+    /// Sets the coordinates of a named instance of a variable font, like
+    /// "Semibold", see `FontFace::named_instances` and `variations`.
+    pub fn named_instance(mut self, instance: &NamedInstance) -> Self {
+        self.variations = self.face.variation_axes().into_iter()
+            .zip(&instance.coordinates)
+            .map(|(axis, value)| (axis.tag, *value))
+            .collect();
+        self
+    }
+
+    /// Sets the rendering resources the scaled face uses, instead of creating
+    /// its own.
+    pub fn render_context(mut self, context: &'a RenderContext<B>) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Scales the font face with the configured settings. Fails if the em
+    /// square would be smaller than a pixel or larger than 16384 pixels, or
+    /// a variation refers to an axis the font doesn't have.
+    pub fn build(self) -> Result<ScaledFontFace<B>> {
+        check_size(self.pts, self.dpi)?;
+        if self.gamma <= 0.0 || !self.gamma.is_finite() {
+            return Err(Error::UserError(format!("Invalid gamma value {}!", self.gamma)));
+        }
+        let gamma_lut = if self.gamma == 1.0 {
+            None
+        }
+        else {
+            let exponent = 1.0 / self.gamma;
+            Some((0..=255u8)
+                .map(|c| ((c as f64 / 255.0).powf(exponent) * 255.0).round() as u8)
+                .collect())
+        };
+        if self.colors.text == self.colors.background {
+            return Err(Error::UserError("The text and background colors must differ!".into()));
+        }
+        if self.stretch <= 0.0 || !self.stretch.is_finite() {
+            return Err(Error::UserError(format!("Invalid stretch value {}!", self.stretch)));
+        }
+        let axes = self.face.variation_axes();
+        let mut variations = Vec::new();
+        for (tag, value) in &self.variations {
+            let axis = axes.iter().find(|a| a.tag == *tag)
+                .ok_or_else(|| Error::UserError(format!("No variation axis '{}' in the font!", tag)))?;
+            if !value.is_finite() {
+                return Err(Error::UserError(format!("Invalid coordinate {} on the axis '{}'!", value, tag)));
+            }
+            // The default instance needs no support from the backend
+            let value = value.max(axis.min).min(axis.max);
+            if value != axis.default {
+                variations.push((tag.clone(), value));
+            }
+        }
+        let quality = if self.gasp {
+            const POINTS_PER_INCH: f64 = 72.0;
+            self.face.gasp_quality(self.pts * self.dpi / POINTS_PER_INCH, self.quality)
+        }
+        else {
+            self.quality
+        };
+        let settings = ScaleSettings{
+            pts: self.pts,
+            dpi: self.dpi,
+            quality,
+            colors: self.colors,
+            escapement: self.escapement,
+            stretch: self.stretch,
+            flip_y: self.flip_y,
+            variations: variations.clone(),
+        };
+        let inner = B::scale(&self.face.inner, &settings, self.context.map(|c| &c.inner))?;
+        Ok(ScaledFontFace{
+            inner,
+            face: self.face.clone(),
+            pts: self.pts,
+            dpi: self.dpi,
+            quality: self.quality,
+            colors: self.colors,
+            gamma: self.gamma,
+            gamma_lut,
+            trim: self.trim,
+            flip_y: self.flip_y,
+            escapement: self.escapement,
+            stretch: self.stretch,
+            gasp: self.gasp,
+            variations,
+            context: self.context.cloned(),
+            others: Vec::new(),
+            scratch: Vec::new(),
+            _marker: PhantomData,
+        })
     }
 }
 
 /// Represents a font face that has been scaled to a given size.
-pub struct ScaledFontFace(itypes::ScaledFontFaceImpl);
+///
+/// Scaled font faces are neither `Send` nor `Sync`, as they own rendering
+/// resources bound to the creating thread. To render on multiple threads,
+/// scale the `FontFace` on each of them.
+#[cfg(feature = "std")]
+pub struct ScaledFontFace<B: FontBackend = DefaultBackend> {
+    inner     : B::Scaled                      ,
+    // The face and settings it got scaled with, so it can be rescaled
+    face      : FontFace<B>                    ,
+    pts       : f64                            ,
+    dpi       : f64                            ,
+    quality   : RenderQuality                  ,
+    colors    : RenderColors                   ,
+    gamma     : f64                            ,
+    gamma_lut : Option<Box<[u8]>>              ,
+    trim      : bool                           ,
+    flip_y    : bool                           ,
+    escapement: i32                            ,
+    stretch   : f64                            ,
+    gasp      : bool                           ,
+    variations: Vec<(String, f32)>             ,
+    context   : Option<RenderContext<B>>       ,
+    // The face scaled with other qualities, see rasterize_glyph_with_quality
+    others    : Vec<(RenderQuality, B::Scaled)>,
+    // The buffer of with_rasterized, kept to be reused
+    scratch   : Vec<u8>                        ,
+    _marker   : NotThreadSafe                  ,
+}
+
+#[cfg(feature = "std")]
+impl <B: FontBackend> ScaledFontFace<B> {
+    /// Scales the same font face to the same size and with the same settings
+    /// for another device, like when a window moves to a monitor with a
+    /// different DPI.
+    pub fn rescale(&self, ctx: &ScaleContext) -> Result<ScaledFontFace<B>> {
+        let mut builder = self.face.scaler(self.pts, ctx.dpi)
+            .quality(self.quality)
+            .colors(self.colors)
+            .gamma(self.gamma)
+            .trim(self.trim)
+            .flip_y(self.flip_y)
+            .escapement(self.escapement)
+            .stretch(self.stretch)
+            .gasp(self.gasp);
+        builder.variations = self.variations.clone();
+        match &self.context {
+            Some(context) => builder.render_context(context).build(),
+            None => builder.build(),
+        }
+    }
+
+    /// Releases the rendering resources of the face right away, like the GDI
+    /// objects on Windows, which are limited. The face can't be used after,
+    /// as it's consumed. This is the same as dropping it, only more explicit.
+    /// A shared `RenderContext` is only released with the last face using it.
+    pub fn close(self) {
+        drop(self)
+    }
+
+    /// Changes the size of the face in place, like for zooming. Where the
+    /// backend supports it, only the font gets replaced, the rendering
+    /// resources are kept, so this is cheaper than scaling the face again.
+    /// The faces scaled with other qualities for
+    /// `rasterize_glyph_with_quality` are dropped. Fails on the sizes `build`
+    /// rejects, leaving the face unchanged.
+    pub fn set_size(&mut self, pts: f64, dpi: f64) -> Result<()> {
+        check_size(pts, dpi)?;
+        let quality = if self.gasp {
+            const POINTS_PER_INCH: f64 = 72.0;
+            self.face.gasp_quality(pts * dpi / POINTS_PER_INCH, self.quality)
+        }
+        else {
+            self.quality
+        };
+        match self.inner.set_size(pts, dpi, quality) {
+            Err(Error::Unsupported(_)) => {
+                let context = self.context.as_ref().map(|c| &c.inner);
+                self.inner = B::scale(&self.face.inner, &self.settings(pts, dpi, quality), context)?;
+            },
+            result => result?,
+        }
+        self.pts = pts;
+        self.dpi = dpi;
+        self.others.clear();
+        Ok(())
+    }
+
+    /// Returns the settings the backend scales the face with, at the given
+    /// size and quality.
+    fn settings(&self, pts: f64, dpi: f64, quality: RenderQuality) -> ScaleSettings {
+        ScaleSettings{
+            pts,
+            dpi,
+            quality,
+            colors: self.colors,
+            escapement: self.escapement,
+            stretch: self.stretch,
+            flip_y: self.flip_y,
+            variations: self.variations.clone(),
+        }
+    }
+
+    /// Returns the layout box of a glyph, the advance times the line height.
+    fn cell(&self, glyph_id: Option<u16>) -> GlyphBox {
+        let ttf = &self.face.ttf;
+        let advance = glyph_id.and_then(|g| ttf.horizontal_advance(g)).unwrap_or(0);
+        let design_scale = self.inner.em_size() / ttf.units_per_em() as f64;
+        GlyphBox{
+            x: 0,
+            y: 0,
+            width: (advance as f64 * design_scale * self.stretch).round() as i32,
+            height: self.inner.line_height(),
+        }
+    }
+
+    /// Fills in the layout box of a glyph the backend rasterized.
+    fn with_cell(&self, glyph_id: Option<u16>, mut glyph: RasterizedGlyph) -> RasterizedGlyph {
+        glyph.cell = self.cell(glyph_id);
+        glyph.baseline = self.inner.ascent();
+        glyph
+    }
+
+    /// Places the glyph into a bitmap covering its whole cell, if the face
+    /// was scaled without trimming.
+    fn untrim(&self, glyph: RasterizedGlyph) -> RasterizedGlyph {
+        if self.trim {
+            return glyph;
+        }
+        let width = glyph.cell.width.max(0) as usize;
+        let height = glyph.cell.height.max(0) as usize;
+        let mut data = vec![0u8; width * height];
+        // The offset points downwards, flipped rows are placed from the bottom
+        let top = if self.flip_y {
+            height as i32 - glyph.height as i32 - glyph.y_offset
+        }
+        else {
+            glyph.y_offset
+        };
+        for y in 0..glyph.height {
+            let cy = y as i32 + top;
+            if cy < 0 || cy >= height as i32 {
+                continue;
+            }
+            for x in 0..glyph.width {
+                let cx = x as i32 + glyph.x_offset;
+                if cx < 0 || cx >= width as i32 {
+                    continue;
+                }
+                data[cy as usize * width + cx as usize] = glyph.data[y * glyph.width + x];
+            }
+        }
+        RasterizedGlyph{
+            x_offset: 0,
+            y_offset: 0,
+            width,
+            height,
+            data: data.into_boxed_slice(),
+            ..glyph
+        }
+    }
+
+    /// Returns the size of the em square in pixels, the points scaled to the
+    /// DPI. Metrics in font design units are converted to pixels by
+    /// multiplying them with this, then dividing by `FontInfo::units_per_em`.
+    pub fn pixels_per_em(&self) -> f64 {
+        self.inner.em_size()
+    }
+
+    /// Returns the natural distance between the baselines of consecutive
+    /// lines in pixels, the height of a line without any text shaped, like
+    /// for reserving space for empty lines.
+    pub fn line_height(&self) -> i32 {
+        self.inner.line_height()
+    }
+
+    /// Returns the largest width and height the bitmaps of rasterized glyphs
+    /// can have at the current size, from the bounding box of all glyphs in
+    /// the 'head' table. Buffers of this size fit any glyph of the face, like
+    /// for `rasterize_glyph_into`. Untrimmed glyphs are as large as the
+    /// widest cell instead.
+    pub fn max_glyph_bounds(&self) -> (usize, usize) {
+        let ttf = &self.face.ttf;
+        let design_scale = self.inner.em_size() / ttf.units_per_em() as f64;
+        if !self.trim {
+            let width = ttf.max_horizontal_advance() as f64 * design_scale * self.stretch;
+            return (width.round() as usize, self.inner.line_height().max(0) as usize);
+        }
+        let (x_min, y_min, x_max, y_max) = ttf.bounding_box();
+        let mut width = (x_max as f64 - x_min as f64) * design_scale * self.stretch;
+        let mut height = (y_max as f64 - y_min as f64) * design_scale;
+        if self.escapement != 0 {
+            // A rotated box fits into a square as large as its diagonal
+            width = width.hypot(height);
+            height = width;
+        }
+        // Hinting and antialiasing can spill over a pixel on each side
+        (width.max(0.0).ceil() as usize + 2, height.max(0.0).ceil() as usize + 2)
+    }
+
+    /// Applies the gamma correction to the coverage of a rasterized glyph.
+    fn correct_gamma(&self, mut glyph: RasterizedGlyph) -> RasterizedGlyph {
+        if let Some(lut) = &self.gamma_lut {
+            for c in glyph.data.iter_mut() {
+                *c = lut[*c as usize];
+            }
+        }
+        glyph
+    }
 
-impl ScaledFontFace {
     /// Rasterizes the given character to a grayscale bitmap.
     pub fn rasterize_glyph(&mut self, codepoint: char) -> Result<RasterizedGlyph> {
-        self.0.rasterize_glyph(codepoint)
+        let glyph = self.inner.rasterize_glyph(codepoint)?;
+        let glyph = self.with_cell(self.face.ttf.glyph_index(codepoint), glyph);
+        Ok(self.untrim(self.correct_gamma(glyph)))
+    }
+
+    /// Rasterizes the given character straight into an 8-bit grayscale bitmap
+    /// `dst_width` pixels wide, like a glyph atlas, with the top left corner
+    /// of the glyph bitmap at `(x, y)`. This saves allocating the bitmap of
+    /// the glyph and copying it over. The rest of the destination is left as
+    /// is. Returns the metrics of the glyph and the size of its bitmap, or an
+    /// error if it doesn't fit the destination.
+    pub fn rasterize_glyph_into(&mut self, codepoint: char, dst: &mut [u8], dst_width: usize,
+        x: usize, y: usize) -> Result<(GlyphMetrics, (usize, usize))> {
+        let cell = self.cell(self.face.ttf.glyph_index(codepoint));
+        if !self.trim {
+            // The bitmap gets reshaped, rasterize it the usual way
+            let glyph = self.rasterize_glyph(codepoint)?;
+            copy_into(&glyph, dst, dst_width, x, y)?;
+            let metrics = GlyphMetrics{
+                x_offset: glyph.x_offset,
+                y_offset: glyph.y_offset,
+                x_advance: cell.width,
+            };
+            return Ok((metrics, (glyph.width, glyph.height)));
+        }
+        let ink = self.inner.rasterize_glyph_into(codepoint, dst, dst_width, x, y)?;
+        let (width, height) = (ink.width as usize, ink.height as usize);
+        if let Some(lut) = &self.gamma_lut {
+            for gy in 0..height {
+                let row = (y + gy) * dst_width + x;
+                for c in dst[row..(row + width)].iter_mut() {
+                    *c = lut[*c as usize];
+                }
+            }
+        }
+        let metrics = GlyphMetrics{
+            x_offset: ink.x,
+            y_offset: ink.y,
+            x_advance: cell.width,
+        };
+        Ok((metrics, (width, height)))
+    }
+
+    /// Rasterizes the given character into a buffer the face keeps, and calls
+    /// `f` with a view of it. Once the buffer is large enough nothing gets
+    /// allocated, which suits glyphs that are only rasterized to be copied
+    /// somewhere, like into an atlas. The view always covers just the ink,
+    /// even if the face was scaled without trimming.
+    pub fn with_rasterized<R, F: FnOnce(GlyphView<'_>) -> R>(&mut self, codepoint: char, f: F) -> Result<R> {
+        let (max_width, max_height) = self.max_glyph_bounds();
+        let len = max_width.checked_mul(max_height)
+            .ok_or_else(|| Error::UserError("Glyph bitmap size overflows!".into()))?;
+        if self.scratch.len() < len {
+            self.scratch.resize(len, 0);
+        }
+        let (x_offset, y_offset, width, height) =
+            match self.inner.rasterize_glyph_into(codepoint, &mut self.scratch, max_width, 0, 0) {
+                Ok(ink) => {
+                    // Close the gaps between the rows
+                    let (width, height) = (ink.width as usize, ink.height as usize);
+                    for y in 1..height {
+                        self.scratch.copy_within((y * max_width)..(y * max_width + width), y * width);
+                    }
+                    (ink.x, ink.y, width, height)
+                },
+                Err(Error::UserError(_)) => {
+                    // The glyph is larger than the font bounds, copy it over instead
+                    let glyph = self.inner.rasterize_glyph(codepoint)?;
+                    self.scratch.clear();
+                    self.scratch.extend_from_slice(&glyph.data);
+                    (glyph.x_offset, glyph.y_offset, glyph.width, glyph.height)
+                },
+                Err(err) => return Err(err),
+            };
+        let data = &mut self.scratch[..(width * height)];
+        if let Some(lut) = &self.gamma_lut {
+            for c in data.iter_mut() {
+                *c = lut[*c as usize];
+            }
+        }
+        let cell = self.cell(self.face.ttf.glyph_index(codepoint));
+        Ok(f(GlyphView{
+            character: codepoint,
+            x_offset,
+            y_offset,
+            width,
+            height,
+            cell,
+            baseline: self.inner.ascent(),
+            data: &self.scratch[..(width * height)],
+        }))
+    }
+
+    /// Tells whether a glyph rasterized for its character has ink, and if it
+    /// doesn't, whether that's expected. This tells a real space apart from a
+    /// character the font can't draw, like for deciding on a fallback font.
+    pub fn ink_status(&self, glyph: &RasterizedGlyph) -> InkStatus {
+        let ttf = &self.face.ttf;
+        let glyph_id = match ttf.glyph_index(glyph.character) {
+            Some(glyph_id) if glyph_id != 0 => glyph_id,
+            // Glyph 0 is the one drawn for missing characters
+            _ => return InkStatus::Missing,
+        };
+        let inked = (0..glyph.height)
+            .any(|y| (0..glyph.width).any(|x| glyph.coverage(x, y) != 0));
+        if inked {
+            return InkStatus::Inked;
+        }
+        match ttf.glyph_data_range(glyph_id) {
+            Some(range) if !range.is_empty() => InkStatus::Unrendered,
+            _ => InkStatus::Blank,
+        }
+    }
+
+    /// Rasterizes the given character like `rasterize_glyph`, but with the
+    /// given antialiasing instead of the one the face got scaled with, like
+    /// for pixel-perfect box-drawing characters in antialiased text. The face
+    /// gets scaled again for each other quality on first use.
+    pub fn rasterize_glyph_with_quality(&mut self, codepoint: char, quality: RenderQuality) -> Result<RasterizedGlyph> {
+        // The quality the face got scaled with, after applying the 'gasp' table
+        let current = if self.gasp {
+            self.face.gasp_quality(self.inner.em_size(), self.quality)
+        }
+        else {
+            self.quality
+        };
+        if quality == current {
+            return self.rasterize_glyph(codepoint);
+        }
+        let index = match self.others.iter().position(|(q, _)| *q == quality) {
+            Some(index) => index,
+            None => {
+                let context = self.context.as_ref().map(|c| &c.inner);
+                let inner = B::scale(&self.face.inner, &self.settings(self.pts, self.dpi, quality), context)?;
+                self.others.push((quality, inner));
+                self.others.len() - 1
+            },
+        };
+        let glyph = self.others[index].1.rasterize_glyph(codepoint)?;
+        let glyph = self.with_cell(self.face.ttf.glyph_index(codepoint), glyph);
+        Ok(self.untrim(self.correct_gamma(glyph)))
+    }
+
+    /// Rasterizes the character encoded by the given UTF-16 units, which must be
+    /// a single unit or a surrogate pair.
+    pub fn rasterize_glyph_utf16(&mut self, units: &[u16]) -> Result<RasterizedGlyph> {
+        let mut chars = core::char::decode_utf16(units.iter().copied());
+        match (chars.next(), chars.next()) {
+            (Some(Ok(c)), None) => self.rasterize_glyph(c),
+            _ => Err(Error::UserError(format!("{:x?} is not a single UTF-16 encoded character!", units))),
+        }
+    }
+
+    /// Rasterizes the given character like `rasterize_glyph`, with the coverage
+    /// packed to the given bit depth.
+    pub fn rasterize_glyph_with_depth(&mut self, codepoint: char, bit_depth: BitDepth) -> Result<RasterizedGlyph> {
+        Ok(self.rasterize_glyph(codepoint)?.to_bit_depth(bit_depth))
+    }
+
+    /// Rasterizes every distinct character of the passed in text, in the order
+    /// of their first occurrence. A character failing doesn't stop the rest,
+    /// the successfully rasterized glyphs are returned along with the errors
+    /// of the failed characters, so for example these can be drawn as tofu.
+    pub fn rasterize_glyphs(&mut self, chars: &str) -> (Vec<RasterizedGlyph>, Vec<(char, Error)>) {
+        let mut seen = std::collections::HashSet::new();
+        let mut glyphs = Vec::new();
+        let mut errors = Vec::new();
+        for c in chars.chars().filter(|c| seen.insert(*c)) {
+            match self.rasterize_glyph(c) {
+                Ok(glyph) => glyphs.push(glyph),
+                Err(err) => errors.push((c, err)),
+            }
+        }
+        (glyphs, errors)
+    }
+
+    /// Rasterizes every character of `chars` and packs them into a single
+    /// bitmap. Characters occurring multiple times are only packed once.
+    pub fn build_atlas(&mut self, chars: &str, options: AtlasOptions) -> Result<Atlas> {
+        let mut glyphs = std::collections::HashMap::new();
+        for c in chars.chars() {
+            if let Entry::Vacant(entry) = glyphs.entry(c) {
+                entry.insert(self.rasterize_glyph(c)?);
+            }
+        }
+        Ok(atlas::compose(glyphs, &options))
+    }
+
+    /// Rasterizes the given character like `rasterize_glyph`, tinted with the
+    /// `foreground` color and composited over the optional `background` color.
+    /// Colors are RGBA.
+    pub fn rasterize_glyph_colored(&mut self, codepoint: char, foreground: [u8; 4],
+        background: Option<[u8; 4]>) -> Result<ColoredGlyph> {
+        Ok(self.rasterize_glyph(codepoint)?.to_colored(foreground, background))
+    }
+
+    /// Rasterizes the given character like `rasterize_glyph`, filled with the
+    /// `fill` color and outlined with the `stroke` color, see
+    /// `RasterizedGlyph::to_outlined`.
+    pub fn rasterize_glyph_outlined(&mut self, codepoint: char, fill: [u8; 4], stroke: [u8; 4],
+        stroke_width: usize) -> Result<ColoredGlyph> {
+        Ok(self.rasterize_glyph(codepoint)?.to_outlined(fill, stroke, stroke_width))
+    }
+
+    /// Lays out the text and rasterizes it into a single RGBA bitmap, with
+    /// each glyph tinted with the color `color` returns for the byte offset of
+    /// its character in `text`, like for syntax highlighting. Ligatures take
+    /// the color of their first character. The background is transparent, the
+    /// offsets of the result are zero, its cell is the whole bitmap and its
+    /// `character` is U+0000.
+    pub fn rasterize_text_colored<C: Fn(usize) -> [u8; 4]>(&mut self, text: &str, options: ShapeOptions,
+        color: C) -> Result<ColoredGlyph> {
+        let offsets: Vec<_> = text.char_indices().map(|(offset, _)| offset).collect();
+        let mut glyphs = Vec::new();
+        let (width, height) = self.shape_text(text, options, |g| glyphs.push(g))?;
+        let width = width.max(0) as usize;
+        let height = height.max(0) as usize;
+        let mut data = vec![0u8; width * height * 4];
+        let mut rasterized = std::collections::HashMap::new();
+        for shaped in glyphs {
+            if shaped.character == '\n' || is_zero_width(shaped.character) {
+                continue;
+            }
+            let glyph = match rasterized.entry((shaped.character, shaped.glyph_id)) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.rasterize_shaped(&shaped)?),
+            };
+            let [r, g, b, a] = color(offsets[shaped.index]);
+            for y in 0..glyph.height {
+                let py = shaped.y + glyph.y_offset + y as i32;
+                if py < 0 || py >= height as i32 {
+                    continue;
+                }
+                for x in 0..glyph.width {
+                    let px = shaped.x + glyph.x_offset + x as i32;
+                    if px < 0 || px >= width as i32 {
+                        continue;
+                    }
+                    // The glyph is already flipped, the whole bitmap gets flipped at the end
+                    let row = if self.flip_y { glyph.height - 1 - y } else { y };
+                    let src_a = (a as u32 * glyph.coverage(x, row) as u32 + 127) / 255;
+                    let offs = (py as usize * width + px as usize) * 4;
+                    let dst = [data[offs], data[offs + 1], data[offs + 2], data[offs + 3]];
+                    data[offs..(offs + 4)].copy_from_slice(&composite([r, g, b, src_a as u8], dst));
+                }
+            }
+        }
+        if self.flip_y {
+            flip_rows(&mut data, width * 4);
+        }
+        Ok(ColoredGlyph{
+            character: '\0',
+            x_offset: 0,
+            y_offset: 0,
+            width,
+            height,
+            cell: GlyphBox{
+                x: 0,
+                y: 0,
+                width: width as i32,
+                height: height as i32,
+            },
+            data: data.into_boxed_slice(),
+        })
+    }
+
+    /// Lays out the text and returns the box of the pixels its glyphs
+    /// actually cover, in the coordinates of `shape_text`. Unlike the size
+    /// `shape_text` returns, this includes the parts of glyphs overshooting
+    /// their advance or the line, like the hook of an italic f or accents on
+    /// capitals, and leaves out the empty space around the ink. The box is
+    /// empty at 0, 0 if the text has no ink.
+    pub fn ink_bounds(&mut self, text: &str, options: ShapeOptions) -> Result<GlyphBox> {
+        let mut glyphs = Vec::new();
+        self.shape_text(text, options, |g| glyphs.push(g))?;
+        let mut rasterized = std::collections::HashMap::new();
+        let mut bounds: Option<(i32, i32, i32, i32)> = None;
+        for shaped in glyphs {
+            if shaped.character == '\n' || is_zero_width(shaped.character) {
+                continue;
+            }
+            let glyph = match rasterized.entry((shaped.character, shaped.glyph_id)) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(self.rasterize_shaped(&shaped)?),
+            };
+            for y in 0..glyph.height {
+                // Untrimmed bitmaps have empty space around the ink
+                let row = if self.flip_y { glyph.height - 1 - y } else { y };
+                let mut coverage = (0..glyph.width).map(|x| glyph.coverage(x, row));
+                let (left, right) = match (coverage.clone().position(|c| c != 0), coverage.rposition(|c| c != 0)) {
+                    (Some(left), Some(right)) => (left as i32, right as i32 + 1),
+                    _ => continue,
+                };
+                let x = shaped.x + glyph.x_offset;
+                let y = shaped.y + glyph.y_offset + y as i32;
+                bounds = Some(match bounds {
+                    Some((x0, y0, x1, y1)) => (x0.min(x + left), y0.min(y), x1.max(x + right), y1.max(y + 1)),
+                    None => (x + left, y, x + right, y + 1),
+                });
+            }
+        }
+        Ok(bounds
+            .map(|(x0, y0, x1, y1)| GlyphBox{ x: x0, y: y0, width: x1 - x0, height: y1 - y0 })
+            .unwrap_or_default())
+    }
+
+    /// Rasterizes the given character shifted right by a fraction of a pixel,
+    /// so glyphs placed between pixels keep their spacing even. The fraction
+    /// is taken modulo 1, quantizing it (for example to quarters) lets the
+    /// results be cached. The bitmap gets one pixel wider if it's shifted.
+    pub fn rasterize_glyph_subpixel(&mut self, codepoint: char, x_fraction: f32) -> Result<RasterizedGlyph> {
+        let glyph = self.rasterize_glyph(codepoint)?;
+        let fraction = x_fraction.rem_euclid(1.0);
+        if fraction == 0.0 || glyph.width == 0 {
+            return Ok(glyph);
+        }
+        // Box-filter the coverage onto the shifted pixel grid
+        let width = glyph.width + 1;
+        let mut data = vec![0u8; width * glyph.height].into_boxed_slice();
+        for y in 0..glyph.height {
+            let row = &glyph.data[(y * glyph.width)..((y + 1) * glyph.width)];
+            for x in 0..width {
+                let current = row.get(x).copied().unwrap_or(0) as f32;
+                let previous = if x > 0 { row[x - 1] as f32 } else { 0.0 };
+                data[y * width + x] = (current * (1.0 - fraction) + previous * fraction).round() as u8;
+            }
+        }
+        Ok(RasterizedGlyph{
+            data,
+            width,
+            ..glyph
+        })
+    }
+
+    /// Rasterizes the given character into a bitmap of exactly `cell` width
+    /// and height, with the ink of the glyph centered in it. Glyphs that don't
+    /// fit get scaled down, keeping their aspect ratio. Useful for icon fonts,
+    /// where uniform cells matter more than the metrics of the font. The
+    /// offsets of the result are zero and its cell is the whole bitmap.
+    pub fn rasterize_glyph_fit(&mut self, c: char, cell: (usize, usize)) -> Result<RasterizedGlyph> {
+        let (cell_width, cell_height) = cell;
+        if cell_width == 0 || cell_height == 0 {
+            return Err(Error::UserError(format!("Can't fit a glyph into an empty cell of {:?}!", cell)));
+        }
+        let glyph = self.inner.rasterize_glyph(c)?;
+        let glyph = self.correct_gamma(self.with_cell(self.face.ttf.glyph_index(c), glyph));
+        let scale = if glyph.width > cell_width || glyph.height > cell_height {
+            f64::min(cell_width as f64 / glyph.width as f64, cell_height as f64 / glyph.height as f64)
+        }
+        else {
+            1.0
+        };
+        let width = ((glyph.width as f64 * scale).round() as usize).max(1).min(cell_width);
+        let height = ((glyph.height as f64 * scale).round() as usize).max(1).min(cell_height);
+        let ink = if glyph.width == 0 || glyph.height == 0 {
+            Vec::new()
+        }
+        else {
+            resample_coverage(&glyph.data, glyph.width, glyph.height, width, height)
+        };
+        // Center the ink in the cell, flipped rows are placed from the bottom
+        let x0 = (cell_width - width) / 2;
+        let y0 = (cell_height - height) / 2;
+        let row0 = if self.flip_y { cell_height - height - y0 } else { y0 };
+        let mut data = vec![0u8; cell_width * cell_height];
+        for (y, row) in ink.chunks(width).enumerate() {
+            let offs = (row0 + y) * cell_width + x0;
+            data[offs..(offs + width)].copy_from_slice(row);
+        }
+        Ok(RasterizedGlyph{
+            x_offset: 0,
+            y_offset: 0,
+            width: cell_width,
+            height: cell_height,
+            cell: GlyphBox{
+                x: 0,
+                y: 0,
+                width: cell_width as i32,
+                height: cell_height as i32,
+            },
+            // Moves with the ink
+            baseline: y0 as i32 + ((glyph.baseline - glyph.y_offset) as f64 * scale).round() as i32,
+            data: data.into_boxed_slice(),
+            ..glyph
+        })
+    }
+
+    /// Rasterizes the given character to a signed distance field, that stays
+    /// sharp when scaled up. The bitmap is extended by `spread` pixels on each
+    /// side, the edge of the glyph is at the value 128, and the values change
+    /// by `127 / spread` per pixel, increasing towards the inside.
+    pub fn rasterize_glyph_sdf(&mut self, codepoint: char, spread: u32) -> Result<RasterizedGlyph> {
+        if spread == 0 {
+            return Err(Error::UserError("The spread of a distance field must be positive!".into()));
+        }
+        let glyph = self.inner.rasterize_glyph(codepoint)?;
+        let glyph = self.with_cell(self.face.ttf.glyph_index(codepoint), glyph);
+        if glyph.width == 0 || glyph.height == 0 {
+            // Nothing to measure the distance from
+            return Ok(glyph);
+        }
+        Ok(sdf::to_sdf(&glyph, spread as usize))
+    }
+
+    /// Generates a multi-channel signed distance field of the given character
+    /// from its outline, which keeps the corners of the glyph sharp when
+    /// scaled up, unlike `rasterize_glyph_sdf`. The red, green and blue
+    /// channels hold the distances to differently colored edges, the glyph is
+    /// reconstructed from their median. The alpha channel holds the true
+    /// signed distance. The values and the spread are like the ones of
+    /// `rasterize_glyph_sdf`. The glyph isn't stretched or rotated, and only
+    /// fonts with TrueType outlines are supported, see `glyph_outline`.
+    pub fn rasterize_glyph_msdf(&self, codepoint: char, spread: u32) -> Result<ColoredGlyph> {
+        if spread == 0 {
+            return Err(Error::UserError("The spread of a distance field must be positive!".into()));
+        }
+        let outline = self.glyph_outline(codepoint)?;
+        let glyph = sdf::to_msdf(&outline, spread as usize);
+        let mut glyph = ColoredGlyph{
+            character: codepoint,
+            // The outline is relative to the baseline
+            y_offset: glyph.y_offset + self.inner.ascent(),
+            cell: self.cell(self.face.ttf.glyph_index(codepoint)),
+            ..glyph
+        };
+        if self.flip_y {
+            flip_rows(&mut glyph.data, glyph.width * 4);
+        }
+        Ok(glyph)
+    }
+
+    /// Rasterizes the glyph with the given index to a grayscale bitmap. Useful
+    /// for glyphs that don't correspond to a single character, like the
+    /// ligatures reported by `shape_text`. The `character` of the resulting
+    /// glyph is `'\0'`.
+    pub fn rasterize_glyph_index(&mut self, glyph_id: u16) -> Result<RasterizedGlyph> {
+        let glyph_count = self.face.glyph_count();
+        if glyph_id >= glyph_count {
+            return Err(Error::UserError(format!("Glyph index {} is out of range, the font has {} glyphs!",
+                glyph_id, glyph_count)));
+        }
+        let glyph = self.inner.rasterize_glyph_index(glyph_id)?;
+        let glyph = self.with_cell(Some(glyph_id), glyph);
+        Ok(self.untrim(self.correct_gamma(glyph)))
+    }
+
+    /// Rasterizes a glyph reported by `shape_text`. Glyphs substituted while
+    /// shaping, like ligatures, are rasterized by their index, so they match
+    /// the shaped text. The `character` of the result is the one the glyph
+    /// got reported for.
+    pub fn rasterize_shaped(&mut self, glyph: &GlyphPositioning) -> Result<RasterizedGlyph> {
+        if self.face.ttf.glyph_index(glyph.character) == Some(glyph.glyph_id) {
+            return self.rasterize_glyph(glyph.character);
+        }
+        Ok(RasterizedGlyph{
+            character: glyph.character,
+            ..self.rasterize_glyph_index(glyph.glyph_id)?
+        })
+    }
+
+    /// Returns the vector outline of the given character, scaled to the size
+    /// of the face. Components of composite glyphs are merged into a single
+    /// set of contours. Only fonts with TrueType outlines are supported.
+    pub fn glyph_outline(&self, c: char) -> Result<Outline> {
+        let ttf = &self.face.ttf;
+        if !ttf.has_glyph_outlines() {
+            return Err(Error::Unsupported("Outlines other than TrueType ones"));
+        }
+        let glyph = ttf.glyph_index(c).ok_or(Error::GlyphNotFound(c))?;
+        let contours = ttf.glyph_outline(glyph)
+            .map_err(|_| Error::FormatError(format!("Malformed outline for glyph {}!", glyph)))?;
+        let scale = self.inner.em_size() / ttf.units_per_em() as f64;
+        Ok(Outline::from_points(&contours, scale as f32))
     }
 
     /// Shapes the passed in text to get laied out in the plane for rendering.
-    pub fn shape_text<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions, f: F) -> (i32, i32) {
-        self.0.shape_text(text, options, f)
+    /// Returns the size of the laid out text, use `shape_text_from` to get the
+    /// caret position after the last character as well.
+    ///
+    /// Non-printing control characters, the ones `char::is_control` is true
+    /// for (U+0000 to U+001F and U+007F to U+009F) except for the newline and
+    /// the tab, take up no space. They're reported at the caret position of the
+    /// character following them, so stray control bytes don't break the
+    /// layout. The space is a regular character with its own advance, and the
+    /// tab advances as much as a space.
+    pub fn shape_text<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions, f: F) -> Result<(i32, i32)> {
+        self.shape_text_horizontal(text, options, f)
+    }
+
+    /// Shapes the passed in UTF-16 text like `shape_text`, without converting
+    /// it to UTF-8 where the platform works with UTF-16 anyway. Unpaired
+    /// surrogates are shaped as U+FFFD.
+    ///
+    /// The text still gets converted, allocating a UTF-8 copy, if it contains
+    /// tabs or control characters, or with the options handled before the
+    /// backend, `TRIM_TRAILING_WHITESPACE` and `NORMALIZE`. With the
+    /// `unicode-segmentation` feature the grapheme clusters are always found
+    /// on a converted copy.
+    pub fn shape_text_utf16<F: FnMut(GlyphPositioning)>(&self, units: &[u16], options: ShapeOptions, f: F) -> Result<(i32, i32)> {
+        // The options handled before the backend work on strings
+        let has_controls = units.iter()
+            .any(|u| *u == '\t' as u16 || core::char::from_u32(*u as u32).map_or(false, is_zero_width));
+        let convert = has_controls || options.contains(ShapeOptions::TRIM_TRAILING_WHITESPACE);
+        #[cfg(feature = "unicode-normalization")]
+        let convert = convert || options.contains(ShapeOptions::NORMALIZE);
+        if convert {
+            return self.shape_text_horizontal(&String::from_utf16_lossy(units), options, f);
+        }
+        #[cfg(feature = "unicode-segmentation")]
+        let f = with_clusters(&String::from_utf16_lossy(units), f);
+        self.inner.shape_text_utf16(units, options, f)
+    }
+
+    /// Shapes the passed in text like `shape_text`, but with the caret starting
+    /// at `origin`, for continuing a line after a previously shaped run. Lines
+    /// after the first one start at the left edge, below the origin. Returns
+    /// the size of the text along with the caret position after the last
+    /// character, where the next run should start.
+    pub fn shape_text_from<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions,
+        origin: (i32, i32), mut f: F) -> Result<ShapeResult> {
+        let (origin_x, origin_y) = origin;
+        // The extra newline reports the caret position at the end of the text,
+        // so each glyph is only passed on once the next one arrives
+        let mut first_line = true;
+        let mut prev: Option<GlyphPositioning> = None;
+        let (width, _) = self.shape_text_horizontal(&format!("{}\n", text), options, |mut g| {
+            let dx = if first_line { origin_x } else { 0 };
+            if g.character == '\n' {
+                first_line = false;
+            }
+            g.x += dx;
+            g.y += origin_y;
+            g.caret_x += dx;
+            g.caret_y += origin_y;
+            if let Some(prev) = prev.replace(g) {
+                f(prev);
+            }
+        })?;
+        let (caret_x, caret_y) = prev.map_or(origin, |g| (g.caret_x, g.caret_y));
+        let height = if text.is_empty() { 0 } else { caret_y - origin_y + self.inner.line_height() };
+        Ok(ShapeResult{
+            width,
+            height,
+            caret_x,
+            caret_y,
+        })
+    }
+
+    /// Shapes the text horizontally with the backend, applying the options
+    /// the backends don't handle, see `shape_text`.
+    pub(crate) fn shape_text_horizontal<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions,
+        mut f: F) -> Result<(i32, i32)> {
+        if options.line_height != LineHeight::Default {
+            let native = self.inner.line_height();
+            let extra = match options.line_height {
+                LineHeight::Default => 0,
+                LineHeight::Multiple(m) => (native as f64 * m).round() as i32 - native,
+                LineHeight::Pixels(p) => p - native,
+            };
+            let mut line = 0;
+            let options = options.with_line_height(LineHeight::Default);
+            let (width, height) = self.shape_text_horizontal(text, options, |mut g| {
+                g.y += line * extra;
+                g.caret_y += line * extra;
+                if g.character == '\n' {
+                    line += 1;
+                }
+                f(g)
+            })?;
+            if height == 0 {
+                return Ok((width, height));
+            }
+            // The last line keeps its own height
+            let breaks = text.matches('\n').count() as i32;
+            return Ok((width, height + breaks * extra));
+        }
+        #[cfg(feature = "unicode-normalization")]
+        {
+            if options.contains(ShapeOptions::NORMALIZE) {
+                let (normalized, sources) = normalize_nfc(text);
+                #[cfg(feature = "unicode-segmentation")]
+                let mut f = with_clusters(text, f);
+                #[cfg(not(feature = "unicode-segmentation"))]
+                let mut f = f;
+                return self.shape_text_horizontal(&normalized, options & !ShapeOptions::NORMALIZE, |mut g| {
+                    g.index = sources.get(g.index).copied().unwrap_or(g.index);
+                    g.cluster = g.index;
+                    f(g)
+                });
+            }
+        }
+        #[cfg(feature = "unicode-segmentation")]
+        let f = with_clusters(text, f);
+        self.shape_text_printable(text, options, f)
+    }
+
+    /// Shapes the text with the backend, with the zero-width control
+    /// characters taken out and the tabs replaced by spaces.
+    fn shape_text_printable<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions,
+        mut f: F) -> Result<(i32, i32)> {
+        if options.contains(ShapeOptions::TRIM_TRAILING_WHITESPACE) {
+            return self.shape_text_trimmed(text, options & !ShapeOptions::TRIM_TRAILING_WHITESPACE, f);
+        }
+        if !text.chars().any(|c| c == '\t' || is_zero_width(c)) {
+            return self.inner.shape_text(text, options, f);
+        }
+        // The index of each remaining character in the original text, the
+        // backends have no advance for the tab
+        let (indices, printed): (Vec<_>, String) = text.chars()
+            .enumerate()
+            .filter(|(_, c)| !is_zero_width(*c))
+            .map(|(i, c)| (i, if c == '\t' { ' ' } else { c }))
+            .unzip();
+        let tabs: Vec<_> = text.chars().enumerate().filter(|(_, c)| *c == '\t').map(|(i, _)| i).collect();
+        let mut glyphs = Vec::new();
+        let size = self.inner.shape_text(&printed, options, |g| glyphs.push(g))?;
+        // Control characters at the end go to the caret position after the
+        // last character, which is reported for an extra newline
+        let mut end = None;
+        if text.chars().last().map_or(false, is_zero_width) {
+            self.inner.shape_text(&format!("{}\n", printed), options, |g| end = Some((g.caret_x, g.caret_y)))?;
+        }
+        let report = |f: &mut F, index: usize, c: char, (caret_x, caret_y): (i32, i32)| f(GlyphPositioning{
+            character: c,
+            index,
+            cluster: index,
+            glyph_id: 0,
+            x: caret_x,
+            y: caret_y,
+            x_fraction: 0.0,
+            caret_x,
+            caret_y,
+        });
+        let controls: Vec<_> = text.chars().enumerate().filter(|(_, c)| is_zero_width(*c)).collect();
+        let mut next = 0;
+        for mut g in glyphs {
+            g.index = indices[g.index];
+            if tabs.binary_search(&g.index).is_ok() {
+                g.character = '\t';
+            }
+            while next < controls.len() && controls[next].0 < g.index {
+                let (i, c) = controls[next];
+                report(&mut f, i, c, (g.caret_x, g.caret_y));
+                next += 1;
+            }
+            f(g);
+        }
+        for (i, c) in &controls[next..] {
+            report(&mut f, *i, *c, end.unwrap_or_default());
+        }
+        Ok(size)
+    }
+
+    /// Shapes the text like `shape_text_printable`, but measures the lines
+    /// without their trailing whitespace.
+    fn shape_text_trimmed<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions,
+        mut f: F) -> Result<(i32, i32)> {
+        // The extra newline reports the end of the last line
+        let mut glyphs = Vec::new();
+        self.shape_text_printable(&format!("{}\n", text), options, |g| glyphs.push(g))?;
+        let last = glyphs.last().map_or(0, |g| g.caret_y);
+        let end = text.chars().count();
+        let mut width = 0;
+        // Where the whitespace at the end of the current line starts
+        let mut trailing = None;
+        for g in glyphs {
+            if g.character == '\n' {
+                width = core::cmp::max(width, trailing.unwrap_or(g.x));
+                trailing = None;
+            }
+            else if g.character.is_whitespace() {
+                trailing = trailing.or(Some(g.caret_x));
+            }
+            else {
+                trailing = None;
+            }
+            if g.index < end {
+                f(g);
+            }
+        }
+        let height = if text.is_empty() { 0 } else { last + self.inner.line_height() };
+        Ok((width, height))
+    }
+
+    /// Shapes the passed in text like `shape_text`, but in the given writing
+    /// mode.
+    pub fn shape_text_with_mode<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions,
+        mode: WritingMode, f: F) -> Result<(i32, i32)> {
+        match mode {
+            WritingMode::Horizontal => self.shape_text_horizontal(text, options, f),
+            WritingMode::VerticalRL => self.shape_text_vertical(text, options, f),
+        }
+    }
+
+    /// Returns the size of a cell of the grid `shape_text_grid` lays text out
+    /// on, the advance of the face times the line height. The advance is the
+    /// one of 'M', as the face is expected to be monospaced.
+    pub fn grid_cell(&self) -> (i32, i32) {
+        let ttf = &self.face.ttf;
+        let glyph_id = ttf.glyph_index('M');
+        let cell = match glyph_id.and_then(|g| ttf.horizontal_advance(g)) {
+            Some(advance) if advance != 0 => self.cell(glyph_id),
+            // Fall back to the widest glyph
+            _ => {
+                let design_scale = self.inner.em_size() / ttf.units_per_em() as f64;
+                let width = ttf.max_horizontal_advance() as f64 * design_scale * self.stretch;
+                GlyphBox{ x: 0, y: 0, width: width.round() as i32, height: self.inner.line_height() }
+            },
+        };
+        (cell.width, cell.height)
+    }
+
+    /// Lays out the text on a fixed grid, like a terminal does, reporting each
+    /// character to `f` at the top left corner of its cell. Characters take a
+    /// cell each, regardless of their advance, and characters without an
+    /// advance, like combining marks, go into the cell of the character before
+    /// them, as do the rest of a grapheme cluster. Newlines are reported at
+    /// the end of their lines. No ligatures or kerning are applied. Returns
+    /// the number of columns and rows used. To rasterize glyphs covering whole
+    /// cells, scale the face with `ScaleBuilder::trim` off.
+    pub fn shape_text_grid<F: FnMut(GlyphPositioning)>(&self, text: &str, mut f: F) -> (usize, usize) {
+        let ttf = &self.face.ttf;
+        let (cell_width, cell_height) = self.grid_cell();
+        #[cfg(feature = "unicode-segmentation")]
+        let clusters = cluster_indices(text);
+        #[cfg(not(feature = "unicode-segmentation"))]
+        let clusters: Vec<_> = (0..text.chars().count()).collect();
+        let (mut column, mut row, mut columns) = (0usize, 0usize, 0usize);
+        for (index, c) in text.chars().enumerate() {
+            let glyph_id = ttf.glyph_index(c);
+            let cluster = clusters.get(index).copied().unwrap_or(index);
+            let stacks = column > 0 && !is_zero_width(c) && c != '\n' && (
+                clusters.get(index - 1) == Some(&cluster)
+                || glyph_id.and_then(|g| ttf.horizontal_advance(g)) == Some(0));
+            let cell_column = if stacks { column - 1 } else { column };
+            let x = cell_column as i32 * cell_width;
+            let y = row as i32 * cell_height;
+            f(GlyphPositioning{
+                character: c,
+                index,
+                cluster,
+                glyph_id: glyph_id.unwrap_or(0),
+                x,
+                y,
+                x_fraction: 0.0,
+                caret_x: x,
+                caret_y: y,
+            });
+            if c == '\n' {
+                column = 0;
+                row += 1;
+            }
+            else if !stacks && !is_zero_width(c) {
+                column += 1;
+                columns = columns.max(column);
+            }
+        }
+        let rows = if text.is_empty() { 0 } else { row + 1 };
+        (columns, rows)
+    }
+
+    /// Measures each line of the passed in text, as `shape_text` would lay it
+    /// out.
+    pub fn measure_lines(&self, text: &str, options: ShapeOptions) -> Result<Vec<LineMetrics>> {
+        // The same metrics the lines are laid out with, so the baselines agree
+        let ascent = self.inner.ascent();
+        let descent = self.inner.line_height() - ascent;
+        // The extra newline makes sure the end of the last line gets reported
+        let mut lines = Vec::new();
+        self.shape_text_horizontal(&format!("{}\n", text), options, |g| {
+            if g.character == '\n' {
+                lines.push(LineMetrics{
+                    width: g.x,
+                    baseline: g.caret_y + ascent,
+                    ascent,
+                    descent,
+                });
+            }
+        })?;
+        Ok(lines)
+    }
+
+    /// Lays out the text in top-to-bottom columns, going from right to left.
+    /// The glyphs are selected by the horizontal shaping, but advance by the
+    /// vertical metrics of the font, or the em square if the font has none.
+    fn shape_text_vertical<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions, mut f: F) -> Result<(i32, i32)> {
+        let ttf = &self.face.ttf;
+        let em_size = self.inner.em_size();
+        let design_scale = em_size / ttf.units_per_em() as f64;
+        let to_pixels = |units: i32| (units as f64 * design_scale).round() as i32;
+        let em = em_size.round() as i32;
+        let column_width = ttf.vertical_line_advance().map(to_pixels).unwrap_or(em);
+
+        // Shape horizontally first. The extra newline makes sure the end of
+        // every line gets reported, so we know the advance of each glyph.
+        let mut glyphs = Vec::new();
+        self.shape_text_horizontal(&format!("{}\n", text), options, |g| glyphs.push(g))?;
+        glyphs.pop();
+
+        let columns = text.split('\n').count() as i32;
+        let mut column = 0;
+        let mut yoff = 0;
+        let mut max_h = 0;
+        for (i, g) in glyphs.iter().enumerate() {
+            // The first column is the rightmost one
+            let column_x = (columns - 1 - column) * column_width;
+            if g.character == '\n' {
+                f(GlyphPositioning{
+                    character: '\n',
+                    index: g.index,
+                    cluster: g.cluster,
+                    glyph_id: g.glyph_id,
+                    x: column_x,
+                    y: yoff,
+                    x_fraction: 0.0,
+                    caret_x: column_x,
+                    caret_y: yoff,
+                });
+                column += 1;
+                yoff = 0;
+                continue;
+            }
+            // Center the glyph horizontally in the column
+            let next_x = glyphs.get(i + 1).map(|n| n.x).unwrap_or(g.x);
+            let x_advance = next_x - g.x;
+            // Not every backend reports glyph indices
+            let y_advance = Some(g.glyph_id)
+                .filter(|id| *id != 0)
+                .or_else(|| ttf.glyph_index(g.character))
+                .and_then(|id| ttf.vertical_advance(id))
+                .map(|a| to_pixels(a as i32))
+                .unwrap_or(em);
+            f(GlyphPositioning{
+                character: g.character,
+                index: g.index,
+                cluster: g.cluster,
+                glyph_id: g.glyph_id,
+                x: column_x + (column_width - x_advance) / 2,
+                y: yoff,
+                x_fraction: 0.0,
+                caret_x: column_x,
+                caret_y: yoff,
+            });
+            yoff += y_advance;
+            max_h = core::cmp::max(max_h, yoff);
+        }
+        Ok((columns * column_width, max_h))
+    }
+}
+
+/// Composites an RGBA color over another, source over destination, with
+/// straight alpha.
+fn composite(src: [u8; 4], dst: [u8; 4]) -> [u8; 4] {
+    let src_a = src[3] as u32;
+    let dst_a = (dst[3] as u32 * (255 - src_a) + 127) / 255;
+    let out_a = src_a + dst_a;
+    let blend = |src: u8, dst: u8| if out_a == 0 {
+        0
+    }
+    else {
+        ((src as u32 * src_a + dst as u32 * dst_a + out_a / 2) / out_a) as u8
+    };
+    [blend(src[0], dst[0]), blend(src[1], dst[1]), blend(src[2], dst[2]), out_a as u8]
+}
+
+/// Reverses the order of the rows of a bitmap in place.
+#[cfg(feature = "std")]
+fn flip_rows(data: &mut [u8], row_len: usize) {
+    if row_len == 0 {
+        return;
+    }
+    let rows = data.len() / row_len;
+    for y in 0..(rows / 2) {
+        let (top, bottom) = data.split_at_mut((rows - 1 - y) * row_len);
+        top[(y * row_len)..((y + 1) * row_len)].swap_with_slice(&mut bottom[..row_len]);
+    }
+}
+
+/// Normalizes the text to NFC, along with the index of the source character
+/// each normalized character came from. The text is normalized in runs that
+/// nothing composes across, the characters of a run that changed length
+/// all map to its first character.
+#[cfg(all(feature = "std", feature = "unicode-normalization"))]
+fn normalize_nfc(text: &str) -> (String, Vec<usize>) {
+    use unicode_normalization::UnicodeNormalization;
+    use unicode_normalization::char::canonical_combining_class;
+    let mut normalized = String::new();
+    let mut sources = Vec::new();
+    fn flush(run: &str, run_start: usize, normalized: &mut String, sources: &mut Vec<usize>) {
+        let chars: Vec<_> = run.nfc().collect();
+        let same_length = chars.len() == run.chars().count();
+        for (i, c) in chars.into_iter().enumerate() {
+            normalized.push(c);
+            sources.push(if same_length { run_start + i } else { run_start });
+        }
+    }
+    // The current run and the index of its first character
+    let mut run = String::new();
+    let mut run_start = 0;
+    for (index, c) in text.chars().enumerate() {
+        // Only a starter can begin a new run, if it doesn't compose with the
+        // run before it
+        if !run.is_empty() && canonical_combining_class(c) == 0 {
+            let mut joined = run.clone();
+            joined.push(c);
+            let separate = run.nfc().chain(core::iter::once(c).nfc());
+            if joined.nfc().eq(separate) {
+                flush(&run, run_start, &mut normalized, &mut sources);
+                run.clear();
+                run_start = index;
+            }
+        }
+        run.push(c);
+    }
+    flush(&run, run_start, &mut normalized, &mut sources);
+    (normalized, sources)
+}
+
+/// Returns the index of the grapheme cluster of each character of the text.
+#[cfg(all(feature = "std", feature = "unicode-segmentation"))]
+fn cluster_indices(text: &str) -> Vec<usize> {
+    use unicode_segmentation::UnicodeSegmentation;
+    text.graphemes(true)
+        .enumerate()
+        .flat_map(|(i, g)| core::iter::repeat_n(i, g.chars().count()))
+        .collect()
+}
+
+/// Wraps the callback of shaping the text to fill in the grapheme clusters
+/// of the reported characters.
+#[cfg(all(feature = "std", feature = "unicode-segmentation"))]
+fn with_clusters<F: FnMut(GlyphPositioning)>(text: &str, mut f: F) -> impl FnMut(GlyphPositioning) {
+    let clusters = cluster_indices(text);
+    move |mut g| {
+        g.cluster = clusters.get(g.index).copied().unwrap_or(g.index);
+        f(g)
+    }
+}
+
+/// The largest em size in pixels faces can be scaled to. Glyphs of larger
+/// faces wouldn't fit the bitmaps they get drawn to.
+#[cfg(feature = "std")]
+const MAX_EM_SIZE: f64 = 16384.0;
+
+/// Checks if a face can be scaled to `pts` points at `dpi` dots per inch.
+/// Backends would quietly fall back to a default size for em sizes below a
+/// pixel, which GDI rounds to zero, so those are rejected along with the
+/// negative, NaN and too large sizes.
+#[cfg(feature = "std")]
+fn check_size(pts: f64, dpi: f64) -> Result<()> {
+    const POINTS_PER_INCH: f64 = 72.0;
+    let em_size = pts * dpi / POINTS_PER_INCH;
+    // Comparisons with NaN are false, so it's rejected too
+    let valid = pts > 0.0 && dpi > 0.0 && (1.0..=MAX_EM_SIZE).contains(&em_size);
+    if !valid {
+        return Err(Error::UserError(format!("Invalid size {} pt at {} DPI!", pts, dpi)));
+    }
+    Ok(())
+}
+
+/// Checks if a bitmap of the given size fits into a destination of `dst_len`
+/// bytes and `dst_width` pixels wide, with its top left corner at `(x, y)`.
+#[cfg(feature = "std")]
+pub(crate) fn check_fits(dst_len: usize, dst_width: usize, x: usize, y: usize,
+    width: usize, height: usize) -> Result<()> {
+    let end = x.checked_add(width).filter(|right| *right <= dst_width)
+        .and_then(|_| y.checked_add(height))
+        .and_then(|bottom| bottom.checked_mul(dst_width));
+    match end {
+        Some(end) if end <= dst_len => Ok(()),
+        _ => Err(Error::UserError(format!("Glyph of size {}x{} doesn't fit the destination at {}, {}!",
+            width, height, x, y))),
     }
 }
 
+/// Copies the coverage of the glyph into an 8-bit grayscale bitmap
+/// `dst_width` pixels wide, with the top left corner at `(x, y)`.
+#[cfg(feature = "std")]
+pub(crate) fn copy_into(glyph: &RasterizedGlyph, dst: &mut [u8], dst_width: usize, x: usize, y: usize) -> Result<()> {
+    check_fits(dst.len(), dst_width, x, y, glyph.width, glyph.height)?;
+    for gy in 0..glyph.height {
+        let row = (y + gy) * dst_width + x;
+        for gx in 0..glyph.width {
+            dst[row + gx] = glyph.coverage(gx, gy);
+        }
+    }
+    Ok(())
+}
+
+/// Returns true for the non-printing control characters, which take up no
+/// space in shaped text. The newline breaks the line and the tab is shaped as
+/// a space.
+#[cfg(feature = "std")]
+fn is_zero_width(c: char) -> bool {
+    c != '\n' && c != '\t' && c.is_control()
+}
+
+// Compile-time check that the shareable types really are shareable.
+#[cfg(feature = "std")]
+#[allow(dead_code)]
+fn assert_thread_safety() {
+    fn send_sync<T: Send + Sync>() {}
+    send_sync::<Font>();
+    send_sync::<FontFace>();
+}
+
 /// Represents a glyph that has been rasterized into a byte array.
+///
+/// Offsets are relative to the position the glyph is drawn at, which is the
+/// caret position at the top of the line, like the positions `shape_text`
+/// reports. X grows to the right and Y grows downwards. To align glyphs on a
+/// baseline instead, like ones from different fonts, use `bitmap_left` and
+/// `bitmap_top`, which are relative to the pen position on the baseline.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RasterizedGlyph {
+    /// The character that got rasterized.
+    pub character: char,
+    /// Horizontal offset of the bitmap from the drawing position.
+    pub x_offset: i32,
+    /// Vertical offset of the bitmap from the drawing position, downwards.
+    pub y_offset: i32,
+    /// Width of the bitmap in pixels.
+    pub width: usize,
+    /// Height of the bitmap in pixels.
+    pub height: usize,
+    /// The number of bits each pixel of `data` takes.
+    pub bit_depth: BitDepth,
+    /// The layout box of the glyph, the advance width times the line height.
+    /// Unlike the bitmap, which only covers the ink of the glyph, this is what
+    /// backgrounds and selection highlights should cover.
+    pub cell: GlyphBox,
+    /// The distance of the baseline from the drawing position, downwards.
+    pub baseline: i32,
+    /// The bitmap data itself (row-major, grayscale). Pixels are packed as
+    /// described by `bit_depth`, each row starts on a new byte.
+    pub data: Box<[u8]>,
+}
+
+/// A glyph rasterized into a buffer of the scaled face, see
+/// `ScaledFontFace::with_rasterized`. The fields are the ones of
+/// `RasterizedGlyph`, with 8-bit coverage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GlyphView<'a> {
+    /// The character that got rasterized.
+    pub character: char,
+    /// Horizontal offset of the bitmap from the drawing position.
+    pub x_offset: i32,
+    /// Vertical offset of the bitmap from the drawing position, downwards.
+    pub y_offset: i32,
+    /// Width of the bitmap in pixels.
+    pub width: usize,
+    /// Height of the bitmap in pixels.
+    pub height: usize,
+    /// The layout box of the glyph, see `RasterizedGlyph::cell`.
+    pub cell: GlyphBox,
+    /// The distance of the baseline from the drawing position, downwards.
+    pub baseline: i32,
+    /// The coverage of the pixels in row-major order, one byte each.
+    pub data: &'a [u8],
+}
+
+impl RasterizedGlyph {
+    /// Returns the distance of the left edge of the bitmap from the pen
+    /// position on the baseline, growing to the right. Same as FreeType's
+    /// `bitmap_left`.
+    pub fn bitmap_left(&self) -> i32 {
+        self.x_offset
+    }
+
+    /// Returns the distance of the top edge of the bitmap from the pen
+    /// position on the baseline, growing upwards. Same as FreeType's
+    /// `bitmap_top`, so for a pen at `(x, y)` in a Y-down image the bitmap
+    /// goes to `(x + bitmap_left, y - bitmap_top)`.
+    pub fn bitmap_top(&self) -> i32 {
+        self.baseline - self.y_offset
+    }
+
+    /// Returns the box covered by the ink of the glyph, which is the bitmap
+    /// itself placed at its offsets.
+    pub fn ink_box(&self) -> GlyphBox {
+        GlyphBox{
+            x: self.x_offset,
+            y: self.y_offset,
+            width: self.width as i32,
+            height: self.height as i32,
+        }
+    }
+
+    /// Returns the coverage of the pixel at the given position, scaled to the
+    /// 0-255 range regardless of the bit depth.
+    pub fn coverage(&self, x: usize, y: usize) -> u8 {
+        let row = &self.data[(y * self.bit_depth.bytes_per_row(self.width))..];
+        match self.bit_depth {
+            BitDepth::Eight => row[x],
+            BitDepth::Four => ((row[x / 2] >> (4 - 4 * (x % 2))) & 0x0f) * 17,
+            BitDepth::One => if row[x / 8] & (0x80 >> (x % 8)) != 0 { 0xff } else { 0 },
+        }
+    }
+
+    /// Copies the coverage of the glyph into an 8-bit grayscale bitmap of the
+    /// given size, with the top left corner of the glyph bitmap at `(x, y)`.
+    /// For a glyph drawn at a position reported by `shape_text`, that's the
+    /// position plus the offsets. The parts falling outside of the
+    /// destination, including negative positions, are clipped. Panics if
+    /// `dst` is smaller than `dst_width * dst_height`.
+    pub fn blit_into(&self, dst: &mut [u8], dst_width: usize, dst_height: usize, x: i32, y: i32) {
+        use core::cmp::{min, max};
+        assert!(dst.len() >= dst_width.saturating_mul(dst_height), "Destination smaller than its dimensions!");
+        // The rows and columns of the glyph bitmap that land in the destination
+        let (x, y) = (x as i64, y as i64);
+        let left = max(0, -x);
+        let right = min(self.width as i64, dst_width as i64 - x);
+        let top = max(0, -y);
+        let bottom = min(self.height as i64, dst_height as i64 - y);
+        for gy in top..bottom {
+            let row = (y + gy) as usize * dst_width;
+            for gx in left..right {
+                dst[row + (x + gx) as usize] = self.coverage(gx as usize, gy as usize);
+            }
+        }
+    }
+
+    /// Blends the glyph tinted with the RGBA `color` over an RGBA bitmap of
+    /// the given size, with straight alpha, placed like with `blit_into`.
+    /// Unlike copying the coverage, this keeps the antialiased edges right on
+    /// any background. The blending happens on the stored color values, so
+    /// for gamma-correct edges scale the face with `ScaleBuilder::gamma`.
+    /// Panics if `dst` is smaller than `dst_width * dst_height * 4`.
+    pub fn blend_into(&self, dst: &mut [u8], dst_width: usize, dst_height: usize, x: i32, y: i32, color: [u8; 4]) {
+        use core::cmp::{min, max};
+        assert!(dst.len() / 4 >= dst_width.saturating_mul(dst_height), "Destination smaller than its dimensions!");
+        let (x, y) = (x as i64, y as i64);
+        let left = max(0, -x);
+        let right = min(self.width as i64, dst_width as i64 - x);
+        let top = max(0, -y);
+        let bottom = min(self.height as i64, dst_height as i64 - y);
+        for gy in top..bottom {
+            let row = (y + gy) as usize * dst_width;
+            for gx in left..right {
+                let offs = (row + (x + gx) as usize) * 4;
+                let src_a = (color[3] as u32 * self.coverage(gx as usize, gy as usize) as u32 + 127) / 255;
+                let src = [color[0], color[1], color[2], src_a as u8];
+                let pixel = &mut dst[offs..(offs + 4)];
+                let bg = [pixel[0], pixel[1], pixel[2], pixel[3]];
+                pixel.copy_from_slice(&composite(src, bg));
+            }
+        }
+    }
+
+    /// Converts the glyph to the given bit depth. Converting to a lower depth
+    /// quantizes the coverage, converting back doesn't restore it.
+    pub fn to_bit_depth(&self, bit_depth: BitDepth) -> RasterizedGlyph {
+        let coverage: Vec<_> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.coverage(x, y))
+            .collect();
+        RasterizedGlyph{
+            character: self.character,
+            x_offset: self.x_offset,
+            y_offset: self.y_offset,
+            width: self.width,
+            height: self.height,
+            bit_depth,
+            cell: self.cell,
+            baseline: self.baseline,
+            data: pack_coverage(&coverage, self.width, self.height, bit_depth),
+        }
+    }
+
+    /// Tints the coverage of the glyph with the `foreground` color, and
+    /// composites it over the `background` color if given. Colors are RGBA,
+    /// and the result has straight, not premultiplied alpha.
+    pub fn to_colored(&self, foreground: [u8; 4], background: Option<[u8; 4]>) -> ColoredGlyph {
+        let mut data = Vec::with_capacity(self.width * self.height * 4);
+        let pixels = (0..self.height).flat_map(|y| (0..self.width).map(move |x| (x, y)));
+        for (x, y) in pixels {
+            let src_a = (foreground[3] as u32 * self.coverage(x, y) as u32 + 127) / 255;
+            let src = [foreground[0], foreground[1], foreground[2], src_a as u8];
+            let pixel = match background {
+                None => src,
+                Some(bg) => composite(src, bg),
+            };
+            data.extend_from_slice(&pixel);
+        }
+        ColoredGlyph{
+            character: self.character,
+            x_offset: self.x_offset,
+            y_offset: self.y_offset,
+            width: self.width,
+            height: self.height,
+            cell: self.cell,
+            data: data.into_boxed_slice(),
+        }
+    }
+
+    /// Blurs the coverage of the glyph, like for soft drop shadows. The blur
+    /// approximates a Gaussian one with box blurs, spreading the coverage by
+    /// about `radius` pixels. The bitmap grows by that on every side, the
+    /// offsets move accordingly. The result is always 8-bit.
+    #[cfg(feature = "std")]
+    pub fn blur(&self, radius: f32) -> RasterizedGlyph {
+        blur::blur(self, radius)
+    }
+
+    /// Tints the coverage of the glyph with the `fill` color and surrounds it
+    /// with an outline of the `stroke` color, `stroke_width` pixels wide, like
+    /// for captions readable over any background. The bitmap grows by the
+    /// width of the stroke on every side, the offsets move accordingly. Colors
+    /// are RGBA, the result has straight alpha over a transparent background.
+    pub fn to_outlined(&self, fill: [u8; 4], stroke: [u8; 4], stroke_width: usize) -> ColoredGlyph {
+        let r = stroke_width as i64;
+        let width = self.width + 2 * stroke_width;
+        let height = self.height + 2 * stroke_width;
+        // The coverage at a position of the grown bitmap
+        let coverage = |x: i64, y: i64| {
+            let (gx, gy) = (x - r, y - r);
+            if gx < 0 || gy < 0 || gx >= self.width as i64 || gy >= self.height as i64 {
+                0
+            }
+            else {
+                self.coverage(gx as usize, gy as usize)
+            }
+        };
+        // The stroke is the coverage grown by a disc of the stroke width
+        let disc: Vec<_> = (-r..=r)
+            .flat_map(|dy| (-r..=r).map(move |dx| (dx, dy)))
+            .filter(|(dx, dy)| dx * dx + dy * dy <= r * r)
+            .collect();
+        let mut data = Vec::with_capacity(width * height * 4);
+        for y in 0..(height as i64) {
+            for x in 0..(width as i64) {
+                let grown = disc.iter().map(|(dx, dy)| coverage(x + dx, y + dy)).max().unwrap_or(0);
+                let stroke_a = (stroke[3] as u32 * grown as u32 + 127) / 255;
+                let fill_a = (fill[3] as u32 * coverage(x, y) as u32 + 127) / 255;
+                let pixel = composite(
+                    [fill[0], fill[1], fill[2], fill_a as u8],
+                    [stroke[0], stroke[1], stroke[2], stroke_a as u8]);
+                data.extend_from_slice(&pixel);
+            }
+        }
+        ColoredGlyph{
+            character: self.character,
+            x_offset: self.x_offset - stroke_width as i32,
+            y_offset: self.y_offset - stroke_width as i32,
+            width,
+            height,
+            cell: self.cell,
+            data: data.into_boxed_slice(),
+        }
+    }
+}
+
+/// A box relative to the position a glyph is drawn at, which is the caret
+/// position at the top of the line.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GlyphBox {
+    /// Horizontal position of the left edge.
+    pub x: i32,
+    /// Vertical position of the top edge.
+    pub y: i32,
+    /// The width of the box.
+    pub width: i32,
+    /// The height of the box.
+    pub height: i32,
+}
+
+/// Tells why a rasterized glyph has ink or not, see
+/// `ScaledFontFace::ink_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InkStatus {
+    /// The bitmap has ink.
+    Inked,
+    /// The glyph has no outline to draw, like whitespace. Fonts without
+    /// TrueType outlines report every glyph without ink like this.
+    Blank,
+    /// The font has no glyph for the character, so whatever got drawn, if
+    /// anything, is a substitute.
+    Missing,
+    /// The glyph has an outline, but nothing got drawn.
+    Unrendered,
+}
+
+/// The number of bits used for the coverage of a pixel in a bitmap.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+    /// One byte per pixel.
+    #[default]
+    Eight,
+    /// Two pixels per byte, the first one in the high 4 bits.
+    Four,
+    /// Eight pixels per byte, the first one in the highest bit. The coverage
+    /// is thresholded at the half.
+    One,
+}
+
+impl BitDepth {
+    /// Returns the number of bytes a row of `width` pixels takes.
+    pub fn bytes_per_row(self, width: usize) -> usize {
+        match self {
+            BitDepth::Eight => width,
+            BitDepth::Four => width.div_ceil(2),
+            BitDepth::One => width.div_ceil(8),
+        }
+    }
+}
+
+/// Packs 8-bit coverage of the given dimensions to the given bit depth.
+pub(crate) fn pack_coverage(coverage: &[u8], width: usize, height: usize, bit_depth: BitDepth) -> Box<[u8]> {
+    if bit_depth == BitDepth::Eight {
+        return coverage.into();
+    }
+    let row_bytes = bit_depth.bytes_per_row(width);
+    let mut data = alloc::vec![0u8; row_bytes * height].into_boxed_slice();
+    for y in 0..height {
+        let src = &coverage[(y * width)..((y + 1) * width)];
+        let dst = &mut data[(y * row_bytes)..((y + 1) * row_bytes)];
+        for (x, c) in src.iter().enumerate() {
+            match bit_depth {
+                // Round to the nearest of the 16 levels
+                BitDepth::Four => dst[x / 2] |= (((*c as u32 + 8) / 17) << (4 - 4 * (x % 2))) as u8,
+                BitDepth::One => if *c >= 0x80 { dst[x / 8] |= 0x80 >> (x % 8) },
+                BitDepth::Eight => unreachable!(),
+            }
+        }
+    }
+    data
+}
+
+/// Scales 8-bit coverage to the given dimensions, averaging the source pixels
+/// each target pixel covers.
+#[cfg(feature = "std")]
+fn resample_coverage(coverage: &[u8], width: usize, height: usize, new_width: usize, new_height: usize) -> Vec<u8> {
+    let scale_x = width as f64 / new_width as f64;
+    let scale_y = height as f64 / new_height as f64;
+    // The extent of a target pixel in source pixels
+    let span = |t: usize, scale: f64, len: usize| {
+        let start = t as f64 * scale;
+        let end = (start + scale).min(len as f64);
+        (start, end, start.floor() as usize, (end.ceil() as usize).min(len))
+    };
+    // The length of the overlap of a source pixel and a span
+    let overlap = |s: usize, start: f64, end: f64| end.min(s as f64 + 1.0) - start.max(s as f64);
+    let mut result = Vec::with_capacity(new_width * new_height);
+    for ty in 0..new_height {
+        let (y_start, y_end, sy0, sy1) = span(ty, scale_y, height);
+        for tx in 0..new_width {
+            let (x_start, x_end, sx0, sx1) = span(tx, scale_x, width);
+            let mut sum = 0.0;
+            for sy in sy0..sy1 {
+                let wy = overlap(sy, y_start, y_end);
+                for sx in sx0..sx1 {
+                    sum += coverage[sy * width + sx] as f64 * wy * overlap(sx, x_start, x_end);
+                }
+            }
+            let area = (x_end - x_start) * (y_end - y_start);
+            result.push((sum / area).round().min(255.0) as u8);
+        }
+    }
+    result
+}
+
+/// Represents a glyph that has been rasterized into an RGBA byte array, see
+/// `RasterizedGlyph::to_colored`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColoredGlyph {
     /// The character that got rasterized.
     pub character: char,
     /// Horizontal offset to add when rendering.
@@ -84,79 +2305,384 @@ pub struct RasterizedGlyph {
     pub width: usize,
     /// Height of the bitmap in pixels.
     pub height: usize,
-    /// The bitmap data itself (row-major, grayscale, one byte per pixel).
+    /// The layout box of the glyph, see `RasterizedGlyph::cell`.
+    pub cell: GlyphBox,
+    /// The bitmap data itself (row-major, RGBA, four bytes per pixel).
     pub data: Box<[u8]>,
 }
 
+/// Placement information of a glyph, as atlas descriptors expect it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct GlyphMetrics {
+    /// Horizontal offset to add when rendering, see `RasterizedGlyph`.
+    pub x_offset: i32,
+    /// Vertical offset to add when rendering, see `RasterizedGlyph`.
+    pub y_offset: i32,
+    /// The distance to move the caret horizontally after the glyph.
+    pub x_advance: i32,
+}
+
 /// Represents the parameter pack passed back to the user for text shaping.
 /// Contains information about the actual character's positioning.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct GlyphPositioning {
     /// The character being positioned.
     pub character: char,
     /// The index of the character (0 based, relative to the first one) being
     /// positioned.
     pub index: usize,
+    /// The index of the grapheme cluster the character belongs to (0 based,
+    /// relative to the first one), like a base character with its combining
+    /// marks, or an emoji with its modifiers. Carets should only be placed
+    /// before the first character of a cluster. Clusters are only found with
+    /// the "unicode-segmentation" feature, otherwise every character is its
+    /// own cluster.
+    pub cluster: usize,
+    /// The index of the glyph in the font that represents the character. When
+    /// characters get substituted by a ligature, this is the ligature glyph and
+    /// the rest of the characters in it are not reported.
+    pub glyph_id: u16,
     /// The x offset from 0, 0.
     pub x: i32,
     /// The y offset from 0, 0.
     pub y: i32,
+    /// The fractional part of the x offset, between 0 and 1, for backends that
+    /// position glyphs more precisely than whole pixels. Rasterize with
+    /// `rasterize_glyph_subpixel` to account for it.
+    pub x_fraction: f32,
     /// The caret's x position before this character.
     pub caret_x: i32,
     /// The caret's y position before this character.
     pub caret_y: i32,
 }
 
-/// Contains options for shaping text.
+/// The extent of shaped text, see `ScaledFontFace::shape_text_from`.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-pub struct ShapeOptions(u8);
+pub struct ShapeResult {
+    /// The width of the laid out text, like `shape_text` returns it.
+    pub width: i32,
+    /// The height of the laid out text, like `shape_text` returns it.
+    pub height: i32,
+    /// The caret's x position after the last character, where the next run
+    /// of text continues. Unlike the width, this includes the origin and
+    /// doesn't depend on the ink of the last glyph.
+    pub caret_x: i32,
+    /// The caret's y position after the last character.
+    pub caret_y: i32,
+}
+
+/// Measurements of a line of shaped text, see `ScaledFontFace::measure_lines`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LineMetrics {
+    /// The width of the line in pixels.
+    pub width: i32,
+    /// The distance of the baseline from the top of the text in pixels.
+    pub baseline: i32,
+    /// The distance of the top of the line from the baseline in pixels.
+    pub ascent: i32,
+    /// The distance of the bottom of the line from the baseline in pixels.
+    pub descent: i32,
+}
+
+/// The distance between the baselines of consecutive lines of text.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum LineHeight {
+    /// The line height of the font.
+    #[default]
+    Default,
+    /// A multiple of the line height of the font, like 1.5 for one and a half
+    /// line spacing.
+    Multiple(f64),
+    /// A fixed line height in pixels.
+    Pixels(i32),
+}
+
+/// Contains options for shaping text. The flags are combined with the bit
+/// operators, which keep the line height of the left side.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ShapeOptions {
+    flags      : u8,
+    line_height: LineHeight,
+}
 
 impl ShapeOptions {
     /// Use kerning when calculating coordienates, meaning that spacing is
     /// adjusted between characters for more natural reading.
-    pub const USE_KERNING: ShapeOptions = ShapeOptions(0b00000001);
+    pub const USE_KERNING: ShapeOptions = ShapeOptions::flag(0b00000001);
+
+    /// Substitute standard ligatures (like "fi") when the font defines them.
+    /// Characters merged into a ligature glyph are not reported separately.
+    pub const USE_LIGATURES: ShapeOptions = ShapeOptions::flag(0b00000010);
+
+    /// Leave the whitespace at the end of the lines out of the measured width,
+    /// so trailing spaces don't throw off aligning the text. The whitespace is
+    /// still reported and advances the caret.
+    pub const TRIM_TRAILING_WHITESPACE: ShapeOptions = ShapeOptions::flag(0b00000100);
+
+    /// Normalize the text to NFC before shaping, so precomposed characters
+    /// and combining sequences, like "é" and "e" followed by U+0301, come out
+    /// the same. The reported characters are the ones of the normalized text,
+    /// but their indices and clusters point into the passed in text, at the
+    /// first character of the sequence they were normalized from.
+    #[cfg(feature = "unicode-normalization")]
+    pub const NORMALIZE: ShapeOptions = ShapeOptions::flag(0b00001000);
+
+    const fn flag(flags: u8) -> Self {
+        Self{ flags, line_height: LineHeight::Default }
+    }
+
+    /// Returns the options with the distance between the baselines of the
+    /// lines changed. The returned height of the shaped text reflects the
+    /// changed spacing.
+    pub fn with_line_height(self, line_height: LineHeight) -> Self {
+        Self{ line_height, ..self }
+    }
+
+    /// Returns the distance between the baselines of the lines.
+    pub fn line_height(&self) -> LineHeight {
+        self.line_height
+    }
 
     /// Returns true if a given option (or options) is present in the options.
     pub fn contains(&self, option: ShapeOptions) -> bool {
-        (*self & option) == option
+        (self.flags & option.flags) == option.flags
     }
 }
 
 impl BitOr for ShapeOptions {
     type Output = Self;
-    fn bitor(self, rhs: Self) -> Self::Output { Self(self.0 | rhs.0) }
+    fn bitor(self, rhs: Self) -> Self::Output { Self{ flags: self.flags | rhs.flags, ..self } }
 }
 
 impl BitOrAssign for ShapeOptions {
-    fn bitor_assign(&mut self, rhs: Self) { self.0 |= rhs.0; }
+    fn bitor_assign(&mut self, rhs: Self) { self.flags |= rhs.flags; }
 }
 
 impl BitAnd for ShapeOptions {
     type Output = Self;
-    fn bitand(self, rhs: Self) -> Self::Output { Self(self.0 & rhs.0) }
+    fn bitand(self, rhs: Self) -> Self::Output { Self{ flags: self.flags & rhs.flags, ..self } }
 }
 
 impl BitAndAssign for ShapeOptions {
-    fn bitand_assign(&mut self, rhs: Self) { self.0 &= rhs.0; }
+    fn bitand_assign(&mut self, rhs: Self) { self.flags &= rhs.flags; }
 }
 
 impl BitXor for ShapeOptions {
     type Output = Self;
-    fn bitxor(self, rhs: Self) -> Self::Output { Self(self.0 ^ rhs.0) }
+    fn bitxor(self, rhs: Self) -> Self::Output { Self{ flags: self.flags ^ rhs.flags, ..self } }
 }
 
 impl BitXorAssign for ShapeOptions {
-    fn bitxor_assign(&mut self, rhs: Self) { self.0 ^= rhs.0; }
+    fn bitxor_assign(&mut self, rhs: Self) { self.flags ^= rhs.flags; }
 }
 
 impl Not for ShapeOptions {
     type Output = Self;
-    fn not(self) -> Self::Output { Self(!self.0) }
+    fn not(self) -> Self::Output { Self{ flags: !self.flags, ..self } }
+}
+
+/// The antialiasing applied when rasterizing glyphs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenderQuality {
+    /// Hard edges, every pixel of the bitmap is either 0 or 255.
+    Aliased,
+    /// Grayscale antialiasing.
+    #[default]
+    Antialiased,
+    /// Subpixel antialiasing, averaged back to grayscale coverage. Backends
+    /// without subpixel rendering treat this as `Antialiased`.
+    ClearType,
+}
+
+/// A range of sizes from the 'gasp' table of a font, telling how glyphs should
+/// be rendered up to a size, see `FontFace::gasp_ranges`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GaspRange {
+    /// The largest size the range applies to in pixels per em, the range
+    /// starts after the previous one. The last range goes up to 0xFFFF.
+    pub max_ppem: u16,
+    /// The glyphs should be grid-fitted with the hinting instructions.
+    pub gridfit: bool,
+    /// The glyphs should be rendered with grayscale antialiasing.
+    pub grayscale: bool,
+    /// The glyphs should be grid-fitted symmetrically, with ClearType.
+    pub symmetric_gridfit: bool,
+    /// The glyphs should be smoothed in both directions, with ClearType.
+    pub symmetric_smoothing: bool,
+}
+
+/// The style of a face as flagged in its font file, see
+/// `FontFace::style_flags`. A face with none of these set is the regular one.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StyleFlags {
+    /// The face is bold.
+    pub bold: bool,
+    /// The face is italic.
+    pub italic: bool,
+    /// The face is oblique, a slanted version of the upright one. Only
+    /// flagged in the 'OS/2' table.
+    pub oblique: bool,
+    /// The glyphs are underscored.
+    pub underline: bool,
+    /// The glyphs are outlined, hollow.
+    pub outline: bool,
+    /// The glyphs are struck out. Only flagged in the 'OS/2' table.
+    pub strikeout: bool,
+    /// The glyphs have a shadow. Only flagged in the 'head' table.
+    pub shadow: bool,
+    /// The face is condensed. Only flagged in the 'head' table.
+    pub condensed: bool,
+    /// The face is extended. Only flagged in the 'head' table.
+    pub extended: bool,
+}
+
+/// A design axis of a variable font, see `FontFace::variation_axes`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariationAxis {
+    /// The tag of the axis, like "wght" for the weight or "wdth" for the
+    /// width.
+    pub tag: String,
+    /// The name of the axis to display, from the 'name' table.
+    pub name: Option<String>,
+    /// The smallest coordinate on the axis.
+    pub min: f32,
+    /// The coordinate of the default instance.
+    pub default: f32,
+    /// The largest coordinate on the axis.
+    pub max: f32,
+    /// The axis isn't meant to be shown to users.
+    pub hidden: bool,
+    /// The mapping of normalized coordinates from the 'avar' table, as pairs
+    /// of a coordinate and the one it's mapped to, see `normalize`. Empty if
+    /// the axis isn't remapped.
+    pub mapping: Vec<(f32, f32)>,
+}
+
+impl VariationAxis {
+    /// Normalizes a coordinate on the axis to the range -1.0 to 1.0, with the
+    /// default at 0.0, then maps it through the 'avar' table of the font.
+    /// This is the coordinate the font data gets interpolated with.
+    pub fn normalize(&self, value: f32) -> f32 {
+        let value = value.max(self.min).min(self.max);
+        let normalized = if value < self.default {
+            -(self.default - value) / (self.default - self.min)
+        }
+        else if value > self.default {
+            (value - self.default) / (self.max - self.default)
+        }
+        else {
+            0.0
+        };
+        // The mapping is piecewise linear between the pairs
+        let segment = self.mapping.windows(2)
+            .find(|w| normalized >= w[0].0 && normalized <= w[1].0);
+        match segment {
+            Some(w) if w[1].0 > w[0].0 => {
+                let t = (normalized - w[0].0) / (w[1].0 - w[0].0);
+                w[0].1 + t * (w[1].1 - w[0].1)
+            },
+            Some(w) => w[0].1,
+            None => normalized,
+        }
+    }
+}
+
+/// A named instance of a variable font, see `FontFace::named_instances`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedInstance {
+    /// The name of the instance, like "Semibold", from the 'name' table.
+    pub name: Option<String>,
+    /// The coordinate of the instance on each axis, in the order
+    /// `FontFace::variation_axes` returns them.
+    pub coordinates: Vec<f32>,
+}
+
+/// The colors glyphs get drawn with during rasterization, as RGB.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderColors {
+    /// The color of the text.
+    pub text: [u8; 3],
+    /// The color the text is drawn on.
+    pub background: [u8; 3],
+}
+
+impl Default for RenderColors {
+    fn default() -> Self {
+        RenderColors{
+            text: [0xff, 0xff, 0xff],
+            background: [0, 0, 0],
+        }
+    }
+}
+
+/// The direction text gets laid out in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WritingMode {
+    /// Left-to-right lines, going from top to bottom.
+    #[default]
+    Horizontal,
+    /// Top-to-bottom columns, going from right to left, as used in traditional
+    /// Chinese and Japanese writing.
+    VerticalRL,
 }
 
 /// Packs the glyphs with a best-effort algorithm to occupy the least amount of
-/// space possible.
+/// space possible. Glyphs without pixels, like the ones of whitespace, take up
+/// no space and are placed at the origin. Packing no glyphs at all results in
+/// an empty, 0x0 pack.
 pub fn pack_glyphs<'a>(glyphs: impl IntoIterator<Item = &'a RasterizedGlyph>) -> GlyphPack {
-    use std::cmp::max;
+    pack_glyphs_with(glyphs, &PackOptions::default())
+}
+
+/// Packs the glyphs like `pack_glyphs`, with the given options.
+pub fn pack_glyphs_with<'a>(glyphs: impl IntoIterator<Item = &'a RasterizedGlyph>, options: &PackOptions) -> GlyphPack {
+    use core::cmp::max;
     pack::bin_pack(glyphs.into_iter(),
-        |e| (e.width, e.height), |(w1, h1), (w2, h2)| max(w1, h1).cmp(max(w2, h2)), |e| e.character)
+        |e| (e.width, e.height), |(w1, h1), (w2, h2)| max(w1, h1).cmp(max(w2, h2)), |e| e.character, options)
+}
+
+/// Packs arbitrary rectangles, given as key and size (width, height) pairs,
+/// like `pack_glyphs` packs glyphs.
+pub fn pack_rects<K: Ord>(items: impl IntoIterator<Item = (K, (usize, usize))>) -> PackResult<K> {
+    pack_rects_with(items, &PackOptions::default())
+}
+
+/// Packs arbitrary rectangles like `pack_rects`, with the given options.
+pub fn pack_rects_with<K: Ord>(items: impl IntoIterator<Item = (K, (usize, usize))>,
+    options: &PackOptions) -> PackResult<K> {
+    use core::cmp::max;
+    pack::bin_pack(items.into_iter(),
+        |e| e.1, |(w1, h1), (w2, h2)| max(w1, h1).cmp(max(w2, h2)), |e| e.0, options)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "unicode-normalization")]
+    fn normalized_characters_map_to_their_source() {
+        // Composing shortens the text
+        assert_eq!(normalize_nfc("e\u{301}x"), ("\u{e9}x".into(), vec![0, 2]));
+        // Decomposing lengthens it
+        assert_eq!(normalize_nfc("\u{344}a"), ("\u{308}\u{301}a".into(), vec![0, 0, 1]));
+        // Hangul jamo compose from starters
+        assert_eq!(normalize_nfc("\u{1100}\u{1161}\u{11a8}z"), ("\u{ac01}z".into(), vec![0, 3]));
+        assert_eq!(normalize_nfc("abc"), ("abc".into(), vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn sizes_below_a_pixel_are_rejected() {
+        assert!(check_size(12.0, 96.0).is_ok());
+        // 72 DPI makes a point a pixel
+        assert!(check_size(1.0, 72.0).is_ok());
+        assert!(check_size(0.5, 72.0).is_err());
+        assert!(check_size(0.0, 96.0).is_err());
+        assert!(check_size(-12.0, 96.0).is_err());
+        assert!(check_size(-12.0, -96.0).is_err());
+        assert!(check_size(f64::NAN, 96.0).is_err());
+        assert!(check_size(12.0, f64::NAN).is_err());
+        assert!(check_size(f64::INFINITY, 96.0).is_err());
+        assert!(check_size(1e6, 96.0).is_err());
+    }
 }
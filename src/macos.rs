@@ -0,0 +1,398 @@
+
+// Implementation for macOS, based on CoreText.
+
+#![cfg(target_os = "macos")]
+
+use crate::{FontInfo, FontBackend, ScaledFaceBackend, RasterizedGlyph, BitDepth, GlyphBox, GlyphPositioning, ShapeOptions, RenderQuality, ScaleSettings, Result, Error};
+use crate::coretext::*;
+
+/// An owned CoreFoundation object that gets released when dropped.
+struct CfRef(CFTypeRef);
+
+// The objects we share (data, descriptors and fonts) are immutable, and
+// CoreFoundation allows using immutable objects from any thread.
+unsafe impl Send for CfRef {}
+unsafe impl Sync for CfRef {}
+
+impl CfRef {
+    fn is_err(&self) -> bool { self.0.is_null() }
+}
+
+impl Clone for CfRef {
+    fn clone(&self) -> Self {
+        if self.is_err() {
+            return CfRef(self.0);
+        }
+        // Sharing is just another reference to the same immutable object
+        CfRef(unsafe{ CFRetain(self.0) })
+    }
+}
+
+impl Drop for CfRef {
+    fn drop(&mut self) {
+        if !self.is_err() {
+            unsafe{ CFRelease(self.0) };
+        }
+    }
+}
+
+/// Creates a CoreFoundation string from UTF-16 units.
+fn cf_string(units: &[UniChar]) -> CfRef {
+    CfRef(unsafe{ CFStringCreateWithCharacters(std::ptr::null(), units.as_ptr(), units.len() as CFIndex) })
+}
+
+// Implementation of the font API
+
+// Font
+
+pub struct CoreTextFont {
+    meta       : FontInfo,
+    // The CFArray of font descriptors contained in the file
+    descriptors: CfRef   ,
+}
+
+impl FontBackend for CoreTextFont {
+    type Face = CoreTextFontFace;
+    type Scaled = CoreTextScaledFontFace;
+    type Context = ();
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        // Get metadata
+        let meta = FontInfo::from_bytes(bytes)?;
+        // CoreText can load the font from memory, no need to register it
+        let data = CfRef(unsafe{ CFDataCreate(std::ptr::null(), bytes.as_ptr(), bytes.len() as CFIndex) });
+        if data.is_err() {
+            return Err(Error::SystemError("CFDataCreate failed!".into()));
+        }
+        let descriptors = CfRef(unsafe{ CTFontManagerCreateFontDescriptorsFromData(data.0) });
+        if descriptors.is_err() || unsafe{ CFArrayGetCount(descriptors.0) } == 0 {
+            return Err(Error::SystemError("CTFontManagerCreateFontDescriptorsFromData failed!".into()));
+        }
+        // Done
+        Ok(Self{
+            meta,
+            descriptors,
+        })
+    }
+
+    fn info(&self) -> &FontInfo {
+        &self.meta
+    }
+
+    fn face(&self, name: &str) -> Result<Self::Face> {
+        if self.meta.file().resolve_face(name).is_none() {
+            // No such face
+            return Err(Error::UserError(format!("No face named '{}' found in font!", name)));
+        }
+        // We only parse the first face of the file, so that's the one we name
+        let descriptor = unsafe{ CFArrayGetValueAtIndex(self.descriptors.0, 0) };
+        // The array only borrows it to us
+        Ok(CoreTextFontFace{
+            descriptor: CfRef(unsafe{ CFRetain(descriptor) }),
+        })
+    }
+
+    fn create_context() -> Result<Self::Context> {
+        // Bitmap contexts are created for each glyph, there's nothing to share
+        Ok(())
+    }
+
+    fn scale(face: &Self::Face, settings: &ScaleSettings, _context: Option<&Self::Context>) -> Result<Self::Scaled> {
+        if settings.escapement != 0 {
+            return Err(Error::Unsupported("Rotated text on macOS"));
+        }
+        if settings.stretch != 1.0 {
+            return Err(Error::Unsupported("Stretched text on macOS"));
+        }
+        if !settings.variations.is_empty() {
+            return Err(Error::Unsupported("Font variations on macOS"));
+        }
+        // Only the coverage is drawn
+        face.scale(settings.pts, settings.dpi, settings.quality, settings.flip_y)
+    }
+}
+
+#[derive(Clone)]
+pub struct CoreTextFontFace {
+    descriptor: CfRef,
+}
+
+impl CoreTextFontFace {
+    pub fn scale(&self, pts: f64, dpi: f64, quality: RenderQuality, flip_y: bool) -> Result<CoreTextScaledFontFace> {
+        CoreTextScaledFontFace::create(&self.descriptor, pts, dpi, quality, flip_y)
+    }
+}
+
+// Scaled font face
+
+pub struct CoreTextScaledFontFace {
+    font       : CfRef        ,
+    color_space: CfRef        ,
+    // The size of the em square in pixels
+    em_size    : f64          ,
+    // Distance of the baseline from the top of a line
+    ascent     : f64          ,
+    line_height: f64          ,
+    quality    : RenderQuality,
+    // Whether the rows of the bitmaps are stored bottom-up
+    flip_y     : bool         ,
+}
+
+impl CoreTextScaledFontFace {
+    fn create(descriptor: &CfRef, pts: f64, dpi: f64, quality: RenderQuality, flip_y: bool) -> Result<Self> {
+        // Calculate size
+        const POINTS_PER_INCH: f64 = 72.0;
+        let em_size = pts * dpi / POINTS_PER_INCH;
+        // Create font
+        let font = CfRef(unsafe{ CTFontCreateWithFontDescriptor(descriptor.0, em_size, std::ptr::null()) });
+        if font.is_err() {
+            return Err(Error::SystemError("CTFontCreateWithFontDescriptor failed!".into()));
+        }
+        let color_space = CfRef(unsafe{ CGColorSpaceCreateDeviceGray() });
+        if color_space.is_err() {
+            return Err(Error::SystemError("CGColorSpaceCreateDeviceGray failed!".into()));
+        }
+        let ascent = unsafe{ CTFontGetAscent(font.0) }.ceil();
+        let descent = unsafe{ CTFontGetDescent(font.0) }.ceil();
+        let leading = unsafe{ CTFontGetLeading(font.0) }.round();
+        // We succeeded in creating everything
+        Ok(Self{
+            font,
+            color_space,
+            em_size,
+            ascent,
+            line_height: ascent + descent + leading,
+            quality,
+            flip_y,
+        })
+    }
+
+    /// Rasterizes the given glyph into a cell like the one GDI renders into, so
+    /// offsets are relative to the top-left of the line.
+    fn rasterize(&mut self, codepoint: char, glyph: CGGlyph) -> Result<RasterizedGlyph> {
+        // Get coordinates
+        let mut advance = CGSize::default();
+        let mut rect = CGRect::default();
+        unsafe{
+            CTFontGetAdvancesForGlyphs(self.font.0, kCTFontOrientationDefault, &glyph, &mut advance, 1);
+            CTFontGetBoundingRectsForGlyphs(self.font.0, kCTFontOrientationDefault, &glyph, &mut rect, 1);
+        }
+        // Glyphs can overhang to the left of the origin
+        let pad = (-rect.origin.x).max(0.0).ceil();
+        let ink_right = rect.origin.x + rect.size.width;
+        // One extra column for antialiasing bleeding over the ink bounds
+        let buff_w = (advance.width.max(ink_right) + pad).ceil().max(0.0) as usize + 1;
+        let buff_h = self.line_height.max(0.0) as usize;
+        if buff_w == 0 || buff_h == 0 {
+            return Ok(Self::empty_glyph(codepoint));
+        }
+        // Create the bitmap, the buffer is zeroed so it's cleared already
+        let mut buffer = vec![0u8; buff_w * buff_h];
+        let context = CfRef(unsafe{ CGBitmapContextCreate(buffer.as_mut_ptr() as _, buff_w, buff_h,
+            8, buff_w, self.color_space.0, kCGImageAlphaNone) });
+        if context.is_err() {
+            return Err(Error::SystemError("CGBitmapContextCreate failed!".into()));
+        }
+        // Render to bitmap, the origin is at the bottom-left
+        let position = CGPoint{ x: pad, y: buff_h as f64 - self.ascent };
+        unsafe{
+            // There's no subpixel rendering into grayscale bitmaps
+            CGContextSetShouldAntialias(context.0, self.quality != RenderQuality::Aliased);
+            CGContextSetGrayFillColor(context.0, 1.0, 1.0);
+            CTFontDrawGlyphs(self.font.0, &glyph, &position, 1, context.0);
+        }
+        drop(context);
+        // The memory of the bitmap is top-down already
+        let pixel = |x: usize, y: usize| buffer[y * buff_w + x];
+        // Calculate the tightest bounds
+        let mut left = buff_w;
+        let mut right = 0;
+        let mut top = buff_h;
+        let mut bottom = 0;
+        for y in 0..buff_h {
+            for x in 0..buff_w {
+                if pixel(x, y) != 0 {
+                    left = std::cmp::min(left, x);
+                    right = std::cmp::max(right, x + 1);
+                    top = std::cmp::min(top, y);
+                    bottom = std::cmp::max(bottom, y + 1);
+                }
+            }
+        }
+        if left >= right {
+            // The bitmap must be empty, return empty bitmap
+            return Ok(Self::empty_glyph(codepoint));
+        }
+        let bounds_width = right - left;
+        let bounds_height = bottom - top;
+        // Copy the data to the buffer
+        let mut data = vec![0u8; bounds_width * bounds_height].into_boxed_slice();
+        for y in 0..bounds_height {
+            let y_res_offs = y * bounds_width;
+            let row = if self.flip_y { bottom - 1 - y } else { top + y };
+            for x in 0..bounds_width {
+                data[y_res_offs + x] = pixel(left + x, row);
+            }
+        }
+        // We succeeded
+        Ok(RasterizedGlyph{
+            character: codepoint,
+            x_offset: left as i32 - pad as i32,
+            y_offset: top as i32,
+            width: bounds_width,
+            height: bounds_height,
+            bit_depth: BitDepth::Eight,
+            cell: GlyphBox::default(),
+            baseline: 0,
+            data,
+        })
+    }
+
+    fn empty_glyph(codepoint: char) -> RasterizedGlyph {
+        RasterizedGlyph{
+            character: codepoint,
+            x_offset: 0,
+            y_offset: 0,
+            width: 0,
+            height: 0,
+            bit_depth: BitDepth::Eight,
+            cell: GlyphBox::default(),
+            baseline: 0,
+            data: vec![0u8; 0].into_boxed_slice(),
+        }
+    }
+
+    /// Creates a typeset line of the given UTF-16 text.
+    fn create_line(&self, text: &[UniChar], options: ShapeOptions) -> CfRef {
+        // Zero kerning turns it off, ligature level 0 keeps only the required ones
+        let kern: f64 = 0.0;
+        let ligatures: i32 = if options.contains(ShapeOptions::USE_LIGATURES) { 1 } else { 0 };
+        let kern = CfRef(unsafe{ CFNumberCreate(std::ptr::null(), kCFNumberFloat64Type, &kern as *const f64 as _) });
+        let ligatures = CfRef(unsafe{ CFNumberCreate(std::ptr::null(), kCFNumberSInt32Type, &ligatures as *const i32 as _) });
+        let mut keys = vec![unsafe{ kCTFontAttributeName }, unsafe{ kCTLigatureAttributeName }];
+        let mut values = vec![self.font.0, ligatures.0];
+        if !options.contains(ShapeOptions::USE_KERNING) {
+            keys.push(unsafe{ kCTKernAttributeName });
+            values.push(kern.0);
+        }
+        let attributes = CfRef(unsafe{ CFDictionaryCreate(std::ptr::null(), keys.as_ptr(), values.as_ptr(),
+            keys.len() as CFIndex, &kCFTypeDictionaryKeyCallBacks, &kCFTypeDictionaryValueCallBacks) });
+        let string = cf_string(text);
+        let attributed = CfRef(unsafe{ CFAttributedStringCreate(std::ptr::null(), string.0, attributes.0) });
+        CfRef(unsafe{ CTLineCreateWithAttributedString(attributed.0) })
+    }
+}
+
+impl ScaledFaceBackend for CoreTextScaledFontFace {
+    fn em_size(&self) -> f64 {
+        self.em_size
+    }
+
+    fn line_height(&self) -> i32 {
+        self.line_height as i32
+    }
+
+    fn ascent(&self) -> i32 {
+        self.ascent as i32
+    }
+
+    fn rasterize_glyph(&mut self, codepoint: char) -> Result<RasterizedGlyph> {
+        // Convert to UTF16
+        let mut units = [0u16; 2];
+        let units = codepoint.encode_utf16(&mut units);
+        // Look up the glyph
+        let mut glyphs = [0 as CGGlyph; 2];
+        if !unsafe{ CTFontGetGlyphsForCharacters(self.font.0, units.as_ptr(), glyphs.as_mut_ptr(), units.len() as CFIndex) } {
+            return Err(Error::GlyphNotFound(codepoint));
+        }
+        self.rasterize(codepoint, glyphs[0])
+    }
+
+    fn rasterize_glyph_index(&mut self, glyph_id: u16) -> Result<RasterizedGlyph> {
+        self.rasterize('\0', glyph_id)
+    }
+
+    fn shape_text<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions, mut f: F) -> Result<(i32, i32)> {
+        let line_height = self.line_height as i32;
+
+        // Biggest dimensions
+        let mut max_w = 0;
+        let mut max_h = 0;
+
+        let lines: Vec<&str> = text.split('\n').collect();
+        let mut index = 0;
+        let mut yoff = 0;
+        for (i, line) in lines.iter().enumerate() {
+            let line16: Vec<UniChar> = line.encode_utf16().collect();
+            let ct_line = self.create_line(&line16, options);
+            // Collect the glyph and position starting at each UTF-16 unit
+            let mut placements = vec![None; line16.len()];
+            let runs = unsafe{ CTLineGetGlyphRuns(ct_line.0) };
+            for r in 0..unsafe{ CFArrayGetCount(runs) } {
+                let run = unsafe{ CFArrayGetValueAtIndex(runs, r) };
+                let count = unsafe{ CTRunGetGlyphCount(run) } as usize;
+                let mut glyphs = vec![0 as CGGlyph; count];
+                let mut positions = vec![CGPoint::default(); count];
+                let mut indices = vec![0 as CFIndex; count];
+                let all = CFRange::default();
+                unsafe{
+                    CTRunGetGlyphs(run, all, glyphs.as_mut_ptr());
+                    CTRunGetPositions(run, all, positions.as_mut_ptr());
+                    CTRunGetStringIndices(run, all, indices.as_mut_ptr());
+                }
+                for g in 0..count {
+                    let slot = &mut placements[indices[g] as usize];
+                    if slot.is_none() {
+                        *slot = Some((glyphs[g], positions[g]));
+                    }
+                }
+            }
+            // Report the characters
+            let mut unit = 0;
+            for ch in line.chars() {
+                let placement = placements[unit];
+                let caret_x = unsafe{ CTLineGetOffsetForStringIndex(ct_line.0, unit as CFIndex, std::ptr::null_mut()) };
+                unit += ch.len_utf16();
+                index += 1;
+                let (glyph_id, position) = match placement {
+                    Some(p) => p,
+                    // Merged into the glyph of the preceding character
+                    None => continue,
+                };
+                f(GlyphPositioning{
+                    character: ch,
+                    index: index - 1,
+                    cluster: index - 1,
+                    glyph_id,
+                    x: position.x.floor() as i32,
+                    // The positions point up
+                    y: yoff - position.y.round() as i32,
+                    x_fraction: (position.x - position.x.floor()) as f32,
+                    caret_x: caret_x.round() as i32,
+                    caret_y: yoff,
+                });
+            }
+            let width = unsafe{ CTLineGetTypographicBounds(ct_line.0,
+                std::ptr::null_mut(), std::ptr::null_mut(), std::ptr::null_mut()) }.round() as i32;
+            max_w = std::cmp::max(max_w, width);
+            max_h = std::cmp::max(max_h, yoff + line_height);
+            if i + 1 < lines.len() {
+                // Report the newline that got split off
+                f(GlyphPositioning{
+                    character: '\n',
+                    index,
+                    cluster: index,
+                    glyph_id: 0,
+                    x: width,
+                    y: yoff,
+                    x_fraction: 0.0,
+                    caret_x: width,
+                    caret_y: yoff,
+                });
+                index += 1;
+                yoff += line_height;
+            }
+        }
+        Ok((max_w, max_h))
+    }
+}
@@ -0,0 +1,93 @@
+
+// Vector outlines of glyphs, built from the points of the TrueType contours.
+// TrueType contours are quadratic B-splines: two consecutive off-curve points
+// have an implied on-curve point halfway between them.
+
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use crate::ttf::OutlinePoint;
+
+/// A segment of a contour, starting where the previous segment ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+    /// A straight line to `to`.
+    Line{ to: (f32, f32) },
+    /// A quadratic Bézier curve to `to` with the control point `ctrl`.
+    Quadratic{ ctrl: (f32, f32), to: (f32, f32) },
+    /// A cubic Bézier curve to `to` with the control points `ctrl1` and
+    /// `ctrl2`.
+    Cubic{ ctrl1: (f32, f32), ctrl2: (f32, f32), to: (f32, f32) },
+}
+
+/// A closed contour of a glyph outline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contour {
+    /// The point the contour starts at.
+    pub start: (f32, f32),
+    /// The segments of the contour, the last one ends at `start`.
+    pub segments: Vec<PathSegment>,
+}
+
+/// The vector outline of a glyph. Coordinates are in pixels, relative to the
+/// origin of the glyph on the baseline, with the y axis pointing down. The
+/// inside of the glyph is given by the non-zero winding rule.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Outline {
+    /// The contours of the glyph.
+    pub contours: Vec<Contour>,
+}
+
+#[cfg(feature = "std")]
+impl Outline {
+    /// Creates the outline from TrueType contours in font design units,
+    /// multiplying them by `scale`.
+    pub(crate) fn from_points(contours: &[Vec<OutlinePoint>], scale: f32) -> Self {
+        Self{
+            contours: contours.iter()
+                .filter_map(|points| contour_from_points(points, scale))
+                .collect(),
+        }
+    }
+}
+
+/// Converts the points of a TrueType contour to segments, or None if the
+/// contour has no points.
+#[cfg(feature = "std")]
+fn contour_from_points(points: &[OutlinePoint], scale: f32) -> Option<Contour> {
+    let n = points.len();
+    // Flip the y axis to point down
+    let to_pixels = |p: &OutlinePoint| (p.x * scale, -p.y * scale);
+    let midpoint = |a: (f32, f32), b: (f32, f32)| ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0);
+    // Start from an on-curve point, or an implied one if there's none
+    let (start, first, count) = match points.iter().position(|p| p.on_curve) {
+        Some(i) => (to_pixels(&points[i]), i + 1, n - 1),
+        None => (midpoint(to_pixels(points.last()?), to_pixels(points.first()?)), 0, n),
+    };
+    let mut segments = Vec::with_capacity(n);
+    let mut ctrl = None;
+    for k in 0..count {
+        let p = &points[(first + k) % n];
+        let pos = to_pixels(p);
+        if p.on_curve {
+            segments.push(match ctrl.take() {
+                Some(ctrl) => PathSegment::Quadratic{ ctrl, to: pos },
+                None => PathSegment::Line{ to: pos },
+            });
+        }
+        else {
+            if let Some(ctrl) = ctrl {
+                segments.push(PathSegment::Quadratic{ ctrl, to: midpoint(ctrl, pos) });
+            }
+            ctrl = Some(pos);
+        }
+    }
+    // Close the contour
+    segments.push(match ctrl {
+        Some(ctrl) => PathSegment::Quadratic{ ctrl, to: start },
+        None => PathSegment::Line{ to: start },
+    });
+    Some(Contour{
+        start,
+        segments,
+    })
+}
@@ -1,127 +1,198 @@
 
 // Utility for packing characters into the smallest size atlas possible.
 // Note that the problem is the 2 dimensional version of the Bin Packing
-// problem, which is essentially NP-hard. The algorithm used is a best-effort
+// problem, which is essentially NP-hard. The default algorithm is a best-effort
 // algorithm based on: https://codeincomplete.com/posts/bin-packing/.
-// The algorithm is represented in a generic manner, it could be used for any
-// kind of 2D packing, but the exposed API is for rendered glyphs explicitly.
+// Alternatively the MaxRects algorithm can be used, as described in: Jukka
+// Jylänki, A Thousand Ways to Pack the Bin.
+// The algorithm is represented in a generic manner, so besides glyphs it's
+// exposed for packing any kind of rectangles.
 
-use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::hash::Hash;
-use std::rc::Rc;
-use std::cell::RefCell;
+use core::cmp::Ordering;
+use alloc::collections::BTreeMap;
+use alloc::collections::btree_map;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+
+/// The algorithm used to pack rectangles.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PackStrategy {
+    /// Splits the free space into a binary tree, growing it to the right or
+    /// downwards when the next rectangle doesn't fit. Fast, and works well
+    /// for rectangles of similar sizes.
+    #[default]
+    Tree,
+    /// Keeps track of the maximal free rectangles and places each rectangle
+    /// as far to the bottom left as possible. Slower, but usually packs
+    /// rectangles of very different sizes tighter.
+    MaxRects,
+}
+
+/// Options for packing, see `pack_glyphs_with`.
+#[derive(Debug, Clone, Default)]
+pub struct PackOptions {
+    /// The algorithm to pack with.
+    pub strategy: PackStrategy,
+}
 
 /// The packer algorithm itself.
 pub(crate) fn bin_pack<
     /// The type being passed as input.
     T,
     /// The key type.
-    K: Eq + Hash,
+    K: Ord,
     /// The size selector function.
     FS: FnMut(&T) -> (usize, usize),
     /// The ordering function.
     FO: FnMut(&(usize, usize), &(usize, usize)) -> Ordering,
-    /// The key selector function.
-    FK: FnMut(&T) -> K,
+    /// The key selector function, taking the entry.
+    FK: FnMut(T) -> K,
 >(to_pack: impl Iterator<Item = T>,
-    mut size_f: FS, mut ordering_f: FO, mut key_f: FK) -> PackResult<K> {
-    let mut to_pack: Vec<_> = to_pack.collect();
+    mut size_f: FS, mut ordering_f: FO, mut key_f: FK, options: &PackOptions) -> PackResult<K> {
+    // Entries without an area, like the glyphs of whitespace, take up no space,
+    // they're all placed at the origin
+    let (empty, mut to_pack): (Vec<_>, Vec<_>) = to_pack.partition(|e| {
+        let (w, h) = size_f(e);
+        w == 0 || h == 0
+    });
     to_pack.sort_by(|a, b| ordering_f(&size_f(a), &size_f(b)).reverse());
 
-    let (w, h) = to_pack.first().map(|i| size_f(i)).unwrap_or((0, 0));
-    let mut packer = Packer::new(w, h);
+    let used_area = to_pack.iter().map(&mut size_f).map(|(w, h)| w * h).sum();
+
+    let mut items = BTreeMap::new();
+    for e in empty {
+        let (w, h) = size_f(&e);
+        items.insert(key_f(e), Rect{ x: 0, y: 0, width: w, height: h });
+    }
 
-    let mut items = HashMap::new();
+    if options.strategy == PackStrategy::MaxRects {
+        let sizes: Vec<_> = to_pack.iter().map(&mut size_f).collect();
+        let (width, height, rects) = max_rects(&sizes);
+        items.extend(to_pack.into_iter().map(key_f).zip(rects));
+        return PackResult{
+            width, height, items, used_area,
+        };
+    }
+
+    let (w, h) = to_pack.first().map(&mut size_f).unwrap_or((0, 0));
+    let mut packer = Packer::new(w, h);
 
     for e in to_pack {
         let (w, h) = size_f(&e);
-        let k = key_f(&e);
+        let k = key_f(e);
         let rect = packer.fit(w, h);
         items.insert(k, rect);
     }
 
-    let width = packer.root.borrow().width;
-    let height = packer.root.borrow().height;
+    let width = packer.nodes[packer.root].width;
+    let height = packer.nodes[packer.root].height;
     PackResult{
-        width, height, items,
+        width, height, items, used_area,
     }
 }
 
 /// Returned by the packing operation to summate the results.
-pub struct PackResult<K> {
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PackResult<K: Ord> {
     /// The required width to fit in every entry.
     width: usize,
     /// The required height to fit in every entry.
     height: usize,
     /// The map from the entry key to it's fit rectangle.
-    items: HashMap<K, Rect>,
+    items: BTreeMap<K, Rect>,
+    /// The sum of the areas of the entries.
+    used_area: usize,
 }
 
-impl <K> PackResult<K> {
+impl <K: Ord> PackResult<K> {
     /// Returns the required width to fit in every entry.
     pub fn width(&self) -> usize { self.width }
     /// Returns the required height to fit in every entry.
     pub fn height(&self) -> usize { self.height }
+
+    /// Returns the ratio of the area covered by the entries to the whole area,
+    /// between 0 and 1. An empty packing is considered fully occupied.
+    pub fn occupancy(&self) -> f64 {
+        let area = self.width * self.height;
+        if area == 0 { 1.0 } else { self.used_area as f64 / area as f64 }
+    }
+
+    /// Returns the area not covered by any entry.
+    pub fn wasted_area(&self) -> usize {
+        self.width * self.height - self.used_area
+    }
 }
 
-impl <'a, K> IntoIterator for &'a PackResult<K> {
+impl <'a, K: Ord> IntoIterator for &'a PackResult<K> {
     type Item = (&'a K, &'a Rect);
-    type IntoIter = std::collections::hash_map::Iter<'a, K, Rect>;
+    type IntoIter = btree_map::Iter<'a, K, Rect>;
 
     fn into_iter(self) -> Self::IntoIter { self.items.iter() }
 }
 
-impl <K> IntoIterator for PackResult<K> {
+impl <K: Ord> IntoIterator for PackResult<K> {
     type Item = (K, Rect);
-    type IntoIter = std::collections::hash_map::IntoIter<K, Rect>;
+    type IntoIter = btree_map::IntoIter<K, Rect>;
 
     fn into_iter(self) -> Self::IntoIter { self.items.into_iter() }
 }
 
-/// The backing data-structure to the packing algorithm.
+/// The backing data-structure to the packing algorithm. The nodes of the tree
+/// live in a single vector and refer to each other by index.
 struct Packer {
-    /// The root node of the packer.
-    root: Rc<RefCell<Node>>,
+    /// All of the nodes of the tree.
+    nodes: Vec<Node>,
+    /// The index of the root node of the packer.
+    root: usize,
 }
 
 impl Packer {
     /// Creates an empty packer.
     fn new(w: usize, h: usize) -> Self {
         Self{
-            root: Rc::new(RefCell::new(Node::new(0, 0, w, h))),
+            nodes: vec![Node::new(0, 0, w, h)],
+            root: 0,
         }
     }
 
-    /// Tries to fit in a block.
+    /// Adds a node to the tree and returns its index.
+    fn add_node(&mut self, node: Node) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Tries to fit in a block. The node it gets placed in can be larger than
+    /// the block, the rest of it is left free.
     fn fit(&mut self, w: usize, h: usize) -> Rect {
-        let node = if let Some(node) = self.find_node(&self.root, w, h) {
-                self.split_node(&node, w, h)
+        let node = if let Some(node) = self.find_node(self.root, w, h) {
+                self.split_node(node, w, h)
             }
             else {
                 self.grow_node(w, h)
             };
-        let node = node.borrow();
+        let node = &self.nodes[node];
         Rect{
             x: node.x,
             y: node.y,
-            width: node.width,
-            height: node.height,
+            width: w,
+            height: h,
         }
     }
 
     /// Finds the first fitting node, or none in the tree.
-    fn find_node(&self, root: &Rc<RefCell<Node>>, w: usize, h: usize) -> Option<Rc<RefCell<Node>>> {
-        let node = root.borrow();
+    fn find_node(&self, root: usize, w: usize, h: usize) -> Option<usize> {
+        let node = &self.nodes[root];
         if node.occupied {
-            let r = self.find_node(&node.right.as_ref().unwrap(), w, h);
+            let r = self.find_node(node.right.unwrap(), w, h);
             if r.is_some() {
                 return r;
             }
-            self.find_node(&node.down.as_ref().unwrap(), w, h)
+            self.find_node(node.down.unwrap(), w, h)
         }
         else if w <= node.width && h <= node.height {
-            Some(root.clone())
+            Some(root)
         }
         else {
             None
@@ -129,18 +200,21 @@ impl Packer {
     }
 
     /// Splits and occupies the node.
-    fn split_node(&mut self, node: &Rc<RefCell<Node>>, w: usize, h: usize) -> Rc<RefCell<Node>> {
-        let mut bnode = node.borrow_mut();
+    fn split_node(&mut self, node: usize, w: usize, h: usize) -> usize {
+        let Node{ x, y, width, height, .. } = self.nodes[node];
+        let down = self.add_node(Node::new(x, y + h, width, height - h));
+        let right = self.add_node(Node::new(x + w, y, width - w, h));
+        let bnode = &mut self.nodes[node];
         bnode.occupied = true;
-        bnode.down = Some(Rc::new(RefCell::new(Node::new(bnode.x, bnode.y + h, bnode.width, bnode.height - h))));
-        bnode.right = Some(Rc::new(RefCell::new(Node::new(bnode.x + w, bnode.y, bnode.width - w, h))));
-        node.clone()
+        bnode.down = Some(down);
+        bnode.right = Some(right);
+        node
     }
 
     /// Grows the node in size and tries to remain close to a square.
-    fn grow_node(&mut self, w: usize, h: usize) -> Rc<RefCell<Node>> {
-        let root_w = self.root.borrow().width;
-        let root_h = self.root.borrow().height;
+    fn grow_node(&mut self, w: usize, h: usize) -> usize {
+        let root_w = self.nodes[self.root].width;
+        let root_h = self.nodes[self.root].height;
 
         let can_down = w <= root_w;
         let can_right = h <= root_h;
@@ -161,42 +235,131 @@ impl Packer {
             self.grow_down(w, h)
         }
         else {
-            panic!("Invalid sorting!");
+            // The block is both wider and taller than the root, so the root
+            // is widened to fit it below
+            self.extend_right(w - root_w);
+            self.grow_down(w, h)
         }
     }
 
-    /// Grows a node to the right.
-    fn grow_right(&mut self, w: usize, h: usize) -> Rc<RefCell<Node>> {
-        let root_w = self.root.borrow().width;
-        let root_h = self.root.borrow().height;
+    /// Adds free space of the given width to the right of the root.
+    fn extend_right(&mut self, w: usize) {
+        let root_w = self.nodes[self.root].width;
+        let root_h = self.nodes[self.root].height;
 
+        let right = self.add_node(Node::new(root_w, 0, w, root_h));
         let mut root = Node::new(0, 0, root_w + w, root_h);
         root.occupied = true;
-        root.down = Some(self.root.clone());
-        root.right = Some(Rc::new(RefCell::new(Node::new(root_w, 0, w, root_h))));
-        self.root = Rc::new(RefCell::new(root));
+        root.down = Some(self.root);
+        root.right = Some(right);
+        self.root = self.add_node(root);
+    }
 
-        let node = self.find_node(&self.root, w, h).expect("Invalid sorting!");
-        self.split_node(&node, w, h)
+    /// Grows a node to the right.
+    fn grow_right(&mut self, w: usize, h: usize) -> usize {
+        self.extend_right(w);
+
+        let node = self.find_node(self.root, w, h).expect("Invalid sorting!");
+        self.split_node(node, w, h)
     }
 
     /// Grows a node to down.
-    fn grow_down(&mut self, w: usize, h: usize) -> Rc<RefCell<Node>> {
-        let root_w = self.root.borrow().width;
-        let root_h = self.root.borrow().height;
+    fn grow_down(&mut self, w: usize, h: usize) -> usize {
+        let root_w = self.nodes[self.root].width;
+        let root_h = self.nodes[self.root].height;
 
+        let down = self.add_node(Node::new(0, root_h, root_w, h));
         let mut root = Node::new(0, 0, root_w, root_h + h);
         root.occupied = true;
-        root.right = Some(self.root.clone());
-        root.down = Some(Rc::new(RefCell::new(Node::new(0, root_h, root_w, h))));
-        self.root = Rc::new(RefCell::new(root));
+        root.right = Some(self.root);
+        root.down = Some(down);
+        self.root = self.add_node(root);
 
-        let node = self.find_node(&self.root, w, h).expect("Invalid sorting!");
-        self.split_node(&node, w, h)
+        let node = self.find_node(self.root, w, h).expect("Invalid sorting!");
+        self.split_node(node, w, h)
+    }
+}
+
+/// Packs the rectangles of the given sizes with the MaxRects algorithm.
+/// Returns the size of the packing and the position of each rectangle.
+fn max_rects(sizes: &[(usize, usize)]) -> (usize, usize, Vec<Rect>) {
+    use core::cmp::max;
+    // The bin is as tall as it needs to be, so only the width is picked.
+    // A few widths around the square one are tried, keeping the least area.
+    let area: usize = sizes.iter().map(|(w, h)| w * h).sum();
+    let min_width = sizes.iter().map(|(w, _)| *w).max().unwrap_or(0);
+    let mut side = 0;
+    while side * side < area {
+        side += 1;
+    }
+    let mut best: Option<(usize, usize, Vec<Rect>)> = None;
+    // Widths of 1, 1.25, 1.5 and 2 times the side
+    for quarters in &[4, 5, 6, 8] {
+        let width = max(min_width, (side * quarters).div_ceil(4));
+        let packed = max_rects_with_width(sizes, width);
+        let better = best.as_ref().is_none_or(|(w, h, _)| packed.0 * packed.1 < w * h);
+        if better {
+            best = Some(packed);
+        }
+    }
+    best.unwrap_or((0, 0, Vec::new()))
+}
+
+/// Packs the rectangles into a bin of the given width with the MaxRects
+/// algorithm, using the bottom-left rule.
+fn max_rects_with_width(sizes: &[(usize, usize)], bin_width: usize) -> (usize, usize, Vec<Rect>) {
+    use core::cmp::max;
+    let bin_height: usize = sizes.iter().map(|(_, h)| *h).sum();
+    let mut free = vec![Rect{ x: 0, y: 0, width: bin_width, height: bin_height }];
+    let mut rects = Vec::with_capacity(sizes.len());
+    let (mut width, mut height) = (0, 0);
+    for &(w, h) in sizes {
+        // The free rectangle the placement ends up the lowest, then leftmost in
+        let (x, y) = free.iter()
+            .filter(|f| w <= f.width && h <= f.height)
+            .min_by_key(|f| (f.y + h, f.x))
+            .map(|f| (f.x, f.y))
+            .expect("The bin must fit every rectangle!");
+        let placed = Rect{ x, y, width: w, height: h };
+        // Cut the placed rectangle out of the free ones it overlaps
+        let mut split = Vec::with_capacity(free.len());
+        for f in free {
+            if !placed.overlaps(&f) {
+                split.push(f);
+                continue;
+            }
+            if placed.x > f.x {
+                split.push(Rect{ x: f.x, y: f.y, width: placed.x - f.x, height: f.height });
+            }
+            if placed.x + w < f.x + f.width {
+                split.push(Rect{ x: placed.x + w, y: f.y, width: f.x + f.width - placed.x - w, height: f.height });
+            }
+            if placed.y > f.y {
+                split.push(Rect{ x: f.x, y: f.y, width: f.width, height: placed.y - f.y });
+            }
+            if placed.y + h < f.y + f.height {
+                split.push(Rect{ x: f.x, y: placed.y + h, width: f.width, height: f.y + f.height - placed.y - h });
+            }
+        }
+        // Drop the free rectangles contained in others
+        free = Vec::with_capacity(split.len());
+        for (i, f) in split.iter().enumerate() {
+            let redundant = split.iter().enumerate()
+                .any(|(j, g)| i != j && g.contains(f) && (!f.contains(g) || j < i));
+            if !redundant {
+                free.push(f.clone());
+            }
+        }
+        width = max(width, x + w);
+        height = max(height, y + h);
+        rects.push(placed);
     }
+    (width, height, rects)
 }
 
 /// Represents a section in the packing that has been positioned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Rect {
     /// The x position of the upper-left corner of the rectangle.
     pub x: usize,
@@ -208,6 +371,20 @@ pub struct Rect {
     pub height: usize,
 }
 
+impl Rect {
+    /// Checks if the two rectangles have a common area.
+    fn overlaps(&self, other: &Rect) -> bool {
+        self.x < other.x + other.width && other.x < self.x + self.width
+            && self.y < other.y + other.height && other.y < self.y + self.height
+    }
+
+    /// Checks if the other rectangle is completely inside this one.
+    fn contains(&self, other: &Rect) -> bool {
+        self.x <= other.x && other.x + other.width <= self.x + self.width
+            && self.y <= other.y && other.y + other.height <= self.y + self.height
+    }
+}
+
 /// A helper structure to represent a node in the packer.
 struct Node {
     /// Is this node occupied by other entries, or free to fill.
@@ -220,10 +397,10 @@ struct Node {
     width: usize,
     /// The height of this node.
     height: usize,
-    /// The node below this.
-    down: Option<Rc<RefCell<Node>>>,
-    /// The node right to this.
-    right: Option<Rc<RefCell<Node>>>,
+    /// The index of the node below this.
+    down: Option<usize>,
+    /// The index of the node right to this.
+    right: Option<usize>,
 }
 
 impl Node {
@@ -237,3 +414,72 @@ impl Node {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    /// Packs the sizes keyed by their index.
+    fn pack(sizes: &[(usize, usize)], strategy: PackStrategy) -> PackResult<usize> {
+        use core::cmp::max;
+        bin_pack(sizes.iter().copied().enumerate(),
+            |e| e.1, |(w1, h1), (w2, h2)| max(w1, h1).cmp(max(w2, h2)), |e| e.0,
+            &PackOptions{ strategy })
+    }
+
+    #[test]
+    fn both_strategies_report_the_requested_sizes() {
+        let sizes = vec![(8, 8), (3, 5), (3, 5), (10, 2), (1, 1), (6, 4), (3, 5), (2, 9)];
+        for strategy in &[PackStrategy::Tree, PackStrategy::MaxRects] {
+            let packed = pack(&sizes, *strategy);
+            for (i, rect) in &packed {
+                assert_eq!((rect.width, rect.height), sizes[*i], "{:?}", strategy);
+                assert!(rect.x + rect.width <= packed.width() && rect.y + rect.height <= packed.height());
+                for (j, other) in &packed {
+                    assert!(i == j || !rect.overlaps(other), "{:?}", strategy);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tree_strategy_places_a_fixed_set_exactly() {
+        // The placements of the original, reference counted tree packer
+        let sizes = vec![(8, 8), (3, 5), (3, 5), (10, 2), (1, 1), (6, 4), (3, 5), (2, 9)];
+        let packed = pack(&sizes, PackStrategy::Tree);
+        assert_eq!((packed.width(), packed.height()), (19, 11));
+        let expected = vec![(2, 2), (10, 4), (13, 4), (0, 0), (16, 5), (10, 0), (16, 0), (0, 2)];
+        let placed: Vec<_> = packed.into_iter().map(|(_, r)| (r.x, r.y)).collect();
+        assert_eq!(placed, expected);
+    }
+
+    #[test]
+    fn empty_input_packs_into_nothing() {
+        for strategy in &[PackStrategy::Tree, PackStrategy::MaxRects] {
+            let packed = pack(&[], *strategy);
+            assert_eq!((packed.width(), packed.height()), (0, 0), "{:?}", strategy);
+            assert!(packed.into_iter().next().is_none(), "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn entries_without_area_are_placed_at_the_origin() {
+        let sizes = vec![(0, 0), (5, 0), (0, 7)];
+        for strategy in &[PackStrategy::Tree, PackStrategy::MaxRects] {
+            let packed = pack(&sizes, *strategy);
+            assert_eq!((packed.width(), packed.height()), (0, 0), "{:?}", strategy);
+            for (i, rect) in &packed {
+                assert_eq!(*rect, Rect{ x: 0, y: 0, width: sizes[*i].0, height: sizes[*i].1 });
+            }
+            assert_eq!(packed.into_iter().count(), sizes.len());
+        }
+    }
+
+    #[test]
+    fn pack_rects_reports_the_requested_sizes() {
+        let packed = crate::pack_rects(vec![("a", (8, 8)), ("b", (3, 5)), ("c", (10, 2)), ("d", (1, 1))]);
+        let sizes: Vec<_> = packed.into_iter().map(|(k, r)| (k, r.width, r.height)).collect();
+        assert_eq!(sizes, vec![("a", 8, 8), ("b", 3, 5), ("c", 10, 2), ("d", 1, 1)]);
+    }
+}
@@ -1,6 +1,8 @@
 
 // Binary parser utilities.
 
+use alloc::vec::Vec;
+
 /// The result of parsing.
 pub(crate) type ParseResult<T> = Result<T, ()>;
 
@@ -12,6 +14,21 @@ pub(crate) trait Parse: Sized {
     fn parse_be(_input: &mut &[u8]) -> ParseResult<Self> { unimplemented!(); }
 }
 
+/// Parses a given number of elements from a big-endian input.
+pub(crate) fn parse_be_array<T: Parse>(input: &mut &[u8], count: usize) -> ParseResult<Vec<T>> {
+    let mut bytes = *input;
+    let mut result = Vec::with_capacity(count);
+    for _ in 0..count { result.push(T::parse_be(&mut bytes)?); }
+    *input = bytes;
+    Ok(result)
+}
+
+/// Returns the input starting at the given offset, or an error if the offset
+/// is out of bounds.
+pub(crate) fn offset_slice(input: &[u8], offset: usize) -> ParseResult<&[u8]> {
+    input.get(offset..).ok_or(())
+}
+
 // Macro to implement for integral types.
 
 macro_rules! parseable_integral {
@@ -20,7 +37,7 @@ macro_rules! parseable_integral {
             fn parse_le(input: &mut &[u8]) -> ParseResult<Self> {
                 let bytes = *input;
                 // Bounds check
-                const LEN: usize = std::mem::size_of::<$t>();
+                const LEN: usize = core::mem::size_of::<$t>();
                 if bytes.len() < LEN {
                     return Err(());
                 }
@@ -36,7 +53,7 @@ macro_rules! parseable_integral {
             fn parse_be(input: &mut &[u8]) -> ParseResult<Self> {
                 let bytes = *input;
                 // Bounds check
-                const LEN: usize = std::mem::size_of::<$t>();
+                const LEN: usize = core::mem::size_of::<$t>();
                 if bytes.len() < LEN {
                     return Err(());
                 }
@@ -0,0 +1,510 @@
+
+// Signed distance field generation from coverage bitmaps, and multi-channel
+// signed distance fields (MSDF) from outlines. MSDFs preserve sharp corners by
+// storing the distances to differently colored edges in separate channels,
+// as described in: Viktor Chlumský, Shape Decomposition for Multi-channel
+// Distance Fields.
+
+use crate::{RasterizedGlyph, ColoredGlyph, GlyphBox, BitDepth, Outline, PathSegment};
+
+/// Stands for infinity in the distance transform, without the NaNs that real
+/// infinity would produce when subtracted from itself.
+const FAR: f32 = 1e20;
+
+/// The one-dimensional squared Euclidean distance transform of Felzenszwalb and
+/// Huttenlocher. `f` is the sampled function, the result is written to `d`.
+/// `v` and `z` are scratch buffers of at least `f.len()` and `f.len() + 1`
+/// elements.
+fn edt_1d(f: &[f32], d: &mut [f32], v: &mut [usize], z: &mut [f32]) {
+    let n = f.len();
+    // The parabola intersecting at q
+    let intersect = |q: usize, p: usize| {
+        ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2 * q - 2 * p) as f32
+    };
+    // Compute the lower envelope of the parabolas
+    let mut k = 0;
+    v[0] = 0;
+    z[0] = -FAR;
+    z[1] = FAR;
+    for q in 1..n {
+        let mut s = intersect(q, v[k]);
+        while s <= z[k] {
+            k -= 1;
+            s = intersect(q, v[k]);
+        }
+        k += 1;
+        v[k] = q;
+        z[k] = s;
+        z[k + 1] = FAR;
+    }
+    // Sample the envelope
+    k = 0;
+    for (q, d) in d[..n].iter_mut().enumerate() {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        let dq = q as f32 - v[k] as f32;
+        *d = dq * dq + f[v[k]];
+    }
+}
+
+/// Computes the squared distance of every pixel to the nearest pixel where
+/// `grid` is 0, in place.
+fn edt_2d(grid: &mut [f32], width: usize, height: usize) {
+    let n = std::cmp::max(width, height);
+    let mut f = vec![0f32; n];
+    let mut d = vec![0f32; n];
+    let mut v = vec![0usize; n];
+    let mut z = vec![0f32; n + 1];
+    // Columns
+    for x in 0..width {
+        for y in 0..height {
+            f[y] = grid[y * width + x];
+        }
+        edt_1d(&f[..height], &mut d[..height], &mut v, &mut z);
+        for y in 0..height {
+            grid[y * width + x] = d[y];
+        }
+    }
+    // Rows
+    for y in 0..height {
+        let row = &mut grid[(y * width)..((y + 1) * width)];
+        f[..width].copy_from_slice(row);
+        edt_1d(&f[..width], row, &mut v, &mut z);
+    }
+}
+
+/// Encodes a signed distance around the edge value of 128.
+fn encode(distance: f32, scale: f32) -> u8 {
+    (128.0 + distance * scale).round().clamp(0.0, 255.0) as u8
+}
+
+/// Converts a rasterized coverage bitmap to a signed distance field. The
+/// bitmap grows by `spread` pixels on each side, and distances up to `spread`
+/// pixels are encoded around the edge value of 128, inside being brighter.
+pub(crate) fn to_sdf(glyph: &RasterizedGlyph, spread: usize) -> RasterizedGlyph {
+    let width = glyph.width + 2 * spread;
+    let height = glyph.height + 2 * spread;
+    let inside = |x: usize, y: usize| {
+        x >= spread && y >= spread && x < glyph.width + spread && y < glyph.height + spread
+            && glyph.coverage(x - spread, y - spread) >= 128
+    };
+    // Distances to the nearest inside and outside pixels
+    let mut to_inside = vec![0f32; width * height];
+    let mut to_outside = vec![0f32; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if inside(x, y) {
+                to_outside[i] = FAR;
+            }
+            else {
+                to_inside[i] = FAR;
+            }
+        }
+    }
+    edt_2d(&mut to_inside, width, height);
+    edt_2d(&mut to_outside, width, height);
+    // Encode the signed distance
+    let scale = 127.0 / spread as f32;
+    let data = to_inside.iter().zip(to_outside.iter())
+        .map(|(i, o)| {
+            // The edge is half a pixel from the centers of the pixels next to it
+            let distance = if *o > 0.0 { o.sqrt() - 0.5 } else { 0.5 - i.sqrt() };
+            encode(distance, scale)
+        })
+        .collect();
+    RasterizedGlyph{
+        character: glyph.character,
+        x_offset: glyph.x_offset - spread as i32,
+        y_offset: glyph.y_offset - spread as i32,
+        width,
+        height,
+        bit_depth: BitDepth::Eight,
+        cell: glyph.cell,
+        baseline: glyph.baseline,
+        data,
+    }
+}
+
+// Multi-channel distance fields
+
+/// The channels of the distance field, the colors of the edges are made of.
+const RED: u8 = 1;
+const GREEN: u8 = 2;
+const BLUE: u8 = 4;
+const WHITE: u8 = RED | GREEN | BLUE;
+
+/// The colors of the edges between corners, any two of them share a single
+/// channel.
+const CORNER_COLORS: [u8; 3] = [GREEN | BLUE, RED | BLUE, RED | GREEN];
+
+/// The colors of the three parts of a contour with a single corner, the first
+/// and last meeting at the corner.
+const TEARDROP_COLORS: [u8; 3] = [RED | BLUE, WHITE, RED | GREEN];
+
+/// Directions change sharper than this at corners, about 8 degrees, as the
+/// sine of the angle.
+const CORNER_THRESHOLD: f32 = 0.14;
+
+/// The largest distance in pixels the flattened curves can be from the real
+/// ones.
+const FLATNESS: f32 = 0.02;
+
+type Point = (f32, f32);
+
+/// Returns the vector from `b` to `a`.
+fn sub(a: Point, b: Point) -> Point { (a.0 - b.0, a.1 - b.1) }
+/// Returns the dot product of two vectors.
+fn dot(a: Point, b: Point) -> f32 { a.0 * b.0 + a.1 * b.1 }
+/// Returns the z component of the cross product of two vectors.
+fn cross(a: Point, b: Point) -> f32 { a.0 * b.1 - a.1 * b.0 }
+/// Returns the length of a vector.
+fn length(a: Point) -> f32 { dot(a, a).sqrt() }
+
+/// A straight piece of a contour, curves are flattened into these.
+struct Edge {
+    from: Point,
+    to: Point,
+    /// The channels the edge is counted in.
+    channels: u8,
+}
+
+/// The distance of a point from an edge.
+#[derive(Clone, Copy)]
+struct EdgeDistance {
+    /// The signed distance, positive inside the glyph.
+    distance: f32,
+    /// How parallel the edge is to the direction of the point, if the
+    /// point is nearest to one of its ends. Of two edges at the same
+    /// distance, the less parallel one is nearer.
+    dot: f32,
+    /// Where the nearest point would be on the line of the edge, between 0
+    /// and 1 if it's on the edge.
+    param: f32,
+}
+
+impl EdgeDistance {
+    /// Checks if this distance is nearer than the other one.
+    fn is_nearer(&self, other: &EdgeDistance) -> bool {
+        let (a, b) = (self.distance.abs(), other.distance.abs());
+        a < b || (a == b && self.dot < other.dot)
+    }
+}
+
+impl Edge {
+    /// Returns the distance of a point from the edge. TrueType contours go
+    /// clockwise around the ink with the y axis pointing up, so with the y
+    /// axis pointing down the inside is on the left.
+    fn distance(&self, p: Point) -> EdgeDistance {
+        let ab = sub(self.to, self.from);
+        let ap = sub(p, self.from);
+        let param = dot(ap, ab) / dot(ab, ab);
+        if param > 0.0 && param < 1.0 {
+            let ortho = cross(ab, ap) / length(ab);
+            return EdgeDistance{ distance: ortho, dot: 0.0, param };
+        }
+        let end = sub(if param > 0.5 { self.to } else { self.from }, p);
+        let end_distance = length(end);
+        let side = if cross(ab, ap) >= 0.0 { 1.0 } else { -1.0 };
+        let dot = if end_distance > 0.0 { (dot(ab, end) / (length(ab) * end_distance)).abs() } else { 0.0 };
+        EdgeDistance{ distance: side * end_distance, dot, param }
+    }
+
+    /// Returns the distance of a point from the line the edge is on, if the
+    /// point is beyond the end of the edge it's nearest to. This keeps the
+    /// distances of edges meeting at corners from bending around the corner.
+    fn pseudo_distance(&self, p: Point, d: EdgeDistance) -> f32 {
+        let ab = sub(self.to, self.from);
+        let dir = (ab.0 / length(ab), ab.1 / length(ab));
+        let beyond = if d.param < 0.0 {
+                let ap = sub(p, self.from);
+                Some((dot(ap, dir) < 0.0, ap))
+            }
+            else if d.param > 1.0 {
+                let bp = sub(p, self.to);
+                Some((dot(bp, dir) > 0.0, bp))
+            }
+            else {
+                None
+            };
+        match beyond {
+            Some((true, ep)) if cross(dir, ep).abs() <= d.distance.abs() => cross(dir, ep),
+            _ => d.distance,
+        }
+    }
+}
+
+/// Returns the end point of a segment.
+fn end_point(segment: &PathSegment) -> Point {
+    match *segment {
+        PathSegment::Line{ to } | PathSegment::Quadratic{ to, .. } | PathSegment::Cubic{ to, .. } => to,
+    }
+}
+
+/// Returns the directions a segment starting at `from` starts and ends in.
+fn tangents(from: Point, segment: &PathSegment) -> (Point, Point) {
+    let points = match *segment {
+        PathSegment::Line{ to } => vec![from, to],
+        PathSegment::Quadratic{ ctrl, to } => vec![from, ctrl, to],
+        PathSegment::Cubic{ ctrl1, ctrl2, to } => vec![from, ctrl1, ctrl2, to],
+    };
+    let to = end_point(segment);
+    // Control points on the end points don't give a direction
+    let start = points.iter().find(|p| **p != from).map_or((0.0, 0.0), |p| sub(*p, from));
+    let end = points.iter().rev().find(|p| **p != to).map_or((0.0, 0.0), |p| sub(to, *p));
+    (start, end)
+}
+
+/// Checks if the direction changes sharply enough from `a` to `b` to be a
+/// corner.
+fn is_corner(a: Point, b: Point) -> bool {
+    let (a_len, b_len) = (length(a), length(b));
+    if a_len == 0.0 || b_len == 0.0 {
+        return false;
+    }
+    dot(a, b) <= 0.0 || (cross(a, b) / (a_len * b_len)).abs() > CORNER_THRESHOLD
+}
+
+/// Flattens a segment starting at `from` into the points of a polyline,
+/// after `from`.
+fn flatten(from: Point, segment: &PathSegment, points: &mut Vec<Point>) {
+    // The chord error of a piece is bounded by the second derivative, and
+    // falls with the square of the number of pieces
+    let pieces = |error: f32| ((error / FLATNESS).sqrt().ceil() as usize).clamp(1, 64);
+    match *segment {
+        PathSegment::Line{ to } => points.push(to),
+        PathSegment::Quadratic{ ctrl, to } => {
+            let n = pieces(length(sub(sub(from, ctrl), sub(ctrl, to))) / 4.0);
+            for i in 1..=n {
+                let t = i as f32 / n as f32;
+                let (a, b, c) = ((1.0 - t) * (1.0 - t), 2.0 * (1.0 - t) * t, t * t);
+                points.push((a * from.0 + b * ctrl.0 + c * to.0, a * from.1 + b * ctrl.1 + c * to.1));
+            }
+        },
+        PathSegment::Cubic{ ctrl1, ctrl2, to } => {
+            let dd1 = length(sub(sub(from, ctrl1), sub(ctrl1, ctrl2)));
+            let dd2 = length(sub(sub(ctrl1, ctrl2), sub(ctrl2, to)));
+            let n = pieces(dd1.max(dd2) * 0.75);
+            for i in 1..=n {
+                let t = i as f32 / n as f32;
+                let mt = 1.0 - t;
+                let (a, b, c, d) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+                points.push((a * from.0 + b * ctrl1.0 + c * ctrl2.0 + d * to.0,
+                    a * from.1 + b * ctrl1.1 + c * ctrl2.1 + d * to.1));
+            }
+        },
+    }
+}
+
+/// Flattens the contours of the outline into edges, colored so that the
+/// edges meeting at a corner have a single channel in common.
+fn colored_edges(outline: &Outline) -> Vec<Edge> {
+    let mut result = Vec::new();
+    for contour in &outline.contours {
+        // The segments with their start points, the ones without a length
+        // don't count
+        let mut segments = Vec::new();
+        let mut from = contour.start;
+        for segment in &contour.segments {
+            let (start, _) = tangents(from, segment);
+            if start != (0.0, 0.0) {
+                segments.push((from, segment));
+            }
+            from = end_point(segment);
+        }
+        let n = segments.len();
+        // The segments starting at a corner
+        let corners: Vec<usize> = (0..n)
+            .filter(|i| {
+                let (prev_from, prev) = segments[(i + n - 1) % n];
+                let (from, segment) = segments[*i];
+                is_corner(tangents(prev_from, prev).1, tangents(from, segment).0)
+            })
+            .collect();
+        // Flatten the segments, starting from the first corner
+        let first = corners.first().copied().unwrap_or(0);
+        let mut edges = Vec::new();
+        let mut points = Vec::new();
+        for k in 0..n {
+            let i = (first + k) % n;
+            let (mut from, segment) = segments[i];
+            points.clear();
+            flatten(from, segment, &mut points);
+            for to in points.iter() {
+                if *to != from {
+                    edges.push((from, *to, i));
+                }
+                from = *to;
+            }
+        }
+        match corners.len() {
+            // Smooth contours don't need colors
+            0 => result.extend(edges.iter().map(|(from, to, _)| Edge{ from: *from, to: *to, channels: WHITE })),
+            // A single corner is split into three parts by the edges around
+            // it, there have to be at least three edges for that
+            1 => {
+                if edges.len() < 3 {
+                    edges = edges.iter()
+                        .flat_map(|(from, to, i)| (0..3).map(move |k| {
+                            let lerp = |t: f32| (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+                            (lerp(k as f32 / 3.0), lerp((k + 1) as f32 / 3.0), *i)
+                        }))
+                        .collect();
+                }
+                let m = edges.len();
+                result.extend(edges.iter().enumerate().map(|(k, (from, to, _))| Edge{
+                    from: *from,
+                    to: *to,
+                    channels: TEARDROP_COLORS[3 * k / m],
+                }));
+            },
+            // The color changes at every corner, and the last one has to
+            // differ from the first one too
+            _ => {
+                let mut colors = vec![0; n];
+                let mut color = 0;
+                for (k, corner) in corners.iter().enumerate() {
+                    if k > 0 {
+                        color = (color + 1) % 3;
+                        if k == corners.len() - 1 && color == 0 {
+                            color = 1;
+                        }
+                    }
+                    let end = corners.get(k + 1).copied().unwrap_or(first + n);
+                    for i in *corner..end {
+                        colors[i % n] = CORNER_COLORS[color];
+                    }
+                }
+                result.extend(edges.iter().map(|(from, to, i)| Edge{ from: *from, to: *to, channels: colors[*i] }));
+            },
+        }
+    }
+    result
+}
+
+/// Returns the winding number of the edges around a point, which is non-zero
+/// inside the glyph.
+fn winding(edges: &[Edge], p: Point) -> i32 {
+    let mut winding = 0;
+    for e in edges {
+        let side = cross(sub(e.to, e.from), sub(p, e.from));
+        if e.from.1 <= p.1 && e.to.1 > p.1 && side > 0.0 {
+            winding += 1;
+        }
+        else if e.from.1 > p.1 && e.to.1 <= p.1 && side < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+/// Returns the median of three values.
+fn median(a: f32, b: f32, c: f32) -> f32 {
+    a.min(b).max(a.max(b).min(c))
+}
+
+/// Generates a multi-channel signed distance field from an outline. The red,
+/// green and blue channels hold the distances to the edges of their color,
+/// the alpha channel the true signed distance, encoded like `to_sdf` does.
+/// The offsets are relative to the origin of the outline, and the character
+/// and cell are left empty.
+pub(crate) fn to_msdf(outline: &Outline, spread: usize) -> ColoredGlyph {
+    let edges = colored_edges(outline);
+    let empty = ColoredGlyph{
+        character: '\0',
+        x_offset: 0,
+        y_offset: 0,
+        width: 0,
+        height: 0,
+        cell: GlyphBox::default(),
+        data: Box::new([]),
+    };
+    if edges.is_empty() {
+        return empty;
+    }
+    // The bounds of the edges, grown by the spread
+    let (mut min, mut max) = ((FAR, FAR), (-FAR, -FAR));
+    for e in &edges {
+        min = (min.0.min(e.from.0), min.1.min(e.from.1));
+        max = (max.0.max(e.from.0), max.1.max(e.from.1));
+    }
+    let x0 = min.0.floor() as i32 - spread as i32;
+    let y0 = min.1.floor() as i32 - spread as i32;
+    let width = (max.0.ceil() as i32 + spread as i32 - x0) as usize;
+    let height = (max.1.ceil() as i32 + spread as i32 - y0) as usize;
+
+    let scale = 127.0 / spread as f32;
+    let mut data = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let p = ((x0 + x as i32) as f32 + 0.5, (y0 + y as i32) as f32 + 0.5);
+            // The nearest edge of each channel, and the nearest one overall
+            let mut nearest: [Option<(EdgeDistance, &Edge)>; 3] = [None; 3];
+            let mut true_distance = FAR;
+            for e in &edges {
+                let d = e.distance(p);
+                true_distance = true_distance.min(d.distance.abs());
+                for (c, n) in nearest.iter_mut().enumerate() {
+                    if e.channels & (1 << c) != 0 && n.is_none_or(|(nd, _)| d.is_nearer(&nd)) {
+                        *n = Some((d, e));
+                    }
+                }
+            }
+            let inside = winding(&edges, p) != 0;
+            let true_distance = if inside { true_distance } else { -true_distance };
+            let mut channels = [true_distance; 3];
+            for (c, n) in channels.iter_mut().zip(nearest.iter()) {
+                if let Some((d, e)) = n {
+                    *c = e.pseudo_distance(p, *d);
+                }
+            }
+            // Where the channels would put the pixel on the wrong side of the
+            // edge, like around overlapping contours, fall back to the true
+            // distance
+            if (median(channels[0], channels[1], channels[2]) > 0.0) != inside {
+                channels = [true_distance; 3];
+            }
+            data.extend(channels.iter().map(|d| encode(*d, scale)));
+            data.push(encode(true_distance, scale));
+        }
+    }
+    ColoredGlyph{
+        x_offset: x0,
+        y_offset: y0,
+        width,
+        height,
+        data: data.into_boxed_slice(),
+        ..empty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Contour;
+
+    #[test]
+    fn msdf_keeps_the_corners_of_a_square() {
+        // A 10 pixel square above the baseline, clockwise with the y axis up
+        let line = |x, y| PathSegment::Line{ to: (x, y) };
+        let outline = Outline{
+            contours: vec![Contour{
+                start: (0.0, 0.0),
+                segments: vec![line(0.0, -10.0), line(10.0, -10.0), line(10.0, 0.0), line(0.0, 0.0)],
+            }],
+        };
+        let glyph = to_msdf(&outline, 2);
+        assert_eq!((glyph.x_offset, glyph.y_offset, glyph.width, glyph.height), (-2, -12, 14, 14));
+        let median_at = |x: usize, y: usize| {
+            let p = &glyph.data[((y * glyph.width + x) * 4)..];
+            median(p[0] as f32, p[1] as f32, p[2] as f32)
+        };
+        for y in 0..glyph.height {
+            for x in 0..glyph.width {
+                let inside = (2..12).contains(&x) && (2..12).contains(&y);
+                assert_eq!(median_at(x, y) >= 128.0, inside, "{}, {}", x, y);
+            }
+        }
+    }
+}
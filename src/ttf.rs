@@ -2,10 +2,19 @@
 // TrueType format interpretation.
 
 use super::parse::*;
-use std::collections::{HashMap, HashSet};
+use core::cmp::Ordering;
+use core::ops::Range;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::format;
 
 /// The magic number that must be in the head table's `magic_number` field.
 const HEAD_TABLE_MAGIC: u32 = 0x5F0F3CF5;
+/// The value that the checksum of the whole file and the head table's
+/// `checksum_adjustment` field must add up to.
+const CHECKSUM_MAGIC: u32 = 0xB1B0AFBA;
 
 // Types defined by Apple, they are just for easier doc-reading.
 type Fixed        = i32;
@@ -50,6 +59,76 @@ parseable_struct!{HeadTable{
     glyph_data_format  : i16         ,
 }}
 
+// https://docs.microsoft.com/en-us/typography/opentype/spec/maxp
+parseable_struct!{MaxpHeader{
+    version   : Fixed,
+    num_glyphs: u16  ,
+}}
+
+// The fields that version 1.0 adds, used by fonts with TrueType outlines
+parseable_struct!{MaxpLimits{
+    max_points              : u16,
+    max_contours            : u16,
+    max_composite_points    : u16,
+    max_composite_contours  : u16,
+    max_zones               : u16,
+    max_twilight_points     : u16,
+    max_storage             : u16,
+    max_function_defs       : u16,
+    max_instruction_defs    : u16,
+    max_stack_elements      : u16,
+    max_size_of_instructions: u16,
+    max_component_elements  : u16,
+    max_component_depth     : u16,
+}}
+
+const MAXP_VERSION_0_5: Fixed = 0x00005000;
+const MAXP_VERSION_1_0: Fixed = 0x00010000;
+
+/// Parses the 'maxp' table and returns the number of glyphs in the font.
+fn parse_maxp(table: &[u8]) -> ParseResult<u16> {
+    let mut bytes = table;
+    let header = MaxpHeader::parse_be(&mut bytes)?;
+    match header.version {
+        MAXP_VERSION_0_5 => {},
+        MAXP_VERSION_1_0 => {
+            // We don't need the limits, but the table must hold them
+            MaxpLimits::parse_be(&mut bytes)?;
+        },
+        _ => return Err(()),
+    }
+    Ok(header.num_glyphs)
+}
+
+/// The size of the offsets in the 'loca' table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LocaFormat {
+    /// 16-bit offsets, divided by 2.
+    Short,
+    /// 32-bit offsets.
+    Long,
+}
+
+/// The only defined format of the 'glyf' table.
+const GLYPH_DATA_FORMAT: i16 = 0;
+
+impl HeadTable {
+    /// Returns the format of the 'loca' table, or an error if the
+    /// `index_to_loc_format` field has an undefined value.
+    fn loca_format(&self) -> ParseResult<LocaFormat> {
+        match self.index_to_loc_format {
+            0 => Ok(LocaFormat::Short),
+            1 => Ok(LocaFormat::Long),
+            _ => Err(()),
+        }
+    }
+
+    /// Returns true if the 'glyf' table is in a format we understand.
+    fn has_known_glyph_data_format(&self) -> bool {
+        self.glyph_data_format == GLYPH_DATA_FORMAT
+    }
+}
+
 // https://developer.apple.com/fonts/TrueType-Reference-Manual/RM06/Chap6name.html
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
@@ -88,7 +167,1235 @@ parseable_struct!{NameRecord{
     offset              : u16,
 }}
 
-// TODO: Do we need to store the unused tables?
+// The common header of the GSUB and GPOS tables.
+// https://docs.microsoft.com/en-us/typography/opentype/spec/gsub
+// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos
+parseable_struct!{LayoutHeader{
+    major_version      : u16,
+    minor_version      : u16,
+    script_list_offset : u16,
+    feature_list_offset: u16,
+    lookup_list_offset : u16,
+}}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#feature-list-table
+parseable_struct!{FeatureRecord{
+    feature_tag   : [u8; 4],
+    feature_offset: u16    ,
+}}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#lookup-table
+parseable_struct!{LookupHeader{
+    lookup_type   : u16,
+    lookup_flag   : u16,
+    subtable_count: u16,
+}}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#coverage-table
+parseable_struct!{RangeRecord{
+    start_glyph_id      : u16,
+    end_glyph_id        : u16,
+    start_coverage_index: u16,
+}}
+
+// Ligatures and mark attachments are only applied by the Win32 backend, the
+// other ones shape text with the platform
+
+/// The GSUB features that are considered standard ligatures.
+#[cfg(all(feature = "std", target_os = "windows"))]
+const LIGATURE_FEATURES: [&[u8; 4]; 3] = [b"liga", b"clig", b"rlig"];
+
+/// A ligature substitution from the GSUB table.
+#[cfg(all(feature = "std", target_os = "windows"))]
+#[derive(Debug, Clone)]
+pub(crate) struct Ligature {
+    /// The glyph that replaces the sequence.
+    pub(crate) glyph: u16,
+    /// The glyphs that follow the first glyph in the sequence.
+    pub(crate) components: Vec<u16>,
+}
+
+/// A coverage table, the glyphs a subtable applies to, each with its coverage
+/// index.
+#[derive(Debug, Clone)]
+enum Coverage {
+    /// The covered glyphs in coverage index order (format 1).
+    Glyphs(Vec<u16>),
+    /// Ranges of consecutive glyphs, sorted by their first glyph (format 2).
+    Ranges(Vec<RangeRecord>),
+}
+
+impl Coverage {
+    /// Returns the coverage index of the glyph, or None if it isn't covered.
+    fn index(&self, glyph: u16) -> Option<usize> {
+        match self {
+            Self::Glyphs(glyphs) => glyphs.binary_search(&glyph).ok(),
+            Self::Ranges(ranges) => {
+                let index = ranges.binary_search_by(|range| {
+                    if range.end_glyph_id < glyph {
+                        Ordering::Less
+                    }
+                    else if range.start_glyph_id > glyph {
+                        Ordering::Greater
+                    }
+                    else {
+                        Ordering::Equal
+                    }
+                }).ok()?;
+                let range = &ranges[index];
+                Some(range.start_coverage_index as usize + (glyph - range.start_glyph_id) as usize)
+            },
+        }
+    }
+
+    /// Returns the covered glyphs in coverage index order. The ranges are
+    /// expanded as they are iterated, so callers stop at the number of records
+    /// they have for the glyphs.
+    fn glyphs(&self) -> Box<dyn Iterator<Item = u16> + '_> {
+        match self {
+            Self::Glyphs(glyphs) => Box::new(glyphs.iter().copied()),
+            Self::Ranges(ranges) => Box::new(ranges.iter().flat_map(|r| r.start_glyph_id..=r.end_glyph_id)),
+        }
+    }
+}
+
+/// Parses a coverage table.
+fn parse_coverage(table: &[u8]) -> ParseResult<Coverage> {
+    let mut bytes = table;
+    let format = u16::parse_be(&mut bytes)?;
+    let count = u16::parse_be(&mut bytes)? as usize;
+    match format {
+        1 => Ok(Coverage::Glyphs(parse_be_array(&mut bytes, count)?)),
+        2 => {
+            let mut ranges: Vec<RangeRecord> = parse_be_array(&mut bytes, count)?;
+            // The spec requires the order, but the lookup relies on it
+            ranges.sort_by_key(|range| range.start_glyph_id);
+            Ok(Coverage::Ranges(ranges))
+        },
+        _ => Err(()),
+    }
+}
+
+/// Parses a ligature substitution subtable (lookup type 4), adding the
+/// ligatures keyed by their first glyph.
+#[cfg(all(feature = "std", target_os = "windows"))]
+fn parse_ligature_subst(table: &[u8], ligatures: &mut BTreeMap<u16, Vec<Ligature>>) -> ParseResult<()> {
+    let mut bytes = table;
+    let _format = u16::parse_be(&mut bytes)?;
+    let coverage_offset = u16::parse_be(&mut bytes)?;
+    let set_count = u16::parse_be(&mut bytes)? as usize;
+    let set_offsets: Vec<u16> = parse_be_array(&mut bytes, set_count)?;
+    let coverage = parse_coverage(offset_slice(table, coverage_offset as usize)?)?;
+    for (first, set_offset) in coverage.glyphs().zip(set_offsets) {
+        let set = offset_slice(table, set_offset as usize)?;
+        let mut bytes = set;
+        let count = u16::parse_be(&mut bytes)? as usize;
+        let offsets: Vec<u16> = parse_be_array(&mut bytes, count)?;
+        for offset in offsets {
+            let mut bytes = offset_slice(set, offset as usize)?;
+            let glyph = u16::parse_be(&mut bytes)?;
+            let component_count = u16::parse_be(&mut bytes)? as usize;
+            let components = parse_be_array(&mut bytes, component_count.saturating_sub(1))?;
+            ligatures.entry(first).or_default().push(Ligature{ glyph, components });
+        }
+    }
+    Ok(())
+}
+
+/// Collects the subtables of the lookups referenced by the given features from
+/// a GSUB or GPOS table, resolving extension subtables of the given lookup
+/// type. Returns the lookup type with each subtable. Scripts and languages are
+/// not distinguished.
+fn feature_subtables<'a>(table: &'a [u8], features: &[&[u8; 4]], extension_type: u16)
+    -> ParseResult<Vec<(u16, &'a [u8])>> {
+    let header = LayoutHeader::parse_be(&mut &table[..])?;
+    // Collect the lookups referenced by the features
+    let feature_list = offset_slice(table, header.feature_list_offset as usize)?;
+    let mut bytes = feature_list;
+    let feature_count = u16::parse_be(&mut bytes)? as usize;
+    let mut lookup_indices = Vec::new();
+    for record in parse_be_array::<FeatureRecord>(&mut bytes, feature_count)? {
+        if !features.contains(&&record.feature_tag) {
+            continue;
+        }
+        let mut bytes = offset_slice(feature_list, record.feature_offset as usize)?;
+        let _params_offset = u16::parse_be(&mut bytes)?;
+        let index_count = u16::parse_be(&mut bytes)? as usize;
+        lookup_indices.extend(parse_be_array::<u16>(&mut bytes, index_count)?);
+    }
+    lookup_indices.sort();
+    lookup_indices.dedup();
+    // Collect the subtables of the lookups
+    let lookup_list = offset_slice(table, header.lookup_list_offset as usize)?;
+    let mut bytes = lookup_list;
+    let lookup_count = u16::parse_be(&mut bytes)? as usize;
+    let lookup_offsets: Vec<u16> = parse_be_array(&mut bytes, lookup_count)?;
+    let mut result = Vec::new();
+    for index in lookup_indices {
+        let lookup_offset = *lookup_offsets.get(index as usize).ok_or(())?;
+        let lookup = offset_slice(lookup_list, lookup_offset as usize)?;
+        let mut bytes = lookup;
+        let header = LookupHeader::parse_be(&mut bytes)?;
+        let subtable_offsets: Vec<u16> = parse_be_array(&mut bytes, header.subtable_count as usize)?;
+        for offset in subtable_offsets {
+            let subtable = offset_slice(lookup, offset as usize)?;
+            if header.lookup_type == extension_type {
+                // Wraps a subtable of another type with a 32-bit offset
+                let mut bytes = subtable;
+                let _format = u16::parse_be(&mut bytes)?;
+                let lookup_type = u16::parse_be(&mut bytes)?;
+                let offset = u32::parse_be(&mut bytes)?;
+                result.push((lookup_type, offset_slice(subtable, offset as usize)?));
+            }
+            else {
+                result.push((header.lookup_type, subtable));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Parses the ligature substitutions of the standard ligature features from
+/// the GSUB table.
+#[cfg(all(feature = "std", target_os = "windows"))]
+fn parse_gsub_ligatures(table: &[u8]) -> ParseResult<BTreeMap<u16, Vec<Ligature>>> {
+    let mut ligatures = BTreeMap::new();
+    for (lookup_type, subtable) in feature_subtables(table, &LIGATURE_FEATURES, 7)? {
+        if lookup_type == 4 {
+            parse_ligature_subst(subtable, &mut ligatures)?;
+        }
+    }
+    Ok(ligatures)
+}
+
+/// Parses an anchor table, returning the anchor point in font design units.
+/// Only the coordinates are used from all anchor formats.
+#[cfg(all(feature = "std", target_os = "windows"))]
+fn parse_anchor(table: &[u8]) -> ParseResult<(i16, i16)> {
+    let mut bytes = table;
+    let _format = u16::parse_be(&mut bytes)?;
+    let x = i16::parse_be(&mut bytes)?;
+    let y = i16::parse_be(&mut bytes)?;
+    Ok((x, y))
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#lookup-type-4-mark-to-base-attachment-positioning-subtable
+#[cfg(all(feature = "std", target_os = "windows"))]
+parseable_struct!{MarkBasePosHeader{
+    pos_format          : u16,
+    mark_coverage_offset: u16,
+    base_coverage_offset: u16,
+    mark_class_count    : u16,
+    mark_array_offset   : u16,
+    base_array_offset   : u16,
+}}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#mark-array-table
+#[cfg(all(feature = "std", target_os = "windows"))]
+parseable_struct!{MarkRecord{
+    mark_class        : u16,
+    mark_anchor_offset: u16,
+}}
+
+/// A mark-to-base attachment subtable from the GPOS table.
+#[cfg(all(feature = "std", target_os = "windows"))]
+#[derive(Debug, Default, Clone)]
+pub(crate) struct MarkToBase {
+    /// The mark glyphs with their mark class and anchor point.
+    marks: BTreeMap<u16, (u16, (i16, i16))>,
+    /// The base glyphs with their anchor point for each mark class.
+    bases: BTreeMap<u16, Vec<Option<(i16, i16)>>>,
+}
+
+/// Parses a mark-to-base attachment subtable (lookup type 4).
+#[cfg(all(feature = "std", target_os = "windows"))]
+fn parse_mark_to_base(table: &[u8]) -> ParseResult<MarkToBase> {
+    let header = MarkBasePosHeader::parse_be(&mut &table[..])?;
+    let class_count = header.mark_class_count as usize;
+    let mut result = MarkToBase::default();
+    // Marks
+    let mark_coverage = parse_coverage(offset_slice(table, header.mark_coverage_offset as usize)?)?;
+    let mark_array = offset_slice(table, header.mark_array_offset as usize)?;
+    let mut bytes = mark_array;
+    let mark_count = u16::parse_be(&mut bytes)? as usize;
+    let marks: Vec<MarkRecord> = parse_be_array(&mut bytes, mark_count)?;
+    for (glyph, mark) in mark_coverage.glyphs().zip(marks) {
+        let anchor = parse_anchor(offset_slice(mark_array, mark.mark_anchor_offset as usize)?)?;
+        result.marks.insert(glyph, (mark.mark_class, anchor));
+    }
+    // Bases
+    let base_coverage = parse_coverage(offset_slice(table, header.base_coverage_offset as usize)?)?;
+    let base_array = offset_slice(table, header.base_array_offset as usize)?;
+    let mut bytes = base_array;
+    let base_count = u16::parse_be(&mut bytes)? as usize;
+    for glyph in base_coverage.glyphs().take(base_count) {
+        let offsets: Vec<u16> = parse_be_array(&mut bytes, class_count)?;
+        let mut anchors = Vec::with_capacity(class_count);
+        for offset in offsets {
+            // A null offset means no anchor for the class
+            anchors.push(if offset == 0 {
+                None
+            }
+            else {
+                Some(parse_anchor(offset_slice(base_array, offset as usize)?)?)
+            });
+        }
+        result.bases.insert(glyph, anchors);
+    }
+    Ok(result)
+}
+
+/// Parses the mark-to-base attachments of the 'mark' feature from the GPOS
+/// table.
+#[cfg(all(feature = "std", target_os = "windows"))]
+fn parse_gpos_mark_to_base(table: &[u8]) -> ParseResult<Vec<MarkToBase>> {
+    let mut result = Vec::new();
+    for (lookup_type, subtable) in feature_subtables(table, &[b"mark"], 9)? {
+        if lookup_type == 4 {
+            result.push(parse_mark_to_base(subtable)?);
+        }
+    }
+    Ok(result)
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/gpos#lookup-type-2-pair-adjustment-positioning-subtable
+parseable_struct!{PairPosHeader{
+    pos_format     : u16,
+    coverage_offset: u16,
+    value_format1  : u16,
+    value_format2  : u16,
+}}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/chapter2#class-definition-table
+parseable_struct!{ClassRangeRecord{
+    start_glyph_id: u16,
+    end_glyph_id  : u16,
+    class         : u16,
+}}
+
+/// The value record flag of the horizontal advance adjustment.
+const X_ADVANCE: u16 = 0x0004;
+
+/// Parses a value record of the given format, returning the adjustment of the
+/// horizontal advance. The rest of the values are skipped.
+fn parse_x_advance(input: &mut &[u8], format: u16) -> ParseResult<i16> {
+    let mut x_advance = 0;
+    // Every set flag is followed by a 16-bit value, in the order of the flags
+    for bit in 0..8 {
+        if format & (1 << bit) != 0 {
+            let value = i16::parse_be(input)?;
+            if 1 << bit == X_ADVANCE {
+                x_advance = value;
+            }
+        }
+    }
+    Ok(x_advance)
+}
+
+/// A class definition table, glyphs missing from it are in class 0.
+#[derive(Debug, Clone)]
+enum ClassDef {
+    /// The classes of a consecutive run of glyphs (format 1).
+    Array{ start: u16, classes: Vec<u16> },
+    /// Ranges of glyphs sharing a class, sorted by their first glyph (format 2).
+    Ranges(Vec<ClassRangeRecord>),
+}
+
+impl ClassDef {
+    /// Returns the class of the given glyph.
+    fn class(&self, glyph: u16) -> u16 {
+        match self {
+            Self::Array{ start, classes } => glyph.checked_sub(*start)
+                .and_then(|index| classes.get(index as usize))
+                .copied()
+                .unwrap_or(0),
+            Self::Ranges(ranges) => ranges
+                .binary_search_by(|range| {
+                    if range.end_glyph_id < glyph {
+                        Ordering::Less
+                    }
+                    else if range.start_glyph_id > glyph {
+                        Ordering::Greater
+                    }
+                    else {
+                        Ordering::Equal
+                    }
+                })
+                .map(|index| ranges[index].class)
+                .unwrap_or(0),
+        }
+    }
+}
+
+/// Parses a class definition table.
+fn parse_class_def(table: &[u8]) -> ParseResult<ClassDef> {
+    let mut bytes = table;
+    let format = u16::parse_be(&mut bytes)?;
+    match format {
+        1 => {
+            let start = u16::parse_be(&mut bytes)?;
+            let count = u16::parse_be(&mut bytes)? as usize;
+            let classes = parse_be_array(&mut bytes, count)?;
+            Ok(ClassDef::Array{ start, classes })
+        },
+        2 => {
+            let count = u16::parse_be(&mut bytes)? as usize;
+            let mut ranges: Vec<ClassRangeRecord> = parse_be_array(&mut bytes, count)?;
+            // The spec requires the order, but the lookup relies on it
+            ranges.sort_by_key(|range| range.start_glyph_id);
+            Ok(ClassDef::Ranges(ranges))
+        },
+        _ => Err(()),
+    }
+}
+
+/// A pair adjustment subtable from the GPOS table, with only the adjustments
+/// of the horizontal advance of the first glyph kept, which is what kerning
+/// is.
+#[derive(Debug, Clone)]
+enum PairAdjustment {
+    /// The adjustments of individual glyph pairs.
+    Pairs(BTreeMap<(u16, u16), i16>),
+    /// The adjustments between classes of glyphs.
+    Classes{
+        coverage: Coverage,
+        first_classes: ClassDef,
+        second_classes: ClassDef,
+        second_class_count: usize,
+        adjustments: Vec<i16>,
+    },
+}
+
+impl PairAdjustment {
+    /// Returns the adjustment of the pair, or None if the subtable doesn't
+    /// cover it.
+    fn get(&self, left: u16, right: u16) -> Option<i16> {
+        match self {
+            Self::Pairs(pairs) => pairs.get(&(left, right)).copied(),
+            Self::Classes{ coverage, first_classes, second_classes, second_class_count, adjustments } => {
+                coverage.index(left)?;
+                let first = first_classes.class(left) as usize;
+                let second = second_classes.class(right) as usize;
+                adjustments.get(first * second_class_count + second).copied()
+            },
+        }
+    }
+}
+
+/// Parses a pair adjustment subtable (lookup type 2).
+fn parse_pair_adjustment(table: &[u8]) -> ParseResult<PairAdjustment> {
+    let mut bytes = table;
+    let header = PairPosHeader::parse_be(&mut bytes)?;
+    let coverage = parse_coverage(offset_slice(table, header.coverage_offset as usize)?)?;
+    match header.pos_format {
+        1 => {
+            let set_count = u16::parse_be(&mut bytes)? as usize;
+            let set_offsets: Vec<u16> = parse_be_array(&mut bytes, set_count)?;
+            let mut pairs = BTreeMap::new();
+            for (left, offset) in coverage.glyphs().zip(set_offsets) {
+                let mut bytes = offset_slice(table, offset as usize)?;
+                let count = u16::parse_be(&mut bytes)?;
+                for _ in 0..count {
+                    let right = u16::parse_be(&mut bytes)?;
+                    let x_advance = parse_x_advance(&mut bytes, header.value_format1)?;
+                    parse_x_advance(&mut bytes, header.value_format2)?;
+                    pairs.insert((left, right), x_advance);
+                }
+            }
+            Ok(PairAdjustment::Pairs(pairs))
+        },
+        2 => {
+            let first_offset = u16::parse_be(&mut bytes)?;
+            let second_offset = u16::parse_be(&mut bytes)?;
+            let first_class_count = u16::parse_be(&mut bytes)? as usize;
+            let second_class_count = u16::parse_be(&mut bytes)? as usize;
+            // Don't trust the counts for the allocation, without any values
+            // the records take no space to check them against, and the
+            // subtable adjusts nothing anyway
+            let record_size = 2 * (header.value_format1.count_ones() + header.value_format2.count_ones()) as usize;
+            let record_count = first_class_count * second_class_count;
+            let fits = record_count.checked_mul(record_size).is_some_and(|size| size <= bytes.len());
+            if record_size == 0 || !fits {
+                return Err(());
+            }
+            let mut adjustments = Vec::with_capacity(record_count);
+            for _ in 0..record_count {
+                adjustments.push(parse_x_advance(&mut bytes, header.value_format1)?);
+                parse_x_advance(&mut bytes, header.value_format2)?;
+            }
+            Ok(PairAdjustment::Classes{
+                coverage,
+                first_classes: parse_class_def(offset_slice(table, first_offset as usize)?)?,
+                second_classes: parse_class_def(offset_slice(table, second_offset as usize)?)?,
+                second_class_count,
+                adjustments,
+            })
+        },
+        _ => Err(()),
+    }
+}
+
+/// Parses the pair adjustments of the 'kern' feature from the GPOS table.
+fn parse_gpos_kerning(table: &[u8]) -> ParseResult<Vec<PairAdjustment>> {
+    let mut result = Vec::new();
+    for (lookup_type, subtable) in feature_subtables(table, &[b"kern"], 9)? {
+        // A malformed subtable is skipped, the rest of the kerning is still usable
+        if lookup_type == 2 {
+            if let Ok(adjustment) = parse_pair_adjustment(subtable) {
+                result.push(adjustment);
+            }
+        }
+    }
+    Ok(result)
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/kern
+parseable_struct!{KernSubtableHeader{
+    version : u16,
+    length  : u16,
+    coverage: u16,
+}}
+
+parseable_struct!{KernPair{
+    left : u16,
+    right: u16,
+    value: i16,
+}}
+
+/// Parses the horizontal kerning pairs from the legacy 'kern' table. Only the
+/// Windows version of the table and its format 0 subtables are supported.
+fn parse_kern(table: &[u8]) -> ParseResult<BTreeMap<(u16, u16), i16>> {
+    const HORIZONTAL: u16 = 0x0001;
+    const MINIMUM: u16 = 0x0002;
+    const CROSS_STREAM: u16 = 0x0004;
+    const OVERRIDE: u16 = 0x0008;
+
+    let mut bytes = table;
+    let version = u16::parse_be(&mut bytes)?;
+    if version != 0 {
+        // Apple's version of the table has a different layout
+        return Err(());
+    }
+    let table_count = u16::parse_be(&mut bytes)?;
+    let mut result = BTreeMap::new();
+    for _ in 0..table_count {
+        let subtable = bytes;
+        let header = KernSubtableHeader::parse_be(&mut &subtable[..])?;
+        // The length overflows for big subtables, but then it's the last one
+        bytes = subtable.get((header.length as usize)..).unwrap_or(&[]);
+        let format = header.coverage >> 8;
+        let flags = header.coverage & 0xff;
+        if format != 0 || flags & (HORIZONTAL | MINIMUM | CROSS_STREAM) != HORIZONTAL {
+            continue;
+        }
+        let mut pairs = offset_slice(subtable, 6)?;
+        let pair_count = u16::parse_be(&mut pairs)? as usize;
+        // Skip the binary search parameters
+        let _search = <[u16; 3]>::parse_be(&mut pairs)?;
+        for pair in parse_be_array::<KernPair>(&mut pairs, pair_count)? {
+            let key = (pair.left, pair.right);
+            if flags & OVERRIDE != 0 {
+                result.insert(key, pair.value);
+            }
+            else {
+                *result.entry(key).or_insert(0) += pair.value;
+            }
+        }
+    }
+    Ok(result)
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/cmap
+parseable_struct!{EncodingRecord{
+    platform_id: u16,
+    encoding_id: u16,
+    offset     : u32,
+}}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#format-4-segment-mapping-to-delta-values
+parseable_struct!{SegmentMappingHeader{
+    format        : u16,
+    length        : u16,
+    language      : u16,
+    seg_count_x2  : u16,
+    search_range  : u16,
+    entry_selector: u16,
+    range_shift   : u16,
+}}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/cmap#format-12-segmented-coverage
+parseable_struct!{SegmentedCoverageHeader{
+    format    : u16,
+    reserved  : u16,
+    length    : u32,
+    language  : u32,
+    num_groups: u32,
+}}
+
+parseable_struct!{SequentialMapGroup{
+    start_char_code: u32,
+    end_char_code  : u32,
+    start_glyph_id : u32,
+}}
+
+/// Parses a format 4 character to glyph mapping subtable.
+fn parse_cmap_format4(table: &[u8], result: &mut BTreeMap<u32, u16>) -> ParseResult<()> {
+    let mut bytes = table;
+    let header = SegmentMappingHeader::parse_be(&mut bytes)?;
+    let seg_count = (header.seg_count_x2 / 2) as usize;
+    let end_codes: Vec<u16> = parse_be_array(&mut bytes, seg_count)?;
+    let _reserved_pad = u16::parse_be(&mut bytes)?;
+    let start_codes: Vec<u16> = parse_be_array(&mut bytes, seg_count)?;
+    let id_deltas: Vec<u16> = parse_be_array(&mut bytes, seg_count)?;
+    // The range offsets are relative to their own position
+    let range_offsets_start = table.len() - bytes.len();
+    let range_offsets: Vec<u16> = parse_be_array(&mut bytes, seg_count)?;
+    for i in 0..seg_count {
+        let (start, end) = (start_codes[i], end_codes[i]);
+        if start == 0xffff || start > end {
+            continue;
+        }
+        for c in start..=end {
+            let glyph = if range_offsets[i] == 0 {
+                c.wrapping_add(id_deltas[i])
+            }
+            else {
+                let offs = range_offsets_start + i * 2 + range_offsets[i] as usize + (c - start) as usize * 2;
+                let glyph = u16::parse_be(&mut offset_slice(table, offs)?)?;
+                if glyph == 0 { 0 } else { glyph.wrapping_add(id_deltas[i]) }
+            };
+            if glyph != 0 {
+                result.insert(c as u32, glyph);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parses a format 12 character to glyph mapping subtable. The groups are
+/// kept as they are, a single one can cover the whole Unicode range.
+fn parse_cmap_format12(table: &[u8]) -> ParseResult<Vec<SequentialMapGroup>> {
+    let mut bytes = table;
+    let header = SegmentedCoverageHeader::parse_be(&mut bytes)?;
+    let mut groups: Vec<SequentialMapGroup> = parse_be_array(&mut bytes, header.num_groups as usize)?;
+    for group in &groups {
+        if group.start_char_code > group.end_char_code || group.end_char_code > char::MAX as u32 {
+            return Err(());
+        }
+    }
+    // The spec requires the order, but the lookup relies on it
+    groups.sort_by_key(|group| group.start_char_code);
+    Ok(groups)
+}
+
+/// The Unicode character to glyph mapping of a font.
+#[derive(Debug, Clone)]
+enum CharacterMap {
+    /// The glyphs of the individual characters (format 4).
+    Segments(BTreeMap<u32, u16>),
+    /// Ranges of characters mapped to consecutive glyphs, sorted by their
+    /// first character (format 12).
+    Groups(Vec<SequentialMapGroup>),
+}
+
+impl Default for CharacterMap {
+    fn default() -> Self {
+        Self::Segments(BTreeMap::new())
+    }
+}
+
+impl CharacterMap {
+    /// Returns the index of the glyph of the given character, if it's mapped.
+    fn get(&self, c: u32) -> Option<u16> {
+        match self {
+            Self::Segments(glyphs) => glyphs.get(&c).copied(),
+            Self::Groups(groups) => {
+                let index = groups.binary_search_by(|group| {
+                    if group.end_char_code < c {
+                        Ordering::Less
+                    }
+                    else if group.start_char_code > c {
+                        Ordering::Greater
+                    }
+                    else {
+                        Ordering::Equal
+                    }
+                }).ok()?;
+                let group = &groups[index];
+                let glyph = group.start_glyph_id.checked_add(c - group.start_char_code)?;
+                if glyph != 0 && glyph <= u16::MAX as u32 { Some(glyph as u16) } else { None }
+            },
+        }
+    }
+}
+
+/// Parses the Unicode character to glyph mapping from the 'cmap' table. The
+/// full repertoire (format 12) is preferred over the BMP-only one (format 4).
+fn parse_cmap(table: &[u8]) -> ParseResult<CharacterMap> {
+    let mut bytes = table;
+    let _version = u16::parse_be(&mut bytes)?;
+    let num_tables = u16::parse_be(&mut bytes)? as usize;
+    let records: Vec<EncodingRecord> = parse_be_array(&mut bytes, num_tables)?;
+    let mut best: Option<(u16, &[u8])> = None;
+    for record in &records {
+        // Only the Unicode encodings
+        let unicode = matches!((record.platform_id, record.encoding_id), (0, _) | (3, 1) | (3, 10));
+        if !unicode {
+            continue;
+        }
+        let subtable = offset_slice(table, record.offset as usize)?;
+        let format = u16::parse_be(&mut &subtable[..])?;
+        let better = matches!((format, best), (12, Some((4, _))) | (4, None) | (12, None));
+        if better {
+            best = Some((format, subtable));
+        }
+    }
+    match best {
+        Some((4, subtable)) => {
+            let mut result = BTreeMap::new();
+            parse_cmap_format4(subtable, &mut result)?;
+            Ok(CharacterMap::Segments(result))
+        },
+        Some((12, subtable)) => Ok(CharacterMap::Groups(parse_cmap_format12(subtable)?)),
+        _ => Err(()),
+    }
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/hhea
+parseable_struct!{HheaTable{
+    version               : Fixed   ,
+    ascender              : FWord   ,
+    descender             : FWord   ,
+    line_gap              : FWord   ,
+    advance_width_max     : u16     ,
+    min_left_side_bearing : FWord   ,
+    min_right_side_bearing: FWord   ,
+    x_max_extent          : FWord   ,
+    caret_slope_rise      : i16     ,
+    caret_slope_run       : i16     ,
+    caret_offset          : i16     ,
+    reserved              : [i16; 4],
+    metric_data_format    : i16     ,
+    number_of_h_metrics   : u16     ,
+}}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/hmtx
+parseable_struct!{LongHorMetric{
+    advance_width    : u16,
+    left_side_bearing: i16,
+}}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/post
+parseable_struct!{PostHeader{
+    version            : Fixed,
+    italic_angle       : Fixed,
+    underline_position : FWord,
+    underline_thickness: FWord,
+    is_fixed_pitch     : u32  ,
+}}
+
+/// Parses the advance widths of the glyphs with long metrics from the 'hhea'
+/// and 'hmtx' tables.
+fn parse_horizontal_advances(hhea: &[u8], hmtx: &[u8]) -> ParseResult<Vec<u16>> {
+    let header = HheaTable::parse_be(&mut &hhea[..])?;
+    let metrics: Vec<LongHorMetric> = parse_be_array(&mut &hmtx[..], header.number_of_h_metrics as usize)?;
+    if metrics.is_empty() {
+        return Err(());
+    }
+    Ok(metrics.into_iter().map(|m| m.advance_width).collect())
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/vhea
+parseable_struct!{VheaTable{
+    version                : Fixed   ,
+    vert_typo_ascender     : FWord   ,
+    vert_typo_descender    : FWord   ,
+    vert_typo_line_gap     : FWord   ,
+    advance_height_max     : u16     ,
+    min_top_side_bearing   : FWord   ,
+    min_bottom_side_bearing: FWord   ,
+    y_max_extent           : FWord   ,
+    caret_slope_rise       : i16     ,
+    caret_slope_run        : i16     ,
+    caret_offset           : i16     ,
+    reserved               : [i16; 4],
+    metric_data_format     : i16     ,
+    num_of_long_ver_metrics: u16     ,
+}}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/vmtx
+parseable_struct!{LongVerMetric{
+    advance_height  : u16,
+    top_side_bearing: i16,
+}}
+
+/// Vertical metrics from the 'vhea' and 'vmtx' tables.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct VerticalMetrics {
+    header: VheaTable,
+    /// The advance heights of the glyphs with long metrics.
+    advances: Vec<u16>,
+}
+
+/// Parses the 'vhea' table and the long metrics of the 'vmtx' table.
+fn parse_vertical_metrics(vhea: &[u8], vmtx: &[u8]) -> ParseResult<VerticalMetrics> {
+    let header = VheaTable::parse_be(&mut &vhea[..])?;
+    let metrics: Vec<LongVerMetric> = parse_be_array(&mut &vmtx[..], header.num_of_long_ver_metrics as usize)?;
+    if metrics.is_empty() {
+        return Err(());
+    }
+    Ok(VerticalMetrics{
+        header,
+        advances: metrics.into_iter().map(|m| m.advance_height).collect(),
+    })
+}
+
+// https://docs.microsoft.com/en-us/typography/opentype/spec/glyf
+parseable_struct!{GlyphHeader{
+    number_of_contours: i16  ,
+    x_min             : FWord,
+    y_min             : FWord,
+    x_max             : FWord,
+    y_max             : FWord,
+}}
+
+// Flags of the points of simple glyphs
+const ON_CURVE_POINT                      : u8 = 0x01;
+const X_SHORT_VECTOR                      : u8 = 0x02;
+const Y_SHORT_VECTOR                      : u8 = 0x04;
+const REPEAT_FLAG                         : u8 = 0x08;
+const X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR: u8 = 0x20;
+
+// Flags of the components of composite glyphs
+const ARG_1_AND_2_ARE_WORDS   : u16 = 0x0001;
+const ARGS_ARE_XY_VALUES      : u16 = 0x0002;
+const WE_HAVE_A_SCALE         : u16 = 0x0008;
+const MORE_COMPONENTS         : u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO    : u16 = 0x0080;
+
+/// Composite glyphs nested deeper than this are considered malformed. This
+/// also stops components that refer back to their own glyph.
+const MAX_COMPONENT_DEPTH: usize = 16;
+/// Composite glyphs with more components than this, counting the nested ones
+/// too, are considered malformed. A few bytes of components reusing each
+/// other could expand exponentially otherwise.
+const MAX_COMPONENTS: usize = 1024;
+
+/// A point of a glyph outline in font design units, with the y axis pointing
+/// up.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct OutlinePoint {
+    pub(crate) x       : f32 ,
+    pub(crate) y       : f32 ,
+    pub(crate) on_curve: bool,
+}
+
+/// Parses a 2.14 fixed-point number.
+fn parse_f2dot14(input: &mut &[u8]) -> ParseResult<f32> {
+    Ok(i16::parse_be(input)? as f32 / 16384.0)
+}
+
+/// Parses the coordinates of one axis of a simple glyph. Each coordinate is
+/// stored relative to the previous one, in a size given by the point flags.
+fn parse_coordinates(input: &mut &[u8], flags: &[u8], short: u8, same_or_positive: u8) -> ParseResult<Vec<i32>> {
+    let mut value = 0i32;
+    let mut result = Vec::with_capacity(flags.len());
+    for flag in flags {
+        if flag & short != 0 {
+            let delta = u8::parse_be(input)? as i32;
+            value += if flag & same_or_positive != 0 { delta } else { -delta };
+        }
+        else if flag & same_or_positive == 0 {
+            value += i16::parse_be(input)? as i32;
+        }
+        result.push(value);
+    }
+    Ok(result)
+}
+
+/// Parses the contours of a simple glyph, following the glyph header.
+fn parse_simple_glyph(mut data: &[u8], contour_count: usize) -> ParseResult<Vec<Vec<OutlinePoint>>> {
+    let end_points: Vec<u16> = parse_be_array(&mut data, contour_count)?;
+    let point_count = match end_points.last() {
+        Some(last) => *last as usize + 1,
+        None => return Ok(Vec::new()),
+    };
+    // Skip the hinting instructions
+    let instruction_length = u16::parse_be(&mut data)? as usize;
+    data = offset_slice(data, instruction_length)?;
+    // Read the flags, a flag can be repeated for the following points
+    let mut flags = Vec::with_capacity(point_count);
+    while flags.len() < point_count {
+        let flag = u8::parse_be(&mut data)?;
+        let repeat = if flag & REPEAT_FLAG != 0 { u8::parse_be(&mut data)? as usize } else { 0 };
+        for _ in 0..=repeat {
+            flags.push(flag);
+        }
+    }
+    flags.truncate(point_count);
+    let xs = parse_coordinates(&mut data, &flags, X_SHORT_VECTOR, X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR)?;
+    let ys = parse_coordinates(&mut data, &flags, Y_SHORT_VECTOR, Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR)?;
+    // Split the points into contours
+    let mut contours = Vec::with_capacity(contour_count);
+    let mut start = 0;
+    for end in end_points {
+        // The end points must be increasing, the last one gave the count
+        let end = end as usize + 1;
+        if end <= start || end > point_count {
+            return Err(());
+        }
+        contours.push((start..end)
+            .map(|i| OutlinePoint{
+                x: xs[i] as f32,
+                y: ys[i] as f32,
+                on_curve: flags[i] & ON_CURVE_POINT != 0,
+            })
+            .collect());
+        start = end;
+    }
+    Ok(contours)
+}
+
+/// A strike of the 'sbix' table, the color bitmaps of the glyphs made for a
+/// given size.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct BitmapStrike {
+    /// The size the bitmaps were made for, in pixels per em.
+    pub(crate) ppem: u16,
+    // The offset of the data of each glyph in the table, with an extra offset
+    // at the end, so the data of a glyph ends where the next one starts
+    #[cfg(feature = "image")]
+    offsets: Vec<usize>,
+}
+
+/// A color bitmap of a glyph from the 'sbix' table.
+#[cfg(feature = "image")]
+pub(crate) struct ColorBitmap<'a> {
+    /// The position of the bottom-left corner of the image relative to the
+    /// origin of the glyph, in pixels of the strike, growing upwards.
+    pub(crate) origin: (i16, i16),
+    /// The format of the image, like 'png '.
+    pub(crate) format: [u8; 4],
+    /// The encoded image itself.
+    pub(crate) data: &'a [u8],
+}
+
+/// Parses the strikes of the 'sbix' table. The offsets of the bitmaps are
+/// only needed to decode them, with the image feature.
+#[cfg_attr(not(feature = "image"), allow(unused_variables))]
+fn parse_sbix(table: &[u8], num_glyphs: u16) -> ParseResult<Vec<BitmapStrike>> {
+    let mut bytes = table;
+    let _version = u16::parse_be(&mut bytes)?;
+    let _flags = u16::parse_be(&mut bytes)?;
+    let num_strikes = u32::parse_be(&mut bytes)? as usize;
+    // Don't trust the count for the allocation
+    if bytes.len() / 4 < num_strikes {
+        return Err(());
+    }
+    let strike_offsets: Vec<u32> = parse_be_array(&mut bytes, num_strikes)?;
+    strike_offsets.into_iter()
+        .map(|offs| {
+            let start = offs as usize;
+            let mut strike = offset_slice(table, start)?;
+            let ppem = u16::parse_be(&mut strike)?;
+            let _ppi = u16::parse_be(&mut strike)?;
+            #[cfg(feature = "image")]
+            let offsets = parse_be_array::<u32>(&mut strike, num_glyphs as usize + 1)?
+                .into_iter()
+                .map(|o| start + o as usize)
+                .collect();
+            Ok(BitmapStrike{
+                ppem,
+                #[cfg(feature = "image")]
+                offsets,
+            })
+        })
+        .collect()
+}
+
+/// Parses the size ranges of the 'gasp' table, as pairs of the largest size
+/// in pixels per em and the rendering behavior flags up to that size.
+fn parse_gasp(table: &[u8]) -> ParseResult<Vec<(u16, u16)>> {
+    let mut bytes = table;
+    let _version = u16::parse_be(&mut bytes)?;
+    let num_ranges = u16::parse_be(&mut bytes)? as usize;
+    let mut ranges = Vec::with_capacity(num_ranges);
+    for _ in 0..num_ranges {
+        let max_ppem = u16::parse_be(&mut bytes)?;
+        let behavior = u16::parse_be(&mut bytes)?;
+        ranges.push((max_ppem, behavior));
+    }
+    Ok(ranges)
+}
+
+/// A design axis of a variable font from the 'fvar' table.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct FvarAxis {
+    pub(crate) tag    : [u8; 4],
+    pub(crate) min    : f32    ,
+    pub(crate) default: f32    ,
+    pub(crate) max    : f32    ,
+    pub(crate) flags  : u16    ,
+    pub(crate) name_id: u16    ,
+    /// The mapping of the normalized coordinates from the 'avar' table, as
+    /// pairs of the coordinate and the one it's mapped to. Empty if the axis
+    /// isn't remapped.
+    pub(crate) mapping: Vec<(f32, f32)>,
+}
+
+/// A named instance of a variable font from the 'fvar' table, a point in the
+/// design space with a name, like "Semibold".
+#[derive(Debug, Default, Clone)]
+pub(crate) struct FvarInstance {
+    pub(crate) name_id    : u16     ,
+    /// The coordinate on each axis, in the order of the axes.
+    pub(crate) coordinates: Vec<f32>,
+}
+
+/// Parses a 16.16 fixed-point number.
+fn parse_fixed(input: &mut &[u8]) -> ParseResult<f32> {
+    Ok(i32::parse_be(input)? as f32 / 65536.0)
+}
+
+/// Parses the axes and the named instances of the 'fvar' table.
+fn parse_fvar(table: &[u8]) -> ParseResult<(Vec<FvarAxis>, Vec<FvarInstance>)> {
+    let mut bytes = table;
+    let _major_version = u16::parse_be(&mut bytes)?;
+    let _minor_version = u16::parse_be(&mut bytes)?;
+    let axes_offset = u16::parse_be(&mut bytes)? as usize;
+    let _reserved = u16::parse_be(&mut bytes)?;
+    let axis_count = u16::parse_be(&mut bytes)? as usize;
+    let axis_size = u16::parse_be(&mut bytes)? as usize;
+    let instance_count = u16::parse_be(&mut bytes)? as usize;
+    let instance_size = u16::parse_be(&mut bytes)? as usize;
+    // The records can grow in later versions, so they are read with the sizes
+    // given in the header
+    if axis_size < 20 || instance_size < 4 + axis_count * 4 {
+        return Err(());
+    }
+    let axes = (0..axis_count)
+        .map(|i| {
+            let mut record = offset_slice(table, axes_offset + i * axis_size)?;
+            Ok(FvarAxis{
+                tag: <[u8; 4]>::parse_be(&mut record)?,
+                min: parse_fixed(&mut record)?,
+                default: parse_fixed(&mut record)?,
+                max: parse_fixed(&mut record)?,
+                flags: u16::parse_be(&mut record)?,
+                name_id: u16::parse_be(&mut record)?,
+                mapping: Vec::new(),
+            })
+        })
+        .collect::<ParseResult<Vec<_>>>()?;
+    // The instances follow the axes
+    let instances_offset = axes_offset + axis_count * axis_size;
+    let instances = (0..instance_count)
+        .map(|i| {
+            let mut record = offset_slice(table, instances_offset + i * instance_size)?;
+            let name_id = u16::parse_be(&mut record)?;
+            let _flags = u16::parse_be(&mut record)?;
+            let coordinates = (0..axis_count)
+                .map(|_| parse_fixed(&mut record))
+                .collect::<ParseResult<Vec<_>>>()?;
+            Ok(FvarInstance{ name_id, coordinates })
+        })
+        .collect::<ParseResult<Vec<_>>>()?;
+    Ok((axes, instances))
+}
+
+/// Parses the segment maps of the 'avar' table, one for each axis of the
+/// 'fvar' table.
+fn parse_avar(table: &[u8]) -> ParseResult<Vec<Vec<(f32, f32)>>> {
+    let mut bytes = table;
+    let _major_version = u16::parse_be(&mut bytes)?;
+    let _minor_version = u16::parse_be(&mut bytes)?;
+    let _reserved = u16::parse_be(&mut bytes)?;
+    let axis_count = u16::parse_be(&mut bytes)?;
+    (0..axis_count)
+        .map(|_| {
+            let count = u16::parse_be(&mut bytes)?;
+            (0..count)
+                .map(|_| Ok((parse_f2dot14(&mut bytes)?, parse_f2dot14(&mut bytes)?)))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(feature = "image")]
+impl BitmapStrike {
+    /// Returns the bitmap of the given glyph from the 'sbix' table, following
+    /// the glyphs that reuse the bitmap of another one.
+    fn bitmap<'a>(&self, sbix: &'a [u8], glyph: u16, depth: usize) -> Option<ColorBitmap<'a>> {
+        let start = *self.offsets.get(glyph as usize)?;
+        let end = *self.offsets.get(glyph as usize + 1)?;
+        let mut data = sbix.get(start..end)?;
+        let origin_x = i16::parse_be(&mut data).ok()?;
+        let origin_y = i16::parse_be(&mut data).ok()?;
+        let format = <[u8; 4]>::parse_be(&mut data).ok()?;
+        if &format == b"dupe" {
+            // Don't follow cycles
+            if depth > 0 {
+                return None;
+            }
+            let original = u16::parse_be(&mut data).ok()?;
+            return self.bitmap(sbix, original, depth + 1);
+        }
+        Some(ColorBitmap{
+            origin: (origin_x, origin_y),
+            format,
+            data,
+        })
+    }
+}
+
+/// The glyph offsets of the 'loca' table, the 'glyf' table they point into is
+/// read from the font data.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct GlyphData {
+    /// The offset of each glyph in the 'glyf' table, with an extra offset at
+    /// the end, so the data of a glyph ends where the next one starts.
+    offsets: Vec<u32>,
+}
+
+/// Parses the 'loca' table. It has an offset for each glyph, and an extra one
+/// at the end.
+fn parse_glyph_data(loca: &[u8], format: LocaFormat, num_glyphs: u16) -> ParseResult<GlyphData> {
+    let count = num_glyphs as usize + 1;
+    let offsets = match format {
+        LocaFormat::Short => parse_be_array::<u16>(&mut &loca[..], count)?
+            .into_iter()
+            .map(|o| o as u32 * 2)
+            .collect(),
+        LocaFormat::Long => parse_be_array::<u32>(&mut &loca[..], count)?,
+    };
+    Ok(GlyphData{ offsets })
+}
+
+impl GlyphData {
+    /// Returns the byte range of the data of the given glyph in the 'glyf'
+    /// table, or None if the glyph doesn't exist or the range is invalid.
+    fn range(&self, glyf: &[u8], glyph: u16) -> Option<Range<usize>> {
+        let start = *self.offsets.get(glyph as usize)? as usize;
+        let end = *self.offsets.get(glyph as usize + 1)? as usize;
+        if start > end || end > glyf.len() {
+            return None;
+        }
+        Some(start..end)
+    }
+
+    /// Returns the contours of the given glyph from the 'glyf' table.
+    /// Components of composite glyphs are transformed and merged into a single
+    /// set of contours.
+    fn outline(&self, glyf: &[u8], glyph: u16, depth: usize, components: &mut usize)
+        -> ParseResult<Vec<Vec<OutlinePoint>>> {
+        if depth > MAX_COMPONENT_DEPTH {
+            return Err(());
+        }
+        let range = self.range(glyf, glyph).ok_or(())?;
+        if range.is_empty() {
+            // Glyphs without an outline, like the space, have no data
+            return Ok(Vec::new());
+        }
+        let mut data = &glyf[range];
+        let header = GlyphHeader::parse_be(&mut data)?;
+        if header.number_of_contours >= 0 {
+            parse_simple_glyph(data, header.number_of_contours as usize)
+        }
+        else {
+            self.composite_outline(glyf, data, depth, components)
+        }
+    }
+
+    /// Returns the merged contours of the components of a composite glyph,
+    /// following the glyph header. `components` counts the components merged
+    /// so far, including the nested ones.
+    fn composite_outline(&self, glyf: &[u8], mut data: &[u8], depth: usize, components: &mut usize)
+        -> ParseResult<Vec<Vec<OutlinePoint>>> {
+        let mut contours: Vec<Vec<OutlinePoint>> = Vec::new();
+        loop {
+            *components += 1;
+            if *components > MAX_COMPONENTS {
+                return Err(());
+            }
+            let flags = u16::parse_be(&mut data)?;
+            let glyph = u16::parse_be(&mut data)?;
+            // The arguments are either an offset or a pair of point numbers
+            let words = flags & ARG_1_AND_2_ARE_WORDS != 0;
+            let offset = flags & ARGS_ARE_XY_VALUES != 0;
+            let (arg1, arg2) = match (words, offset) {
+                (true, true) => (i16::parse_be(&mut data)? as i32, i16::parse_be(&mut data)? as i32),
+                (true, false) => (u16::parse_be(&mut data)? as i32, u16::parse_be(&mut data)? as i32),
+                (false, true) => (i8::parse_be(&mut data)? as i32, i8::parse_be(&mut data)? as i32),
+                (false, false) => (u8::parse_be(&mut data)? as i32, u8::parse_be(&mut data)? as i32),
+            };
+            // The 2x2 transformation matrix, in column-major order
+            let matrix = if flags & WE_HAVE_A_SCALE != 0 {
+                let scale = parse_f2dot14(&mut data)?;
+                [scale, 0.0, 0.0, scale]
+            }
+            else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                let x_scale = parse_f2dot14(&mut data)?;
+                let y_scale = parse_f2dot14(&mut data)?;
+                [x_scale, 0.0, 0.0, y_scale]
+            }
+            else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                let mut matrix = [0.0; 4];
+                for m in matrix.iter_mut() {
+                    *m = parse_f2dot14(&mut data)?;
+                }
+                matrix
+            }
+            else {
+                [1.0, 0.0, 0.0, 1.0]
+            };
+            let mut component = self.outline(glyf, glyph, depth + 1, components)?;
+            for p in component.iter_mut().flatten() {
+                let (x, y) = (p.x, p.y);
+                p.x = matrix[0] * x + matrix[2] * y;
+                p.y = matrix[1] * x + matrix[3] * y;
+            }
+            let (dx, dy) = if offset {
+                (arg1 as f32, arg2 as f32)
+            }
+            else {
+                // Move the component so its point lands on the point of the
+                // glyph assembled so far
+                let target = contours.iter().flatten().nth(arg1 as usize).ok_or(())?;
+                let source = component.iter().flatten().nth(arg2 as usize).ok_or(())?;
+                (target.x - source.x, target.y - source.y)
+            };
+            for p in component.iter_mut().flatten() {
+                p.x += dx;
+                p.y += dy;
+            }
+            contours.extend(component);
+            if flags & MORE_COMPONENTS == 0 {
+                break;
+            }
+        }
+        Ok(contours)
+    }
+}
+
+/// Returns the bytes of the table described by the directory entry.
+fn table_bytes<'a>(input: &'a [u8], entry: &TableDirectoryEntry) -> Option<&'a [u8]> {
+    let start = entry.offset as usize;
+    let end = start.checked_add(entry.length as usize)?;
+    input.get(start..end)
+}
+
+/// Computes the checksum of a table, the sum of its big-endian 32-bit words,
+/// with the last word padded by zeroes.
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
 /// A type that represents a parsed TTF file.
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
@@ -96,7 +1403,29 @@ pub(crate) struct TtfFile {
     offset: OffsetSubtable,
     head: HeadTable,
     name: NameTable,
-    names: HashMap<u16, HashSet<String>>,
+    names: BTreeMap<u16, BTreeSet<String>>,
+    english_names: BTreeMap<u16, String>,
+    #[cfg(all(feature = "std", target_os = "windows"))]
+    ligatures: BTreeMap<u16, Vec<Ligature>>,
+    #[cfg(all(feature = "std", target_os = "windows"))]
+    mark_to_base: Vec<MarkToBase>,
+    pair_adjustments: Vec<PairAdjustment>,
+    kerning: BTreeMap<(u16, u16), i16>,
+    vertical: Option<VerticalMetrics>,
+    glyph_indices: CharacterMap,
+    advances: Vec<u16>,
+    fixed_pitch: bool,
+    fs_selection: Option<u16>,
+    glyph_data: Option<GlyphData>,
+    color_bitmaps: Vec<BitmapStrike>,
+    gasp: Vec<(u16, u16)>,
+    variation_axes: Vec<FvarAxis>,
+    named_instances: Vec<FvarInstance>,
+    num_glyphs: u16,
+    // The byte range of every table in the file, so any of them can be read
+    // without keeping copies of them
+    tables: BTreeMap<String, Range<usize>>,
+    data: Vec<u8>,
 }
 
 impl TtfFile {
@@ -105,10 +1434,265 @@ impl TtfFile {
         Self::parse_be(&mut input)
     }
 
+    /// Verifies the checksum of every table in the bytes, and the checksum of
+    /// the whole file stored in the 'head' table. Returns the reason on a
+    /// mismatch.
+    pub(crate) fn verify_checksums(input: &[u8]) -> Result<(), String> {
+        let mut bytes = input;
+        let offset = OffsetSubtable::parse_be(&mut bytes)
+            .map_err(|_| String::from("Missing table directory!"))?;
+        let entries: Vec<TableDirectoryEntry> = parse_be_array(&mut bytes, offset.num_tables as usize)
+            .map_err(|_| String::from("Truncated table directory!"))?;
+        let mut adjustment = None;
+        for e in &entries {
+            let tag = String::from_utf8_lossy(&e.tag).into_owned();
+            let table = table_bytes(input, e)
+                .ok_or_else(|| format!("Table '{}' is out of bounds!", tag))?;
+            let mut sum = checksum(table);
+            if &e.tag == b"head" {
+                // The checksum is calculated as if the adjustment was 0
+                let head = HeadTable::parse_be(&mut &table[..])
+                    .map_err(|_| String::from("Truncated 'head' table!"))?;
+                if e.offset % 4 != 0 {
+                    return Err("The 'head' table is misaligned!".into());
+                }
+                sum = sum.wrapping_sub(head.checksum_adjustment);
+                adjustment = Some(head.checksum_adjustment);
+            }
+            if sum != e.checksum {
+                return Err(format!("Checksum mismatch in table '{}'!", tag));
+            }
+        }
+        let adjustment = adjustment.ok_or_else(|| String::from("Missing 'head' table!"))?;
+        let sum = checksum(input).wrapping_sub(adjustment);
+        if CHECKSUM_MAGIC.wrapping_sub(sum) != adjustment {
+            return Err("Checksum mismatch in the font file!".into());
+        }
+        Ok(())
+    }
+
     /// Returns the entries with the given NameID from the 'name' table.
-    pub(crate) fn name(&self, id: u16) -> Option<&HashSet<String>> {
+    pub(crate) fn name(&self, id: u16) -> Option<&BTreeSet<String>> {
         self.names.get(&id)
     }
+
+    /// Returns the entry with the given NameID from the 'name' table to
+    /// display, the US English one if there is one.
+    pub(crate) fn preferred_name(&self, id: u16) -> Option<&str> {
+        self.english_names.get(&id)
+            .or_else(|| self.names.get(&id)?.iter().next())
+            .map(|n| n.as_str())
+    }
+
+    /// Returns the index of the glyph representing the given character from the
+    /// 'cmap' table.
+    pub(crate) fn glyph_index(&self, c: char) -> Option<u16> {
+        self.glyph_indices.get(c as u32)
+    }
+
+    /// Returns the first ligature from the 'GSUB' table that substitutes the
+    /// beginning of the given glyph sequence.
+    #[cfg(all(feature = "std", target_os = "windows"))]
+    pub(crate) fn ligature(&self, glyphs: &[u16]) -> Option<&Ligature> {
+        let (first, rest) = glyphs.split_first()?;
+        self.ligatures.get(first)?
+            .iter()
+            .find(|l| rest.starts_with(&l.components))
+    }
+
+    /// Returns the number of glyphs in the font from the 'maxp' table.
+    pub(crate) fn glyph_count(&self) -> u16 {
+        self.num_glyphs
+    }
+
+    /// Returns the number of font design units in an em.
+    pub(crate) fn units_per_em(&self) -> u16 {
+        self.head.units_per_em
+    }
+
+    /// Returns the offset of a mark glyph relative to the base glyph it's
+    /// attached to, from the 'GPOS' table. The offset is in font design units,
+    /// with the y axis pointing up.
+    #[cfg(all(feature = "std", target_os = "windows"))]
+    pub(crate) fn mark_to_base(&self, base: u16, mark: u16) -> Option<(i32, i32)> {
+        self.mark_to_base.iter().find_map(|subtable| {
+            let (class, (mx, my)) = *subtable.marks.get(&mark)?;
+            let (bx, by) = (*subtable.bases.get(&base)?.get(class as usize)?)?;
+            Some((bx as i32 - mx as i32, by as i32 - my as i32))
+        })
+    }
+
+    /// Returns true if the font attaches marks to base glyphs in the 'GPOS'
+    /// table.
+    #[cfg(all(feature = "std", target_os = "windows"))]
+    pub(crate) fn has_mark_to_base(&self) -> bool {
+        !self.mark_to_base.is_empty()
+    }
+
+    /// Returns the kerning between two glyphs in font design units, from the
+    /// pair adjustments of the 'GPOS' table, or the legacy 'kern' table if
+    /// those don't cover the pair.
+    pub(crate) fn kerning(&self, left: u16, right: u16) -> i16 {
+        self.pair_adjustment(left, right)
+            .or_else(|| self.kerning.get(&(left, right)).copied())
+            .unwrap_or(0)
+    }
+
+    /// Returns the kerning between two glyphs from the 'GPOS' table only, in
+    /// font design units.
+    pub(crate) fn pair_adjustment(&self, left: u16, right: u16) -> Option<i16> {
+        // The first subtable covering the pair applies
+        self.pair_adjustments.iter().find_map(|subtable| subtable.get(left, right))
+    }
+
+    /// Returns true if the font has a legacy 'kern' table with horizontal
+    /// kerning pairs.
+    #[cfg(all(feature = "std", target_os = "windows"))]
+    pub(crate) fn has_kern_table(&self) -> bool {
+        !self.kerning.is_empty()
+    }
+
+    /// Returns the horizontal advance of the given glyph from the 'hmtx' table
+    /// in font design units.
+    pub(crate) fn horizontal_advance(&self, glyph: u16) -> Option<u16> {
+        // The last advance applies to all the glyphs after it
+        self.advances.get(glyph as usize).or_else(|| self.advances.last()).copied()
+    }
+
+    /// Returns the largest horizontal advance from the 'hmtx' table in font
+    /// design units.
+    pub(crate) fn max_horizontal_advance(&self) -> u16 {
+        self.advances.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Returns the bounding box of all glyphs from the 'head' table in font
+    /// design units, as the minimum and maximum x and y.
+    pub(crate) fn bounding_box(&self) -> (i16, i16, i16, i16) {
+        let head = &self.head;
+        (head.x_min, head.y_min, head.x_max, head.y_max)
+    }
+
+    /// Returns the style flags of the 'head' table.
+    pub(crate) fn mac_style(&self) -> u16 {
+        self.head.mac_style
+    }
+
+    /// Returns the style flags of the 'OS/2' table, if the font has one.
+    pub(crate) fn fs_selection(&self) -> Option<u16> {
+        self.fs_selection
+    }
+
+    /// Returns true if every glyph of the font has the same advance width. This
+    /// is either flagged in the 'post' table, or seen from the advances, where
+    /// zero-width glyphs (like combining marks) are ignored.
+    pub(crate) fn is_fixed_pitch(&self) -> bool {
+        if self.fixed_pitch {
+            return true;
+        }
+        let mut widths = self.advances.iter().filter(|a| **a != 0);
+        match widths.next() {
+            Some(first) => widths.all(|a| a == first),
+            None => false,
+        }
+    }
+
+    /// Returns the vertical advance of the given glyph from the 'vmtx' table in
+    /// font design units, or None if the font has no vertical metrics.
+    pub(crate) fn vertical_advance(&self, glyph: u16) -> Option<u16> {
+        let advances = &self.vertical.as_ref()?.advances;
+        // The last advance applies to all the glyphs after it
+        advances.get(glyph as usize).or_else(|| advances.last()).copied()
+    }
+
+    /// Returns the distance between columns of vertical text in font design
+    /// units, or None if the font has no vertical metrics.
+    pub(crate) fn vertical_line_advance(&self) -> Option<i32> {
+        let header = &self.vertical.as_ref()?.header;
+        Some(header.vert_typo_ascender as i32 - header.vert_typo_descender as i32
+            + header.vert_typo_line_gap as i32)
+    }
+
+    /// Returns true if the font has TrueType outlines in the 'glyf' table.
+    pub(crate) fn has_glyph_outlines(&self) -> bool {
+        self.glyph_data.is_some()
+    }
+
+    /// Returns the byte range of the data of the given glyph in the 'glyf'
+    /// table, as the 'loca' table describes it.
+    pub(crate) fn glyph_data_range(&self, glyph: u16) -> Option<Range<usize>> {
+        self.glyph_data.as_ref()?.range(self.raw_table("glyf")?, glyph)
+    }
+
+    /// Returns the bytes of the table with the given tag, if the font has it.
+    /// Tags are always 4 characters, including trailing spaces like 'cvt '.
+    pub(crate) fn raw_table(&self, tag: &str) -> Option<&[u8]> {
+        let range = self.tables.get(tag)?;
+        self.data.get(range.clone())
+    }
+
+    /// Returns the sizes of the color bitmaps in pixels per em, in the order
+    /// they are stored in the 'sbix' table.
+    pub(crate) fn color_bitmap_sizes(&self) -> Vec<u16> {
+        self.color_bitmaps.iter().map(|s| s.ppem).collect()
+    }
+
+    /// Returns the color bitmap of the glyph best suited for the given size,
+    /// along with the size it was made for. That's the smallest one not
+    /// smaller than the requested size, or the biggest one if there's none.
+    #[cfg(feature = "image")]
+    pub(crate) fn color_bitmap(&self, glyph: u16, ppem: f64) -> Option<(u16, ColorBitmap<'_>)> {
+        let sbix = self.raw_table("sbix")?;
+        let mut bitmaps: Vec<_> = self.color_bitmaps.iter()
+            .filter_map(|s| s.bitmap(sbix, glyph, 0).map(|b| (s.ppem, b)))
+            .collect();
+        bitmaps.sort_by_key(|(size, _)| *size);
+        let best = bitmaps.iter().position(|(size, _)| *size as f64 >= ppem)
+            .unwrap_or(bitmaps.len().checked_sub(1)?);
+        Some(bitmaps.swap_remove(best))
+    }
+
+    /// Returns the size ranges of the 'gasp' table, as pairs of the largest
+    /// size in pixels per em and the rendering behavior flags up to that size.
+    pub(crate) fn gasp_ranges(&self) -> &[(u16, u16)] {
+        &self.gasp
+    }
+
+    /// Returns the design axes of a variable font from the 'fvar' table.
+    pub(crate) fn variation_axes(&self) -> &[FvarAxis] {
+        &self.variation_axes
+    }
+
+    /// Returns the named instances of a variable font from the 'fvar' table.
+    pub(crate) fn named_instances(&self) -> &[FvarInstance] {
+        &self.named_instances
+    }
+
+    /// Returns the control values of the hinting instructions from the
+    /// 'cvt ' table, in font design units.
+    pub(crate) fn control_values(&self) -> Vec<i16> {
+        let cvt = self.raw_table("cvt ").unwrap_or_default();
+        parse_be_array(&mut &cvt[..], cvt.len() / 2).unwrap_or_default()
+    }
+
+    /// Returns the instructions of the 'fpgm' table, run once when the font
+    /// is loaded.
+    pub(crate) fn font_program(&self) -> &[u8] {
+        self.raw_table("fpgm").unwrap_or_default()
+    }
+
+    /// Returns the instructions of the 'prep' table, run whenever the size
+    /// changes.
+    pub(crate) fn control_value_program(&self) -> &[u8] {
+        self.raw_table("prep").unwrap_or_default()
+    }
+
+    /// Returns the contours of the given glyph from the 'glyf' table in font
+    /// design units. Fails if the font has no TrueType outlines or the glyph
+    /// is malformed.
+    pub(crate) fn glyph_outline(&self, glyph: u16) -> ParseResult<Vec<Vec<OutlinePoint>>> {
+        let glyf = self.raw_table("glyf").ok_or(())?;
+        self.glyph_data.as_ref().ok_or(())?.outline(glyf, glyph, 0, &mut 0)
+    }
 }
 
 impl Parse for TtfFile {
@@ -118,42 +1702,53 @@ impl Parse for TtfFile {
         // Initial table
         let offset = OffsetSubtable::parse_be(&mut bytes)?;
         // Collect entries
-        let mut entries = HashMap::new();
+        let mut entries = BTreeMap::new();
         for _ in 0..offset.num_tables {
-            let e = TableDirectoryEntry::parse_be(&mut bytes).unwrap();
+            let e = TableDirectoryEntry::parse_be(&mut bytes)?;
             let tag = format!("{}{}{}{}", e.tag[0] as char, e.tag[1] as char,
                 e.tag[2] as char, e.tag[3] as char);
             entries.insert(tag, e);
         }
         // Parse head table
-        let head_entry = entries.get("head");
-        if head_entry.is_none() {
-            return Err(());
-        }
-        let head_entry = head_entry.unwrap();
-        let mut head_bytes = &input[(head_entry.offset as usize)..];
+        let mut head_bytes = entries.get("head")
+            .and_then(|e| input.get((e.offset as usize)..))
+            .ok_or(())?;
         let head = HeadTable::parse_be(&mut head_bytes)?;
         // Check magic
         if head.magic_number != HEAD_TABLE_MAGIC {
             return Err(());
         }
-        // Parse name table
-        let name_entry = entries.get("name");
-        if name_entry.is_none() {
-            return Err(());
-        }
-        let name_entry = name_entry.unwrap();
-        let mut name_bytes = &input[(name_entry.offset as usize)..];
-        let orig_name_bytes = name_bytes;
-        let name = NameTable::parse_be(&mut name_bytes).unwrap();
+        // The outline parsing relies on a valid 'loca' format
+        let loca_format = head.loca_format()?;
+        // Parse the glyph count
+        let num_glyphs = entries.get("maxp")
+            .and_then(|e| input.get((e.offset as usize)..))
+            .ok_or(())
+            .and_then(parse_maxp)?;
+        // Parse name table, subset fonts might leave it out
+        let orig_name_bytes = entries.get("name")
+            .map(|e| input.get((e.offset as usize)..).ok_or(()))
+            .transpose()?;
+        let name = match orig_name_bytes {
+            Some(mut name_bytes) => NameTable::parse_be(&mut name_bytes)?,
+            None => NameTable::default(),
+        };
         // Collect the names
-        let mut names: HashMap<u16, HashSet<String>> = HashMap::new();
-        let strings = &orig_name_bytes[(name.string_offset as usize)..];
+        let mut names: BTreeMap<u16, BTreeSet<String>> = BTreeMap::new();
+        let mut english_names: BTreeMap<u16, String> = BTreeMap::new();
+        let strings = match orig_name_bytes {
+            Some(b) => b.get((name.string_offset as usize)..).ok_or(())?,
+            None => &[],
+        };
         for e in &name.name_records {
             let offs = e.offset as usize;
             let len = e.length as usize;
-            // Byte sequence for the string
-            let data = &strings[offs..(offs + len)];
+            // Byte sequence for the string, records pointing outside of the
+            // table are skipped
+            let data = match strings.get(offs..(offs + len)) {
+                Some(data) => data,
+                None => continue,
+            };
             let text = if e.platform_id == 1 {
                     // ASCII
                     String::from_utf8_lossy(data).into_owned()
@@ -167,14 +1762,106 @@ impl Parse for TtfFile {
                         .collect();
                     String::from_utf16_lossy(&text16)
                 };
-            // Add it to the names
-            if !names.contains_key(&e.name_id) {
-                names.insert(e.name_id, HashSet::new());
+            // Remember the US English names, preferring the Windows ones
+            let windows_english = e.platform_id == 3 && e.language_id == 0x0409;
+            let mac_english = e.platform_id == 1 && e.language_id == 0;
+            if windows_english || (mac_english && !english_names.contains_key(&e.name_id)) {
+                english_names.insert(e.name_id, text.clone());
             }
-            let ns = names.get_mut(&e.name_id).unwrap();
-            ns.insert(text);
+            // Add it to the names
+            names.entry(e.name_id).or_default().insert(text);
         }
 
+        // Parse the ligatures, the GSUB table is optional
+        #[cfg(all(feature = "std", target_os = "windows"))]
+        let ligatures = entries.get("GSUB")
+            .and_then(|e| input.get((e.offset as usize)..))
+            .and_then(|t| parse_gsub_ligatures(t).ok())
+            .unwrap_or_default();
+        // Parse the mark attachments, the GPOS table is optional
+        #[cfg(all(feature = "std", target_os = "windows"))]
+        let mark_to_base = entries.get("GPOS")
+            .and_then(|e| input.get((e.offset as usize)..))
+            .and_then(|t| parse_gpos_mark_to_base(t).ok())
+            .unwrap_or_default();
+        // Parse the kerning, either from the GPOS table or the legacy one
+        let pair_adjustments = entries.get("GPOS")
+            .and_then(|e| input.get((e.offset as usize)..))
+            .and_then(|t| parse_gpos_kerning(t).ok())
+            .unwrap_or_default();
+        let kerning = entries.get("kern")
+            .and_then(|e| table_bytes(input, e))
+            .and_then(|t| parse_kern(t).ok())
+            .unwrap_or_default();
+        // Parse the vertical metrics, only present in fonts made for vertical text
+        let vertical = match (entries.get("vhea"), entries.get("vmtx")) {
+            (Some(vhea), Some(vmtx)) => input.get((vhea.offset as usize)..)
+                .zip(input.get((vmtx.offset as usize)..))
+                .and_then(|(vhea, vmtx)| parse_vertical_metrics(vhea, vmtx).ok()),
+            _ => None,
+        };
+        // Parse the horizontal metrics
+        let advances = match (entries.get("hhea"), entries.get("hmtx")) {
+            (Some(hhea), Some(hmtx)) => input.get((hhea.offset as usize)..)
+                .zip(input.get((hmtx.offset as usize)..))
+                .and_then(|(hhea, hmtx)| parse_horizontal_advances(hhea, hmtx).ok())
+                .unwrap_or_default(),
+            _ => Vec::new(),
+        };
+        let fixed_pitch = entries.get("post")
+            .and_then(|e| input.get((e.offset as usize)..))
+            .and_then(|t| PostHeader::parse_be(&mut &t[..]).ok())
+            .map(|post| post.is_fixed_pitch != 0)
+            .unwrap_or(false);
+        // The style flags are at the same offset in every version of the 'OS/2'
+        // table, the rest of it isn't needed
+        const FS_SELECTION_OFFSET: usize = 62;
+        let fs_selection = entries.get("OS/2")
+            .and_then(|e| table_bytes(input, e))
+            .and_then(|t| t.get(FS_SELECTION_OFFSET..))
+            .and_then(|mut t| u16::parse_be(&mut t).ok());
+        // Parse the character mapping, we can do without it
+        let glyph_indices = entries.get("cmap")
+            .and_then(|e| input.get((e.offset as usize)..))
+            .and_then(|t| parse_cmap(t).ok())
+            .unwrap_or_default();
+        // Parse the TrueType outlines, fonts with CFF outlines don't have them
+        let glyph_data = match (entries.get("loca"), entries.get("glyf")) {
+            (Some(loca), Some(glyf)) if head.has_known_glyph_data_format() => table_bytes(input, loca)
+                .filter(|_| table_bytes(input, glyf).is_some())
+                .and_then(|loca| parse_glyph_data(loca, loca_format, num_glyphs).ok()),
+            _ => None,
+        };
+        // Parse the color bitmaps, mostly used for emoji
+        let color_bitmaps = entries.get("sbix")
+            .and_then(|e| table_bytes(input, e))
+            .and_then(|t| parse_sbix(t, num_glyphs).ok())
+            .unwrap_or_default();
+        // Parse the rendering behavior of the sizes, it's only a hint
+        let gasp = entries.get("gasp")
+            .and_then(|e| table_bytes(input, e))
+            .and_then(|t| parse_gasp(t).ok())
+            .unwrap_or_default();
+        // Parse the design space of variable fonts
+        let (mut variation_axes, named_instances) = entries.get("fvar")
+            .and_then(|e| table_bytes(input, e))
+            .and_then(|t| parse_fvar(t).ok())
+            .unwrap_or_default();
+        let axis_mappings = entries.get("avar")
+            .and_then(|e| table_bytes(input, e))
+            .and_then(|t| parse_avar(t).ok())
+            .filter(|m| m.len() == variation_axes.len())
+            .unwrap_or_default();
+        for (axis, mapping) in variation_axes.iter_mut().zip(axis_mappings) {
+            axis.mapping = mapping;
+        }
+        // Keep every table in bounds, to read the ones not parsed here
+        let tables = entries.iter()
+            .filter(|(_, e)| table_bytes(input, e).is_some())
+            .map(|(tag, e)| (tag.clone(), (e.offset as usize)..(e.offset as usize + e.length as usize)))
+            .collect();
+        let data = input.to_vec();
+
         *input = bytes;
 
         Ok(Self{
@@ -182,6 +1869,150 @@ impl Parse for TtfFile {
             head,
             name,
             names,
+            english_names,
+            #[cfg(all(feature = "std", target_os = "windows"))]
+            ligatures,
+            #[cfg(all(feature = "std", target_os = "windows"))]
+            mark_to_base,
+            pair_adjustments,
+            kerning,
+            vertical,
+            glyph_indices,
+            advances,
+            fixed_pitch,
+            fs_selection,
+            glyph_data,
+            color_bitmaps,
+            gasp,
+            variation_axes,
+            named_instances,
+            num_glyphs,
+            tables,
+            data,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn simple_glyph_with_decreasing_end_points_is_rejected() {
+        // End points 10 and 5, so 6 points, the first contour claiming 11
+        let mut data = vec![0, 10, 0, 5, 0, 0];
+        data.extend(vec![ON_CURVE_POINT | X_SHORT_VECTOR | Y_SHORT_VECTOR; 6]);
+        data.extend(vec![1; 12]);
+        assert!(parse_simple_glyph(&data, 2).is_err());
+    }
+
+    #[test]
+    fn composite_glyph_expanding_exponentially_is_rejected() {
+        // Every glyph is made of the next one twice, glyph 16 is empty
+        let mut glyf = Vec::new();
+        let mut offsets = Vec::new();
+        for glyph in 0..16u16 {
+            offsets.push(glyf.len() as u32);
+            glyf.extend(&[0xff, 0xff, 0, 0, 0, 0, 0, 0, 0, 0]);
+            for more in &[MORE_COMPONENTS, 0] {
+                glyf.extend(&(ARGS_ARE_XY_VALUES | more).to_be_bytes());
+                glyf.extend(&(glyph + 1).to_be_bytes());
+                glyf.extend(&[0, 0]);
+            }
+        }
+        offsets.push(glyf.len() as u32);
+        offsets.push(glyf.len() as u32);
+        let glyph_data = GlyphData{ offsets };
+        assert!(glyph_data.outline(&glyf, 0, 0, &mut 0).is_err());
+        assert!(glyph_data.outline(&glyf, 12, 0, &mut 0).is_ok());
+    }
+
+    /// Returns the position of the directory entry of the table with the
+    /// given tag in the font.
+    fn table_entry(font: &[u8], tag: &[u8]) -> usize {
+        let num_tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+        (0..num_tables).map(|i| 12 + i * 16)
+            .find(|e| &font[*e..(*e + 4)] == tag)
+            .unwrap()
+    }
+
+    #[test]
+    fn name_records_outside_of_the_table_are_skipped() {
+        let mut font = include_bytes!("../examples/JetBrainsMono-Regular.ttf").to_vec();
+        let entry = table_entry(&font, b"name");
+        let name = u32::from_be_bytes([font[entry + 8], font[entry + 9], font[entry + 10], font[entry + 11]]) as usize;
+        let count = u16::from_be_bytes([font[name + 2], font[name + 3]]) as usize;
+        // Point the string of every record past the end of the font
+        for record in 0..count {
+            let offset = name + 6 + record * 12 + 10;
+            font[offset..(offset + 2)].copy_from_slice(&[0xff, 0xff]);
+        }
+        let ttf = TtfFile::parse(&font).unwrap();
+        assert!(ttf.name(4).is_none());
+    }
+
+    #[test]
+    fn tables_past_the_end_are_rejected() {
+        let font = include_bytes!("../examples/JetBrainsMono-Regular.ttf");
+        for tag in &[b"head", b"name"] {
+            let mut font = font.to_vec();
+            let entry = table_entry(&font, *tag);
+            let past_end = (font.len() as u32 + 1).to_be_bytes();
+            font[(entry + 8)..(entry + 12)].copy_from_slice(&past_end);
+            assert!(TtfFile::parse(&font).is_err());
+        }
+    }
+
+    #[test]
+    fn class_ranges_are_looked_up_without_expanding() {
+        // Ranges 300..=65535 in class 2 and 10..=20 in class 1, out of order
+        let table = [0, 2, 0, 2, 1, 44, 255, 255, 0, 2, 0, 10, 0, 20, 0, 1];
+        let class_def = parse_class_def(&table).unwrap();
+        assert_eq!(class_def.class(9), 0);
+        assert_eq!(class_def.class(10), 1);
+        assert_eq!(class_def.class(20), 1);
+        assert_eq!(class_def.class(21), 0);
+        assert_eq!(class_def.class(300), 2);
+        assert_eq!(class_def.class(65535), 2);
+    }
+
+    #[test]
+    fn coverage_ranges_are_looked_up_without_expanding() {
+        // Ranges 300..=65535 from index 11 and 10..=20 from index 0, out of order
+        let table = [0, 2, 0, 2, 1, 44, 255, 255, 0, 11, 0, 10, 0, 20, 0, 0];
+        let coverage = parse_coverage(&table).unwrap();
+        assert_eq!(coverage.index(9), None);
+        assert_eq!(coverage.index(10), Some(0));
+        assert_eq!(coverage.index(20), Some(10));
+        assert_eq!(coverage.index(21), None);
+        assert_eq!(coverage.index(300), Some(11));
+        assert_eq!(coverage.index(65535), Some(11 + 65235));
+        assert_eq!(coverage.glyphs().take(12).last(), Some(300));
+    }
+
+    #[test]
+    fn pair_classes_without_values_are_rejected() {
+        // No value formats with 65535 classes on both sides, the coverage and
+        // the class definitions all share one range over every glyph
+        let mut table = vec![0, 2, 0, 16, 0, 0, 0, 0, 0, 16, 0, 16, 255, 255, 255, 255];
+        table.extend(&[0, 2, 0, 1, 0, 0, 255, 255, 0, 0]);
+        assert!(parse_pair_adjustment(&table).is_err());
+    }
+
+    #[test]
+    fn cmap_groups_are_looked_up_without_expanding() {
+        // One group mapping all of Unicode from glyph 1, and one for 'A'..='Z'
+        let mut table = vec![0, 12, 0, 0, 0, 0, 0, 40, 0, 0, 0, 0, 0, 0, 0, 2];
+        table.extend(&[0, 0, 0, 0x80, 0, 0x10, 0xff, 0xff, 0, 0, 0, 1]);
+        table.extend(&[0, 0, 0, 0x41, 0, 0, 0, 0x5a, 0, 0, 0, 10]);
+        let cmap = CharacterMap::Groups(parse_cmap_format12(&table).unwrap());
+        assert_eq!(cmap.get(0x40), None);
+        assert_eq!(cmap.get(0x41), Some(10));
+        assert_eq!(cmap.get(0x5a), Some(35));
+        assert_eq!(cmap.get(0x80), Some(1));
+        assert_eq!(cmap.get(0x80 + 0xfffe), Some(0xffff));
+        // Past the last glyph index
+        assert_eq!(cmap.get(0x80 + 0xffff), None);
+    }
+}
@@ -0,0 +1,386 @@
+
+// Implementation for the web, based on the 2D context of an offscreen canvas.
+
+#![cfg(target_arch = "wasm32")]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wasm_bindgen::JsCast;
+use web_sys::{Document, HtmlCanvasElement, CanvasRenderingContext2d, TextMetrics};
+use crate::{FontInfo, FontBackend, ScaledFaceBackend, RasterizedGlyph, BitDepth, GlyphBox, GlyphPositioning, ShapeOptions, RenderQuality, ScaleSettings, Result, Error};
+use crate::ttf::TtfFile;
+
+/// Returns the document of the current window.
+fn document() -> Result<Document> {
+    web_sys::window()
+        .and_then(|w| w.document())
+        .ok_or_else(|| Error::SystemError("No document available!".into()))
+}
+
+/// Generates a unique font family name, so the faces of different fonts don't
+/// shadow each other in the document.
+fn unique_family_name() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("rust-text-{}", id)
+}
+
+// Implementation of the font API
+
+// Font
+
+pub struct WebFont {
+    meta  : FontInfo,
+    // The family the font got registered as in the document
+    family: String  ,
+}
+
+impl FontBackend for WebFont {
+    type Face = WebFontFace;
+    type Scaled = WebScaledFontFace;
+    type Context = ();
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        // Get metadata
+        let meta = FontInfo::from_bytes(bytes)?;
+        // Register the font in the document
+        // NOTE: The browser loads the font asynchronously, until the document's
+        // font set becomes ready, rendering falls back to the default font.
+        let family = unique_family_name();
+        let face = web_sys::FontFace::new_with_u8_array(&family, bytes)
+            .map_err(|_| Error::SystemError("Failed to create FontFace!".into()))?;
+        document()?.fonts().add(&face)
+            .map_err(|_| Error::SystemError("Failed to add FontFace to the document!".into()))?;
+        // Done
+        Ok(Self{
+            meta,
+            family,
+        })
+    }
+
+    fn info(&self) -> &FontInfo {
+        &self.meta
+    }
+
+    fn face(&self, name: &str) -> Result<Self::Face> {
+        if self.meta.file().resolve_face(name).is_none() {
+            // No such face
+            return Err(Error::UserError(format!("No face named '{}' found in font!", name)));
+        }
+        // All faces are registered under the same family
+        WebFontFace::create(&self.family, self.meta.file().ttf().clone())
+    }
+
+    fn create_context() -> Result<Self::Context> {
+        // Every face has its own canvas, there's nothing to share
+        Ok(())
+    }
+
+    fn scale(face: &Self::Face, settings: &ScaleSettings, _context: Option<&Self::Context>) -> Result<Self::Scaled> {
+        if settings.escapement != 0 {
+            return Err(Error::Unsupported("Rotated text on the web"));
+        }
+        if settings.stretch != 1.0 {
+            return Err(Error::Unsupported("Stretched text on the web"));
+        }
+        if !settings.variations.is_empty() {
+            return Err(Error::Unsupported("Font variations on the web"));
+        }
+        // Only the coverage is drawn
+        face.scale(settings.pts, settings.dpi, settings.quality, settings.flip_y)
+    }
+}
+
+impl Drop for WebFont {
+    fn drop(&mut self) {
+        // We don't keep the JS object around, so the font stays shareable between
+        // threads, look it up by the family instead
+        let fonts = match document() {
+            Ok(doc) => doc.fonts(),
+            Err(_) => return,
+        };
+        let iter = match js_sys::try_iter(fonts.as_ref()) {
+            Ok(Some(iter)) => iter,
+            _ => return,
+        };
+        for face in iter.flatten() {
+            if let Ok(face) = face.dyn_into::<web_sys::FontFace>() {
+                if face.family().trim_matches('"') == self.family {
+                    fonts.delete(&face);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct WebFontFace {
+    family: String      ,
+    ttf   : Arc<TtfFile>,
+}
+
+impl WebFontFace {
+    fn create(family: &str, ttf: Arc<TtfFile>) -> Result<Self> {
+        Ok(Self{
+            family: family.into(),
+            ttf,
+        })
+    }
+
+    pub fn scale(&self, pts: f64, dpi: f64, quality: RenderQuality, flip_y: bool) -> Result<WebScaledFontFace> {
+        WebScaledFontFace::create(&self.family, self.ttf.clone(), pts, dpi, quality, flip_y)
+    }
+}
+
+// Scaled font face
+
+pub struct WebScaledFontFace {
+    canvas : HtmlCanvasElement       ,
+    context: CanvasRenderingContext2d,
+    ttf    : Arc<TtfFile>            ,
+    // The size of the em square in pixels
+    em_size: f64                     ,
+    // The CSS font string selecting our face and size
+    font   : String                  ,
+    quality: RenderQuality           ,
+    // Whether the rows of the bitmaps are stored bottom-up
+    flip_y : bool                    ,
+}
+
+impl WebScaledFontFace {
+    fn create(family: &str, ttf: Arc<TtfFile>, pts: f64, dpi: f64, quality: RenderQuality,
+        flip_y: bool) -> Result<Self> {
+        // Create the canvas, it's never attached to the document
+        let canvas = document()?.create_element("canvas")
+            .ok()
+            .and_then(|e| e.dyn_into::<HtmlCanvasElement>().ok())
+            .ok_or_else(|| Error::SystemError("Failed to create canvas!".into()))?;
+        let context = canvas.get_context("2d")
+            .ok()
+            .flatten()
+            .and_then(|c| c.dyn_into::<CanvasRenderingContext2d>().ok())
+            .ok_or_else(|| Error::SystemError("Failed to get 2D context of canvas!".into()))?;
+        // Calculate size
+        const POINTS_PER_INCH: f64 = 72.0;
+        let em_size = pts * dpi / POINTS_PER_INCH;
+        // We succeeded in creating everything
+        let mut result = Self{
+            canvas,
+            context,
+            ttf,
+            em_size,
+            font: format!("{}px \"{}\"", em_size, family),
+            quality,
+            flip_y,
+        };
+        result.reset_context();
+        Ok(result)
+    }
+
+    /// Sets up the drawing state of the context. Needs to be called after every
+    /// resize, as resizing the canvas resets the state.
+    fn reset_context(&mut self) {
+        self.context.set_font(&self.font);
+        self.context.set_text_baseline("alphabetic");
+        self.context.set_fill_style_str("white");
+    }
+
+    fn ensure_buffer_size(&mut self, width: usize, height: usize) {
+        let buff_w = self.canvas.width() as usize;
+        let buff_h = self.canvas.height() as usize;
+        if buff_w >= width && buff_h >= height {
+            // Already enough
+            return;
+        }
+        // Need to resize
+        self.canvas.set_width(std::cmp::max(width, buff_w) as u32);
+        self.canvas.set_height(std::cmp::max(height, buff_h) as u32);
+        self.reset_context();
+    }
+
+    fn measure(&self, text: &str) -> Result<TextMetrics> {
+        self.context.measure_text(text)
+            .map_err(|_| Error::SystemError("measureText failed!".into()))
+    }
+
+    /// Returns the height of a line of text and the distance of the baseline
+    /// from the top of the line.
+    fn line_metrics(&self) -> (i32, i32) {
+        match self.measure(" ") {
+            Ok(m) => {
+                let ascent = m.font_bounding_box_ascent().ceil() as i32;
+                let descent = m.font_bounding_box_descent().ceil() as i32;
+                (ascent + descent, ascent)
+            },
+            Err(_) => (0, 0),
+        }
+    }
+}
+
+impl ScaledFaceBackend for WebScaledFontFace {
+    fn em_size(&self) -> f64 {
+        self.em_size
+    }
+
+    fn line_height(&self) -> i32 {
+        self.line_metrics().0
+    }
+
+    fn ascent(&self) -> i32 {
+        self.line_metrics().1
+    }
+
+    fn rasterize_glyph(&mut self, codepoint: char) -> Result<RasterizedGlyph> {
+        let text = format!("{}", codepoint);
+        // Get coordinates
+        let metrics = self.measure(&text)?;
+        let (line_height, baseline) = self.line_metrics();
+        // Glyphs can overhang to the left of the origin
+        let pad = std::cmp::max(0, metrics.actual_bounding_box_left().ceil() as i32);
+        let ink_width = metrics.actual_bounding_box_right().ceil() as i32 + pad;
+        let required_width = std::cmp::max(metrics.width().ceil() as i32 + pad, ink_width) as usize;
+        let required_height = line_height as usize;
+        if required_width == 0 || required_height == 0 {
+            // Nothing to draw
+            return Ok(RasterizedGlyph{
+                character: codepoint,
+                x_offset: 0,
+                y_offset: 0,
+                width: 0,
+                height: 0,
+                bit_depth: BitDepth::Eight,
+                cell: GlyphBox::default(),
+                baseline: 0,
+                data: vec![0u8; 0].into_boxed_slice(),
+            });
+        }
+        // Ensure buffer size
+        self.ensure_buffer_size(required_width, required_height);
+        let buff_w = self.canvas.width() as usize;
+        let buff_h = self.canvas.height() as usize;
+        // Clear the canvas, then render to it
+        self.context.clear_rect(0.0, 0.0, buff_w as f64, buff_h as f64);
+        self.context.fill_text(&text, pad as f64, baseline as f64)
+            .map_err(|_| Error::SystemError("fillText failed!".into()))?;
+        // Read back the pixels, the coverage is in the alpha channel
+        let image = self.context.get_image_data(0.0, 0.0, buff_w as f64, buff_h as f64)
+            .map_err(|_| Error::SystemError("getImageData failed!".into()))?;
+        let rgba = image.data();
+        // The canvas always antialiases text, so aliased output is thresholded
+        let aliased = self.quality == RenderQuality::Aliased;
+        let pixel = |x: usize, y: usize| {
+            let alpha = rgba[(y * buff_w + x) * 4 + 3];
+            if !aliased { alpha } else if alpha >= 0x80 { 0xff } else { 0 }
+        };
+        // Calculate the tightest bounds
+        let mut left = buff_w;
+        let mut right = 0;
+        let mut top = buff_h;
+        let mut bottom = 0;
+        for y in 0..buff_h {
+            for x in 0..buff_w {
+                if pixel(x, y) != 0 {
+                    left = std::cmp::min(left, x);
+                    right = std::cmp::max(right, x + 1);
+                    top = std::cmp::min(top, y);
+                    bottom = std::cmp::max(bottom, y + 1);
+                }
+            }
+        }
+        if left >= right {
+            // The canvas must be empty, return empty canvas
+            return Ok(RasterizedGlyph{
+                character: codepoint,
+                x_offset: 0,
+                y_offset: 0,
+                width: 0,
+                height: 0,
+                bit_depth: BitDepth::Eight,
+                cell: GlyphBox::default(),
+                baseline: 0,
+                data: vec![0u8; 0].into_boxed_slice(),
+            });
+        }
+        let bounds_width = right - left;
+        let bounds_height = bottom - top;
+        // Copy the data to the buffer
+        let mut data = vec![0u8; bounds_width * bounds_height].into_boxed_slice();
+        for y in 0..bounds_height {
+            let y_res_offs = y * bounds_width;
+            let row = if self.flip_y { bottom - 1 - y } else { top + y };
+            for x in 0..bounds_width {
+                data[y_res_offs + x] = pixel(left + x, row);
+            }
+        }
+        // We succeeded
+        Ok(RasterizedGlyph{
+            character: codepoint,
+            x_offset: left as i32 - pad,
+            y_offset: top as i32,
+            width: bounds_width,
+            height: bounds_height,
+            bit_depth: BitDepth::Eight,
+            cell: GlyphBox::default(),
+            baseline: 0,
+            data,
+        })
+    }
+
+    fn rasterize_glyph_index(&mut self, _glyph_id: u16) -> Result<RasterizedGlyph> {
+        // The canvas API can only draw text, not glyphs
+        Err(Error::Unsupported("Rasterizing by glyph index on the web"))
+    }
+
+    fn shape_text<F: FnMut(GlyphPositioning)>(&self, text: &str, _options: ShapeOptions, mut f: F) -> Result<(i32, i32)> {
+        // NOTE: The canvas always applies kerning and ligatures, we can't turn
+        // them off. Glyph indices aren't exposed either.
+        let (line_height, _) = self.line_metrics();
+        let advance = |s: &str| self.measure(s).map(|m| m.width()).unwrap_or(0.0);
+
+        // Biggest dimensions
+        let mut max_w = 0;
+        let mut max_h = 0;
+
+        let lines: Vec<&str> = text.split('\n').collect();
+        let mut index = 0;
+        let mut yoff = 0;
+        for (i, line) in lines.iter().enumerate() {
+            for (offs, ch) in line.char_indices() {
+                let x = advance(&line[..offs]);
+                f(GlyphPositioning{
+                    character: ch,
+                    index,
+                    cluster: index,
+                    // The browser doesn't expose the shaped glyphs, look them up instead
+                    glyph_id: self.ttf.glyph_index(ch).unwrap_or(0),
+                    x: x.floor() as i32,
+                    y: yoff,
+                    x_fraction: (x - x.floor()) as f32,
+                    caret_x: x.round() as i32,
+                    caret_y: yoff,
+                });
+                index += 1;
+            }
+            let width = advance(line).round() as i32;
+            max_w = std::cmp::max(max_w, width);
+            max_h = std::cmp::max(max_h, yoff + line_height);
+            if i + 1 < lines.len() {
+                // Report the newline that got split off
+                f(GlyphPositioning{
+                    character: '\n',
+                    index,
+                    cluster: index,
+                    glyph_id: 0,
+                    x: width,
+                    y: yoff,
+                    x_fraction: 0.0,
+                    caret_x: width,
+                    caret_y: yoff,
+                });
+                index += 1;
+                yoff += line_height;
+            }
+        }
+        Ok((max_w, max_h))
+    }
+}
@@ -5,9 +5,17 @@
 
 use std::io::prelude::*;
 use std::fs::File;
-use crate::{RasterizedGlyph, GlyphPositioning, ShapeOptions, Result, Error};
-use crate::font_file::FontFile;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Weak, Mutex};
+use std::hash::{Hash, Hasher};
+use std::collections::hash_map::DefaultHasher;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{FontInfo, SystemFontInfo, FontBackend, ScaledFaceBackend, RasterizedGlyph, BitDepth, GlyphBox, GlyphPositioning, ShapeOptions, RenderQuality, RenderColors, ScaleSettings, Result, Error};
+use crate::ttf::TtfFile;
 use crate::winapi::*;
+#[cfg(feature = "directwrite")]
+use crate::dwrite::*;
 
 /// UTF-8 to UTF-16 conversion.
 fn utf8_to_utf16(s: &str) -> Box<[WCHAR]> {
@@ -24,6 +32,13 @@ fn utf8_to_utf16(s: &str) -> Box<[WCHAR]> {
     res.into_boxed_slice()
 }
 
+/// Null-terminated UTF-16 to UTF-8 conversion.
+fn utf16_to_utf8(s: &[WCHAR]) -> String {
+    let len = s.iter().position(|c| *c == 0).unwrap_or(s.len());
+    let units: Vec<u16> = s[..len].iter().map(|c| *c as u16).collect();
+    String::from_utf16_lossy(&units)
+}
+
 /// Writes a file with the given bytes.
 fn file_write_bytes(path: &str, bytes: &[u8]) -> std::io::Result<()> {
     let mut buff = File::create(path)?;
@@ -35,6 +50,41 @@ fn file_write_bytes(path: &str, bytes: &[u8]) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Generates a unique path in the temporary directory of the system for a file
+/// with the given extension. Uniqueness is guaranteed within the process by a
+/// counter and between processes by the process ID.
+fn unique_temp_path(extension: &str) -> Result<String> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let fname = format!("_rust_text_{}_{}.{}", std::process::id(), id, extension);
+    let path = std::env::temp_dir().join(fname);
+    path.to_str()
+        .map(|p| p.into())
+        .ok_or_else(|| Error::SystemError("Temporary directory path is not valid UTF-8!".into()))
+}
+
+/// A guard for a temporary file that removes it from disk when dropped, even
+/// when unwinding from a panic.
+struct TempFile(String);
+
+impl TempFile {
+    /// Writes the given bytes to a file at the given path. The guard is set up
+    /// before writing, so a partially written file is removed too.
+    fn write(path: &str, bytes: &[u8]) -> std::io::Result<Self> {
+        let result = Self(path.into());
+        file_write_bytes(&result.0, bytes)?;
+        Ok(result)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // Don't escalate errors!
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
 /// A wrapper type for a GDI DeviceContext.
 struct DeviceContext(HDC);
 
@@ -48,7 +98,7 @@ impl DeviceContext {
 
 impl Drop for DeviceContext {
     fn drop(&mut self) {
-        if self.0 != std::ptr::null_mut() {
+        if !self.0.is_null() {
             unsafe{ DeleteDC(self.0) };
         }
     }
@@ -64,197 +114,1085 @@ impl GdiObject {
 
 impl Drop for GdiObject {
     fn drop(&mut self) {
-        if self.0 != std::ptr::null_mut() {
+        if !self.0.is_null() {
             unsafe{ DeleteObject(self.0) };
         }
     }
 }
 
+/// The largest width and height of the bitmaps glyphs are drawn to. Glyphs
+/// with larger extents get clipped.
+const MAX_BITMAP_SIZE: usize = 16384;
+
+/// A bitmap with its pixel memory accessible. The memory is owned by the
+/// bitmap object, so the pixels are only accessible while it's alive.
+struct DibSection {
+    bitmap: GdiObject    ,
+    bits  : *mut COLORREF,
+    width : usize        ,
+    height: usize        ,
+}
+
+impl DibSection {
+    /// Wraps a bitmap with no accessible pixel memory.
+    fn empty(bitmap: GdiObject) -> Self {
+        Self{
+            bitmap,
+            bits: std::ptr::null_mut(),
+            width: 0,
+            height: 0,
+        }
+    }
+
+    /// Creates a 32-bit bitmap compatible with the given Device Context.
+    fn create(dc: &DeviceContext, width: usize, height: usize) -> Result<Self> {
+        // The pixels are accessed through a slice of this size
+        let size = width.checked_mul(height).and_then(|n| n.checked_mul(std::mem::size_of::<COLORREF>()));
+        if width > MAX_BITMAP_SIZE || height > MAX_BITMAP_SIZE || size.is_none() {
+            return Err(Error::SystemError(format!("Bitmap size {}x{} is out of range!", width, height)));
+        }
+        let mut info = BITMAPINFO::new();
+        info.bmiHeader.biWidth = width as _;
+        info.bmiHeader.biHeight = height as _;
+        info.bmiHeader.biPlanes = 1;
+        info.bmiHeader.biBitCount = 32;
+        info.bmiHeader.biCompression = BI_RGB;
+        info.bmiHeader.biSizeImage = 0;
+        info.bmiHeader.biXPelsPerMeter = 0;
+        info.bmiHeader.biYPelsPerMeter = 0;
+        info.bmiHeader.biClrUsed = 0;
+        info.bmiHeader.biClrImportant = 0;
+        let mut bits: PVOID = std::ptr::null_mut();
+        let bitmap = GdiObject(unsafe{ CreateDIBSection(dc.0, &info, DIB_RGB_COLORS, &mut bits, std::ptr::null_mut(), 0) });
+        if bitmap.is_err() || bits.is_null() {
+            return Err(Error::SystemError("Failed to create Bitmap!".into()));
+        }
+        Ok(Self{
+            bitmap,
+            bits: bits as _,
+            width,
+            height,
+        })
+    }
+
+    fn width(&self) -> usize { self.width }
+    fn height(&self) -> usize { self.height }
+
+    /// Returns the pixels in row-major order.
+    fn pixels(&self) -> &[COLORREF] {
+        if self.bits.is_null() {
+            return &[];
+        }
+        unsafe{ std::slice::from_raw_parts(self.bits, self.width * self.height) }
+    }
+
+    /// Returns the pixels in row-major order for modification.
+    fn pixels_mut(&mut self) -> &mut [COLORREF] {
+        if self.bits.is_null() {
+            return &mut [];
+        }
+        unsafe{ std::slice::from_raw_parts_mut(self.bits, self.width * self.height) }
+    }
+
+    /// Returns the pixel at the given position.
+    fn pixel(&self, x: usize, y: usize) -> COLORREF {
+        assert!(x < self.width && y < self.height, "Pixel out of bitmap bounds!");
+        self.pixels()[y * self.width + x]
+    }
+}
+
+// DirectWrite shaping
+
+/// An owned COM interface pointer that gets released when dropped.
+#[cfg(feature = "directwrite")]
+struct ComPtr<T>(*mut T);
+
+#[cfg(feature = "directwrite")]
+impl <T> ComPtr<T> {
+    fn is_err(&self) -> bool { self.0.is_null() }
+}
+
+#[cfg(feature = "directwrite")]
+impl <T> Drop for ComPtr<T> {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            // Every COM interface starts with the IUnknown vtable
+            let unknown = self.0 as *mut IUnknown;
+            unsafe{ ((*(*unknown).vtbl).Release)(unknown) };
+        }
+    }
+}
+
+// Our implementations of the analysis source and sink live on the stack for
+// the duration of the analysis, so reference counting is a no-op for them.
+
+#[cfg(feature = "directwrite")]
+unsafe extern "system" fn com_query_interface(_this: *mut IUnknown, _iid: *const GUID, obj: *mut PVOID) -> HRESULT {
+    *obj = std::ptr::null_mut();
+    E_NOINTERFACE
+}
+
+#[cfg(feature = "directwrite")]
+unsafe extern "system" fn com_add_ref(_this: *mut IUnknown) -> ULONG { 1 }
+
+#[cfg(feature = "directwrite")]
+unsafe extern "system" fn com_release(_this: *mut IUnknown) -> ULONG { 1 }
+
+#[cfg(feature = "directwrite")]
+const STACK_UNKNOWN_VTBL: IUnknownVtbl = IUnknownVtbl{
+    QueryInterface: com_query_interface,
+    AddRef: com_add_ref,
+    Release: com_release,
+};
+
+/// The text source for script analysis, a single line of text.
+#[cfg(feature = "directwrite")]
+#[repr(C)]
+struct AnalysisSource {
+    vtbl: *const IDWriteTextAnalysisSourceVtbl,
+    text: *const WCHAR                        ,
+    len : UINT32                              ,
+}
+
+#[cfg(feature = "directwrite")]
+impl AnalysisSource {
+    const VTBL: IDWriteTextAnalysisSourceVtbl = IDWriteTextAnalysisSourceVtbl{
+        base: STACK_UNKNOWN_VTBL,
+        GetTextAtPosition: Self::get_text_at_position,
+        GetTextBeforePosition: Self::get_text_before_position,
+        GetParagraphReadingDirection: Self::get_paragraph_reading_direction,
+        GetLocaleName: Self::get_locale_name,
+        GetNumberSubstitution: Self::get_number_substitution,
+    };
+
+    /// The empty string as locale means the user default locale.
+    const EMPTY_LOCALE: [WCHAR; 1] = [0];
+
+    fn new(text: &[WCHAR]) -> Self {
+        Self{
+            vtbl: &Self::VTBL,
+            text: text.as_ptr(),
+            len: text.len() as UINT32,
+        }
+    }
+
+    unsafe extern "system" fn get_text_at_position(this: *mut IDWriteTextAnalysisSource,
+        pos: UINT32, text: *mut *const WCHAR, len: *mut UINT32) -> HRESULT {
+        let this = &*(this as *const Self);
+        if pos >= this.len {
+            *text = std::ptr::null();
+            *len = 0;
+        }
+        else {
+            *text = this.text.offset(pos as isize);
+            *len = this.len - pos;
+        }
+        S_OK
+    }
+
+    unsafe extern "system" fn get_text_before_position(this: *mut IDWriteTextAnalysisSource,
+        pos: UINT32, text: *mut *const WCHAR, len: *mut UINT32) -> HRESULT {
+        let this = &*(this as *const Self);
+        if pos == 0 || pos > this.len {
+            *text = std::ptr::null();
+            *len = 0;
+        }
+        else {
+            *text = this.text;
+            *len = pos;
+        }
+        S_OK
+    }
+
+    unsafe extern "system" fn get_paragraph_reading_direction(_this: *mut IDWriteTextAnalysisSource) -> UINT32 {
+        DWRITE_READING_DIRECTION_LTR
+    }
+
+    unsafe extern "system" fn get_locale_name(this: *mut IDWriteTextAnalysisSource,
+        pos: UINT32, len: *mut UINT32, locale: *mut *const WCHAR) -> HRESULT {
+        let this = &*(this as *const Self);
+        *len = this.len.saturating_sub(pos);
+        *locale = Self::EMPTY_LOCALE.as_ptr();
+        S_OK
+    }
+
+    unsafe extern "system" fn get_number_substitution(this: *mut IDWriteTextAnalysisSource,
+        pos: UINT32, len: *mut UINT32, subst: *mut *mut IUnknown) -> HRESULT {
+        let this = &*(this as *const Self);
+        *len = this.len.saturating_sub(pos);
+        *subst = std::ptr::null_mut();
+        S_OK
+    }
+}
+
+/// The sink of the script analysis, collects the script runs.
+#[cfg(feature = "directwrite")]
+#[repr(C)]
+struct AnalysisSink {
+    vtbl: *const IDWriteTextAnalysisSinkVtbl                 ,
+    runs: Vec<(UINT32, UINT32, DWRITE_SCRIPT_ANALYSIS)>,
+}
+
+#[cfg(feature = "directwrite")]
+impl AnalysisSink {
+    const VTBL: IDWriteTextAnalysisSinkVtbl = IDWriteTextAnalysisSinkVtbl{
+        base: STACK_UNKNOWN_VTBL,
+        SetScriptAnalysis: Self::set_script_analysis,
+        SetLineBreakpoints: Self::set_line_breakpoints,
+        SetBidiLevel: Self::set_bidi_level,
+        SetNumberSubstitution: Self::set_number_substitution,
+    };
+
+    fn new() -> Self {
+        Self{
+            vtbl: &Self::VTBL,
+            runs: Vec::new(),
+        }
+    }
+
+    unsafe extern "system" fn set_script_analysis(this: *mut IDWriteTextAnalysisSink,
+        pos: UINT32, len: UINT32, analysis: *const DWRITE_SCRIPT_ANALYSIS) -> HRESULT {
+        let this = &mut *(this as *mut Self);
+        this.runs.push((pos, len, *analysis));
+        S_OK
+    }
+
+    unsafe extern "system" fn set_line_breakpoints(_this: *mut IDWriteTextAnalysisSink,
+        _pos: UINT32, _len: UINT32, _breakpoints: PVOID) -> HRESULT { S_OK }
+
+    unsafe extern "system" fn set_bidi_level(_this: *mut IDWriteTextAnalysisSink,
+        _pos: UINT32, _len: UINT32, _explicit: u8, _resolved: u8) -> HRESULT { S_OK }
+
+    unsafe extern "system" fn set_number_substitution(_this: *mut IDWriteTextAnalysisSink,
+        _pos: UINT32, _len: UINT32, _subst: *mut IUnknown) -> HRESULT { S_OK }
+}
+
+/// The placement of a single UTF-16 unit in a shaped line.
+#[cfg(feature = "directwrite")]
+#[derive(Debug, Default, Clone, Copy)]
+struct UnitPlacement {
+    x            : f32 ,
+    y            : f32 ,
+    caret_x      : f32 ,
+    glyph_id     : u16 ,
+    cluster_start: bool,
+}
+
+/// Shapes text with DirectWrite, which supports OpenType features (like
+/// ligatures and contextual forms) and complex scripts properly, unlike GDI.
+#[cfg(feature = "directwrite")]
+struct DWriteShaper {
+    font_face: ComPtr<IDWriteFontFace>    ,
+    analyzer : ComPtr<IDWriteTextAnalyzer>,
+    _factory : ComPtr<IDWriteFactory>     ,
+    em_size  : f32                        ,
+}
+
+#[cfg(feature = "directwrite")]
+impl DWriteShaper {
+    /// Creates a shaper for the font selected into the given Device Context.
+    fn create(dc: &DeviceContext, em_size: f32) -> Result<Self> {
+        // Create the factory
+        let mut factory: *mut IUnknown = std::ptr::null_mut();
+        let hr = unsafe{ DWriteCreateFactory(DWRITE_FACTORY_TYPE_SHARED, &IID_IDWriteFactory, &mut factory) };
+        let factory = ComPtr(factory as *mut IDWriteFactory);
+        if hr != S_OK || factory.is_err() {
+            return Err(Error::SystemError("DWriteCreateFactory failed!".into()));
+        }
+        // Convert the GDI font to a DirectWrite one
+        let mut interop = std::ptr::null_mut();
+        let hr = unsafe{ ((*(*factory.0).vtbl).GetGdiInterop)(factory.0, &mut interop) };
+        let interop = ComPtr(interop);
+        if hr != S_OK || interop.is_err() {
+            return Err(Error::SystemError("Failed to get GDI interop!".into()));
+        }
+        let mut font_face = std::ptr::null_mut();
+        let hr = unsafe{ ((*(*interop.0).vtbl).CreateFontFaceFromHdc)(interop.0, dc.0, &mut font_face) };
+        let font_face = ComPtr(font_face);
+        if hr != S_OK || font_face.is_err() {
+            return Err(Error::SystemError("Failed to create DirectWrite font face!".into()));
+        }
+        // Create the analyzer
+        let mut analyzer = std::ptr::null_mut();
+        let hr = unsafe{ ((*(*factory.0).vtbl).CreateTextAnalyzer)(factory.0, &mut analyzer) };
+        let analyzer = ComPtr(analyzer);
+        if hr != S_OK || analyzer.is_err() {
+            return Err(Error::SystemError("Failed to create DirectWrite text analyzer!".into()));
+        }
+        Ok(Self{
+            font_face,
+            analyzer,
+            _factory: factory,
+            em_size,
+        })
+    }
+
+    /// Shapes a single line of text, returning the placement of each UTF-16
+    /// unit and the width of the line.
+    fn shape_line(&self, text: &[WCHAR], options: ShapeOptions) -> Option<(Vec<UnitPlacement>, f32)> {
+        let mut placements = vec![UnitPlacement::default(); text.len()];
+        if text.is_empty() {
+            return Some((placements, 0.0));
+        }
+        let analyzer = self.analyzer.0;
+        let vtbl = unsafe{ &*(*analyzer).vtbl };
+        // Split the text into script runs
+        let mut source = AnalysisSource::new(text);
+        let mut sink = AnalysisSink::new();
+        let hr = unsafe{ (vtbl.AnalyzeScript)(analyzer,
+            &mut source as *mut _ as _, 0, text.len() as UINT32, &mut sink as *mut _ as _) };
+        if hr != S_OK {
+            return None;
+        }
+        // Kerning and ligatures are on by default, so they have to be
+        // explicitly turned off
+        let mut features = Vec::new();
+        if !options.contains(ShapeOptions::USE_KERNING) {
+            features.push(DWRITE_FONT_FEATURE{ nameTag: dwrite_make_tag(*b"kern"), parameter: 0 });
+        }
+        if !options.contains(ShapeOptions::USE_LIGATURES) {
+            features.push(DWRITE_FONT_FEATURE{ nameTag: dwrite_make_tag(*b"liga"), parameter: 0 });
+            features.push(DWRITE_FONT_FEATURE{ nameTag: dwrite_make_tag(*b"clig"), parameter: 0 });
+        }
+        let typographic = DWRITE_TYPOGRAPHIC_FEATURES{
+            features: features.as_mut_ptr(),
+            featureCount: features.len() as UINT32,
+        };
+        let typographic_ptr: *const DWRITE_TYPOGRAPHIC_FEATURES = &typographic;
+        let use_features = !features.is_empty();
+
+        let mut pen_x = 0.0f32;
+        for (pos, len, script) in sink.runs {
+            let run = &text[(pos as usize)..((pos + len) as usize)];
+            let feature_lengths = [len];
+            let (features_ptr, feature_lengths_ptr, feature_ranges) = if use_features {
+                    (&typographic_ptr as *const _, feature_lengths.as_ptr(), 1)
+                }
+                else {
+                    (std::ptr::null(), std::ptr::null(), 0)
+                };
+            // Get the glyphs, growing the buffers if needed
+            const E_NOT_SUFFICIENT_BUFFER: HRESULT = 0x8007007Au32 as HRESULT;
+            let mut max_glyphs = run.len() * 3 / 2 + 16;
+            let mut cluster_map = vec![0u16; run.len()];
+            let mut text_props = vec![0u16; run.len()];
+            let (glyphs, glyph_props, glyph_count) = loop {
+                let mut glyphs = vec![0u16; max_glyphs];
+                let mut glyph_props = vec![0u16; max_glyphs];
+                let mut glyph_count = 0;
+                let hr = unsafe{ (vtbl.GetGlyphs)(analyzer,
+                    run.as_ptr(), len, self.font_face.0, 0, 0, &script,
+                    std::ptr::null(), std::ptr::null_mut(),
+                    features_ptr, feature_lengths_ptr, feature_ranges,
+                    max_glyphs as UINT32, cluster_map.as_mut_ptr(), text_props.as_mut_ptr(),
+                    glyphs.as_mut_ptr(), glyph_props.as_mut_ptr(), &mut glyph_count) };
+                if hr == E_NOT_SUFFICIENT_BUFFER {
+                    max_glyphs *= 2;
+                    continue;
+                }
+                if hr != S_OK {
+                    return None;
+                }
+                break (glyphs, glyph_props, glyph_count as usize);
+            };
+            // Get the placements
+            let mut advances = vec![0f32; glyph_count];
+            let mut offsets = vec![DWRITE_GLYPH_OFFSET::default(); glyph_count];
+            let hr = unsafe{ (vtbl.GetGlyphPlacements)(analyzer,
+                run.as_ptr(), cluster_map.as_ptr(), text_props.as_mut_ptr(), len,
+                glyphs.as_ptr(), glyph_props.as_ptr(), glyph_count as UINT32,
+                self.font_face.0, self.em_size, 0, 0, &script, std::ptr::null(),
+                features_ptr, feature_lengths_ptr, feature_ranges,
+                advances.as_mut_ptr(), offsets.as_mut_ptr()) };
+            if hr != S_OK {
+                return None;
+            }
+            // Pen position before each glyph
+            let mut glyph_x = Vec::with_capacity(glyph_count);
+            for advance in &advances {
+                glyph_x.push(pen_x);
+                pen_x += advance;
+            }
+            // Every unit is positioned where the cluster it belongs to starts
+            for (i, cluster) in cluster_map.iter().enumerate() {
+                let g = *cluster as usize;
+                if g >= glyph_count {
+                    continue;
+                }
+                placements[pos as usize + i] = UnitPlacement{
+                    x: glyph_x[g] + offsets[g].advanceOffset,
+                    y: -offsets[g].ascenderOffset,
+                    caret_x: glyph_x[g],
+                    glyph_id: glyphs[g],
+                    cluster_start: i == 0 || cluster_map[i - 1] != *cluster,
+                };
+            }
+        }
+        Some((placements, pen_x))
+    }
+}
+
 // Implementation of the font API
 
 // Font
 
-pub struct Win32Font {
-    meta   : FontFile    ,
-    fname  : String      ,
-    fname16: Box<[WCHAR]>,
+/// A font file registered as a private resource of the process.
+struct FontResource {
+    fname16 : Box<[WCHAR]>,
+    // The contents of the file, so registrations are matched without reading
+    // it back
+    contents: Box<[u8]>   ,
+    // Removed only after the resource got unregistered in drop
+    _file   : TempFile    ,
 }
 
-impl Win32Font {
-    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
-        // Get metadata
-        let meta = FontFile::from_bytes(bytes)?;
+impl FontResource {
+    /// Writes the bytes to a temporary file and registers it.
+    fn register(bytes: &[u8], extension: &str) -> Result<Self> {
         // Write to file so windows can safely load it as a resource
-        // TODO: Some true random name?
-        let fname = format!("{}.{}", "_temp", meta.extension());
+        let fname = unique_temp_path(extension)?;
         let fname16 = utf8_to_utf16(&fname);
-        // Scope the write so the file gets closed
-        file_write_bytes(&fname, bytes).map_err(|e| Error::IoError(e))?;
+        // The guard removes the file on any early return or panic from here on
+        let file = TempFile::write(&fname, bytes).map_err(Error::IoError)?;
         // Load resource
         let added_fonts = unsafe{ AddFontResourceExW(fname16.as_ptr(), FR_PRIVATE, std::ptr::null_mut()) };
         if added_fonts == 0 {
             unsafe{ RemoveFontResourceExW(fname16.as_ptr(), FR_PRIVATE, std::ptr::null_mut()) };
-            // Remove the file, but don't escalate errors!
-            let _ = std::fs::remove_file(&fname);
             return Err(Error::SystemError("AddFontResourceExW failed!".into()));
         }
+        Ok(Self{
+            fname16,
+            contents: bytes.into(),
+            _file: file,
+        })
+    }
+
+    /// Checks if the registered file has the given contents.
+    fn contains(&self, bytes: &[u8]) -> bool {
+        *self.contents == *bytes
+    }
+}
+
+impl Drop for FontResource {
+    fn drop(&mut self) {
+        // The file itself is removed by the guard after this
+        unsafe{ RemoveFontResourceExW(self.fname16.as_ptr(), FR_PRIVATE, std::ptr::null_mut()) };
+    }
+}
+
+/// The font resources registered by the process, keyed by the hash of their
+/// contents. Fonts loaded from the same bytes share a single registration,
+/// which is only removed when the last of them is dropped.
+static REGISTRY: Mutex<Vec<(u64, Weak<FontResource>)>> = Mutex::new(Vec::new());
+
+/// Registers the font file with the given contents, or returns the existing
+/// registration of it.
+fn acquire_resource(bytes: &[u8], extension: &str) -> Result<Arc<FontResource>> {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    let hash = hasher.finish();
+    // A panic while holding the lock can't leave the list inconsistent
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry.retain(|(_, resource)| resource.strong_count() > 0);
+    let existing = registry.iter()
+        .filter(|(h, _)| *h == hash)
+        .filter_map(|(_, resource)| resource.upgrade())
+        .find(|resource| resource.contains(bytes));
+    if let Some(resource) = existing {
+        return Ok(resource);
+    }
+    let resource = Arc::new(FontResource::register(bytes, extension)?);
+    registry.push((hash, Arc::downgrade(&resource)));
+    Ok(resource)
+}
+
+pub struct Win32Font {
+    meta    : FontInfo         ,
+    resource: Arc<FontResource>,
+}
+
+impl FontBackend for Win32Font {
+    type Face = Win32FontFace;
+    type Scaled = Win32ScaledFontFace;
+    type Context = Rc<Win32RenderContext>;
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        // Get metadata
+        let meta = FontInfo::from_bytes(bytes)?;
+        // Load resource
+        let resource = acquire_resource(bytes, meta.file().extension())?;
         // Done
         Ok(Self{
             meta,
-            fname,
-            fname16,
+            resource,
         })
     }
 
-    pub fn face_names(&self) -> &[String] {
-        self.meta.face_names()
+    fn info(&self) -> &FontInfo {
+        &self.meta
     }
 
-    pub fn face(&self, name: &str) -> Result<Win32FontFace> {
+    fn face(&self, name: &str) -> Result<Self::Face> {
         // TODO: Some fuzzy match? Substring match?
-        if !self.face_names().iter().any(|n| n == name) {
+        let face_name = match self.meta.file().resolve_face(name) {
+            Some(face_name) => face_name,
             // No such face
-            return Err(Error::UserError(format!("No face named '{}' found in font!", name)));
-        }
+            None => return Err(Error::UserError(format!("No face named '{}' found in font!", name))),
+        };
+        // GDI looks fonts up by their full name, not by the typographic one
+        let ttf = self.meta.file().ttf();
+        let face_name = ttf.preferred_name(4).unwrap_or(face_name);
         // Create the font
-        Win32FontFace::create(name)
+        Win32FontFace::create(face_name, ttf.clone(), self.resource.clone())
+    }
+
+    fn create_context() -> Result<Self::Context> {
+        Ok(Rc::new(Win32RenderContext::create()?))
+    }
+
+    fn scale(face: &Self::Face, settings: &ScaleSettings, context: Option<&Self::Context>) -> Result<Self::Scaled> {
+        // GDI always draws the default instance of variable fonts
+        if !settings.variations.is_empty() {
+            return Err(Error::Unsupported("Font variations on Windows"));
+        }
+        face.scale(settings, context)
     }
 }
 
-impl Drop for Win32Font {
-    fn drop(&mut self) {
-        unsafe{ RemoveFontResourceExW(self.fname16.as_ptr(), FR_PRIVATE, std::ptr::null_mut()) };
-        let _ = std::fs::remove_file(&self.fname);
+// System fonts
+
+/// Collects the enumerated fonts into the `Vec<SystemFontInfo>` passed in
+/// `lparam`.
+unsafe extern "system" fn collect_font(logfont: *const LOGFONTW, _metrics: *const TEXTMETRICW,
+    font_type: DWORD, lparam: LPARAM) -> INT {
+    // Only TrueType and OpenType fonts can be parsed
+    if font_type & TRUETYPE_FONTTYPE != 0 {
+        // For these the structure is extended with the style name
+        let logfont = &*(logfont as *const ENUMLOGFONTEXW);
+        let fonts = &mut *(lparam as *mut Vec<SystemFontInfo>);
+        fonts.push(SystemFontInfo{
+            family: utf16_to_utf8(&logfont.elfLogFont.lfFaceName),
+            style: utf16_to_utf8(&logfont.elfStyle),
+        });
+    }
+    // Continue the enumeration
+    1
+}
+
+/// Enumerates the styles of the given font family, or a single style of every
+/// family if it's empty.
+fn enum_fonts(dc: &DeviceContext, family: &str) -> Vec<SystemFontInfo> {
+    let mut logfont = LOGFONTW::new();
+    logfont.lfCharSet = DEFAULT_CHARSET as BYTE;
+    // Always leave room for the terminator
+    let family16 = utf8_to_utf16(family);
+    let len = std::cmp::min(family16.len(), LF_FACESIZE - 1);
+    logfont.lfFaceName[..len].copy_from_slice(&family16[..len]);
+    let mut fonts = Vec::new();
+    unsafe{ EnumFontFamiliesExW(dc.0, &mut logfont, Some(collect_font),
+        &mut fonts as *mut Vec<SystemFontInfo> as LPARAM, 0) };
+    fonts
+}
+
+/// Lists every style of the installed font families.
+pub fn system_fonts() -> Result<Vec<SystemFontInfo>> {
+    let dc = DeviceContext(unsafe{ CreateCompatibleDC(std::ptr::null_mut()) });
+    if dc.is_err() {
+        return Err(Error::SystemError("Failed to create Device Context!".into()));
     }
+    let mut families: Vec<_> = enum_fonts(&dc, "").into_iter()
+        .map(|f| f.family)
+        // The '@' prefixed families are the vertical variants of the others
+        .filter(|f| !f.starts_with('@'))
+        .collect();
+    families.sort();
+    families.dedup();
+    let mut fonts: Vec<_> = families.iter()
+        .flat_map(|f| enum_fonts(&dc, f))
+        .collect();
+    // Fonts are reported once for every character set they support
+    fonts.sort();
+    fonts.dedup();
+    Ok(fonts)
 }
 
+/// Reads the file of the regular style of an installed font family.
+pub fn system_font_bytes(family: &str) -> Result<Vec<u8>> {
+    let dc = DeviceContext(unsafe{ CreateCompatibleDC(std::ptr::null_mut()) });
+    if dc.is_err() {
+        return Err(Error::SystemError("Failed to create Device Context!".into()));
+    }
+    let font = GdiObject(unsafe{ CreateFontW(0, 0,
+        0, 0, FW_NORMAL, 0, 0, 0,
+        DEFAULT_CHARSET, OUT_DEFAULT_PRECIS, CLIP_DEFAULT_PRECIS, DEFAULT_QUALITY,
+        DEFAULT_PITCH | FF_DONTCARE, utf8_to_utf16(family).as_ptr()) });
+    if font.is_err() {
+        return Err(Error::SystemError("CreateFontW failed!".into()));
+    }
+    if !dc.select(&font) {
+        return Err(Error::SystemError("Failed to assign Font to Device Context!".into()));
+    }
+    // GDI silently substitutes unknown families with another font
+    let mut selected = [0 as WCHAR; LF_FACESIZE];
+    let len = unsafe{ GetTextFaceW(dc.0, LF_FACESIZE as INT, selected.as_mut_ptr()) };
+    if len == 0 || !utf16_to_utf8(&selected).eq_ignore_ascii_case(family) {
+        return Err(Error::UserError(format!("No system font named '{}' found!", family)));
+    }
+    // Collections are only recognized by the tag of their header, the face
+    // can't be parsed from them by itself
+    const TTCF: DWORD = u32::from_le_bytes(*b"ttcf");
+    if unsafe{ GetFontData(dc.0, TTCF, 0, std::ptr::null_mut(), 0) } != GDI_ERROR {
+        return selected_face_bytes(&dc);
+    }
+    let size = unsafe{ GetFontData(dc.0, 0, 0, std::ptr::null_mut(), 0) };
+    if size == GDI_ERROR {
+        return Err(Error::SystemError("GetFontData failed!".into()));
+    }
+    font_data(&dc, 0, 0, size)
+}
+
+/// Reads the given range of a table of the font selected into the device
+/// context, or of the whole font file if the table is 0.
+fn font_data(dc: &DeviceContext, table: DWORD, offset: DWORD, size: DWORD) -> Result<Vec<u8>> {
+    let mut bytes = vec![0u8; size as usize];
+    if unsafe{ GetFontData(dc.0, table, offset, bytes.as_mut_ptr() as PVOID, size) } != size {
+        return Err(Error::SystemError("GetFontData failed!".into()));
+    }
+    Ok(bytes)
+}
+
+/// Reads the tables of the face selected from a font collection into a
+/// standalone font file. For collections GetFontData reads the table
+/// directory of the selected face at 0, but the offsets in it point into the
+/// whole collection, so the tables are read by their tags and laid out anew.
+fn selected_face_bytes(dc: &DeviceContext) -> Result<Vec<u8>> {
+    const HEADER_SIZE: usize = 12;
+    const RECORD_SIZE: usize = 16;
+    let header = font_data(dc, 0, 0, HEADER_SIZE as DWORD)?;
+    let num_tables = u16::from_be_bytes([header[4], header[5]]) as usize;
+    let records = font_data(dc, 0, HEADER_SIZE as DWORD, (num_tables * RECORD_SIZE) as DWORD)?;
+
+    let mut bytes = header;
+    bytes.extend_from_slice(&records);
+    for (i, record) in records.chunks_exact(RECORD_SIZE).enumerate() {
+        let tag = u32::from_le_bytes([record[0], record[1], record[2], record[3]]);
+        let length = u32::from_be_bytes([record[12], record[13], record[14], record[15]]);
+        let offset = bytes.len() as u32;
+        bytes.extend(font_data(dc, tag, 0, length)?);
+        // Tables start on 4 byte boundaries
+        bytes.resize((bytes.len() + 3) & !3, 0);
+        let record_offset = HEADER_SIZE + i * RECORD_SIZE + 8;
+        bytes[record_offset..(record_offset + 4)].copy_from_slice(&offset.to_be_bytes());
+    }
+    Ok(bytes)
+}
+
+// Font face
+
+#[derive(Clone)]
 pub struct Win32FontFace {
-    face_name: String,
+    face_name: String           ,
+    ttf      : Arc<TtfFile>     ,
+    // Keeps the font registered while the face is around
+    _resource: Arc<FontResource>,
 }
 
 impl Win32FontFace {
-    fn create(face_name: &str) -> Result<Self> {
+    fn create(face_name: &str, ttf: Arc<TtfFile>, resource: Arc<FontResource>) -> Result<Self> {
         Ok(Self{
             face_name: face_name.into(),
+            ttf,
+            _resource: resource,
         })
     }
 
-    pub fn scale(&self, pts: f64, dpi: f64) -> Result<Win32ScaledFontFace> {
-        Win32ScaledFontFace::create(&self.face_name, pts, dpi)
+    pub fn scale(&self, settings: &ScaleSettings, context: Option<&Rc<Win32RenderContext>>) -> Result<Win32ScaledFontFace> {
+        let context = match context {
+            Some(context) => context.clone(),
+            None => Rc::new(Win32RenderContext::create()?),
+        };
+        Win32ScaledFontFace::create(&self.face_name, self.ttf.clone(), settings, context)
     }
 }
 
-// Scaled font face
-
-pub struct Win32ScaledFontFace {
-    dc    : DeviceContext,
-    bitmap: GdiObject    ,
-    _font : GdiObject    ,
+// Render context
 
-    buffer: &'static mut[COLORREF],
-    buff_w: usize                 ,
-    buff_h: usize                 ,
+/// A Device Context that can be shared between scaled font faces. Each face
+/// selects its own font and bitmap into it before using it.
+pub struct Win32RenderContext {
+    dc         : DeviceContext,
+    // Selected while no face uses the Device Context, so the bitmaps of the
+    // faces can be deleted
+    placeholder: GdiObject    ,
 }
 
-impl Win32ScaledFontFace {
-    fn create(face: &str, pts: f64, dpi: f64) -> Result<Self> {
+impl Win32RenderContext {
+    fn create() -> Result<Self> {
         // Create Device Context
         let dc = DeviceContext(unsafe{ CreateCompatibleDC(std::ptr::null_mut()) });
         if dc.is_err() {
             return Err(Error::SystemError("Failed to create Device Context!".into()));
         }
-        // Calculate size
-        const POINTS_PER_INCH: f64 = 72.0;
-        let pixels_height = -(pts * dpi / POINTS_PER_INCH) as INT;
-        // Create font
-        let font = GdiObject(unsafe{ CreateFontW(pixels_height, 0,
-            0, 0, FW_NORMAL, 0, 0, 0,
-            DEFAULT_CHARSET, OUT_DEFAULT_PRECIS, CLIP_DEFAULT_PRECIS, ANTIALIASED_QUALITY,
-            DEFAULT_PITCH | FF_DONTCARE, utf8_to_utf16(face).as_ptr()) });
-        if font.is_err() {
-            return Err(Error::SystemError("CreateFontW failed!".into()));
+        let placeholder = GdiObject(unsafe{ CreateCompatibleBitmap(dc.0, 0, 0) });
+        if placeholder.is_err() {
+            return Err(Error::SystemError("Failed to create Bitmap!".into()));
         }
+        Ok(Self{
+            dc,
+            placeholder,
+        })
+    }
+
+    /// Deselects the objects of the faces, as selected objects can't be
+    /// deleted.
+    fn release(&self) {
+        unsafe{ SelectObject(self.dc.0, GetStockObject(SYSTEM_FONT)) };
+        self.dc.select(&self.placeholder);
+    }
+}
+
+// Scaled font face
+
+/// Clears the buffer and fills it with `len` zeroes, keeping its capacity.
+fn reset_buffer<T: Default + Clone>(buffer: &mut Vec<T>, len: usize) {
+    buffer.clear();
+    buffer.resize(len, T::default());
+}
+
+/// Decodes the UTF-16 text along with the range of units of each character,
+/// which is two for the characters outside the Basic Multilingual Plane.
+/// Unpaired surrogates are decoded as U+FFFD.
+fn chars_with_units(text16: &[WCHAR]) -> impl Iterator<Item = (char, std::ops::Range<usize>)> + '_ {
+    let mut unit = 0;
+    std::char::decode_utf16(text16.iter().map(|u| *u as u16)).map(move |c| {
+        let first = unit;
+        unit += c.as_ref().map_or(1, |c| c.len_utf16());
+        (c.unwrap_or(std::char::REPLACEMENT_CHARACTER), first..unit)
+    })
+}
+
+/// Returns the glyph a character got placed as by GetCharacterPlacementW,
+/// and its advance, from the placement order and advances of the units. The
+/// character is placed at its first unit, the trailing unit of a surrogate
+/// pair might have been placed as a separate glyph.
+fn char_placement(order: &[UINT], dx: &[INT], units: std::ops::Range<usize>) -> (usize, INT) {
+    let glyph = order[units.start] as usize;
+    let advance = dx[glyph] + order[(units.start + 1)..units.end].iter()
+        .map(|g| *g as usize)
+        .filter(|g| *g != glyph)
+        .map(|g| dx[g])
+        .sum::<INT>();
+    (glyph, advance)
+}
+
+/// Buffers reused between the calls to `shape_text`, grown as needed.
+#[derive(Default)]
+struct ShapeScratch {
+    text16   : Vec<WCHAR>,
+    glyphs   : Vec<WCHAR>,
+    dx       : Vec<INT>  ,
+    order    : Vec<UINT> ,
+    caret_pos: Vec<INT>  ,
+    glyph_ids: Vec<u16>  ,
+}
+
+pub struct Win32ScaledFontFace {
+    #[cfg(feature = "directwrite")]
+    dwrite    : Option<DWriteShaper>  ,
+    context   : Rc<Win32RenderContext>,
+    buffer    : DibSection            ,
+    font      : GdiObject             ,
+    face_name : String                ,
+    ttf       : Arc<TtfFile>          ,
+    // The size of the em square in pixels
+    em_size   : f64                   ,
+    quality   : RenderQuality         ,
+    colors    : RenderColors          ,
+    // The rotation of the text in tenths of a degree, counterclockwise
+    escapement: i32                   ,
+    // The horizontal scale of the glyphs
+    stretch   : f64                   ,
+    // Whether the rows of the bitmaps are stored bottom-up, like the ones of
+    // the DIB section
+    flip_y    : bool                  ,
+    // The line height and the ascent in pixels
+    metrics   : (i32, i32)            ,
+    // The advance and line height of every glyph, if the font is monospaced
+    mono      : Option<(i32, i32)>    ,
+    scratch   : RefCell<ShapeScratch> ,
+}
+
+impl Win32ScaledFontFace {
+    fn create(face: &str, ttf: Arc<TtfFile>, settings: &ScaleSettings, context: Rc<Win32RenderContext>) -> Result<Self> {
+        let &ScaleSettings{ pts, dpi, quality, colors, escapement, stretch, flip_y, .. } = settings;
+        let dc = &context.dc;
+        let em_size = Self::em_size(pts, dpi);
+        let font = Self::create_font(&context, face, em_size, quality, escapement, stretch)?;
         // Select the font for the Device Context
         if !dc.select(&font) {
             return Err(Error::SystemError("Failed to assign Font to Device Context!".into()));
         }
+        let metrics = match Self::check_selected_face(dc, face).and_then(|_| Self::line_metrics(dc)) {
+            Ok(metrics) => metrics,
+            Err(err) => {
+                context.release();
+                return Err(err);
+            },
+        };
         // Create bitmap
         // TODO: Size
         let bitmap = GdiObject(unsafe{ CreateCompatibleBitmap(dc.0, 0, 0) });
         if bitmap.is_err() {
+            context.release();
             return Err(Error::SystemError("Failed to create Bitmap!".into()));
         }
         // Select the bitmap for the Device Context
         if !dc.select(&bitmap) {
+            context.release();
             return Err(Error::SystemError("Failed to assign Bitmap to Device Context!".into()));
         }
+        // DirectWrite is optional, we can fall back to GDI for shaping, which
+        // also takes care of stretched fonts
+        #[cfg(feature = "directwrite")]
+        let dwrite = if stretch == 1.0 { DWriteShaper::create(dc, em_size as f32).ok() } else { None };
+        let mono = Self::mono_metrics(dc, &ttf);
         // We succeeded in creating everything
         Ok(Self{
-            dc,
-            bitmap,
-            _font: font,
-
-            buffer: unsafe{ std::slice::from_raw_parts_mut(std::ptr::NonNull::dangling().as_ptr(), 0) },
-            buff_w: 0,
-            buff_h: 0,
+            #[cfg(feature = "directwrite")]
+            dwrite,
+            context,
+            buffer: DibSection::empty(bitmap),
+            font,
+            face_name: face.into(),
+            ttf,
+            em_size,
+            quality,
+            colors,
+            escapement,
+            stretch,
+            flip_y,
+            metrics,
+            mono,
+            scratch: RefCell::default(),
         })
     }
 
+    /// Calculates the size of the em square in pixels.
+    fn em_size(pts: f64, dpi: f64) -> f64 {
+        const POINTS_PER_INCH: f64 = 72.0;
+        pts * dpi / POINTS_PER_INCH
+    }
+
+    /// Creates the GDI font of the face with the given settings. Stretched
+    /// fonts are measured with the Device Context, and left selected into it.
+    fn create_font(context: &Win32RenderContext, face: &str, em_size: f64, quality: RenderQuality,
+        escapement: i32, stretch: f64) -> Result<GdiObject> {
+        let font = Self::create_font_with_width(face, em_size, quality, escapement, 0)?;
+        if stretch == 1.0 {
+            return Ok(font);
+        }
+        // The width is the average character width, so it's scaled from the
+        // one of the unstretched font
+        let dc = &context.dc;
+        if !dc.select(&font) {
+            return Err(Error::SystemError("Failed to assign Font to Device Context!".into()));
+        }
+        let mut metrics = TEXTMETRICW::new();
+        if unsafe{ GetTextMetricsW(dc.0, &mut metrics) } == 0 {
+            context.release();
+            return Err(Error::SystemError("GetTextMetricsW failed!".into()));
+        }
+        let width = ((metrics.tmAveCharWidth as f64 * stretch).round() as INT).max(1);
+        let stretched = match Self::create_font_with_width(face, em_size, quality, escapement, width) {
+            Ok(stretched) => stretched,
+            Err(err) => {
+                context.release();
+                return Err(err);
+            },
+        };
+        // Deselect the unstretched font, so it can be deleted
+        if !dc.select(&stretched) {
+            context.release();
+            return Err(Error::SystemError("Failed to assign Font to Device Context!".into()));
+        }
+        Ok(stretched)
+    }
+
+    /// Creates the GDI font of the face with the given average character
+    /// width, or the natural one if it's zero.
+    fn create_font_with_width(face: &str, em_size: f64, quality: RenderQuality, escapement: i32,
+        width: INT) -> Result<GdiObject> {
+        let pixels_height = -em_size as INT;
+        let font_quality = match quality {
+            RenderQuality::Aliased => NONANTIALIASED_QUALITY,
+            RenderQuality::Antialiased => ANTIALIASED_QUALITY,
+            RenderQuality::ClearType => CLEARTYPE_QUALITY,
+        };
+        let font = GdiObject(unsafe{ CreateFontW(pixels_height, width,
+            escapement, escapement, FW_NORMAL, 0, 0, 0,
+            DEFAULT_CHARSET, OUT_DEFAULT_PRECIS, CLIP_DEFAULT_PRECIS, font_quality,
+            DEFAULT_PITCH | FF_DONTCARE, utf8_to_utf16(face).as_ptr()) });
+        if font.is_err() {
+            return Err(Error::SystemError("CreateFontW failed!".into()));
+        }
+        Ok(font)
+    }
+
+    /// Checks that GDI selected the font of the face, and didn't substitute
+    /// another one for it. This happens if the face name wasn't read from the
+    /// font, but synthesized for a font without names.
+    fn check_selected_face(dc: &DeviceContext, face: &str) -> Result<()> {
+        let mut selected = [0 as WCHAR; LF_FACESIZE];
+        let len = unsafe{ GetTextFaceW(dc.0, LF_FACESIZE as INT, selected.as_mut_ptr()) };
+        if len == 0 {
+            return Err(Error::SystemError("GetTextFaceW failed!".into()));
+        }
+        // GDI truncates longer face names
+        let expected: Vec<u16> = face.encode_utf16().take(LF_FACESIZE - 1).collect();
+        let expected = String::from_utf16_lossy(&expected);
+        let selected = utf16_to_utf8(&selected);
+        if !selected.eq_ignore_ascii_case(&expected) {
+            return Err(Error::SystemError(
+                format!("GDI selected the font '{}' instead of the face '{}'!", selected, face)));
+        }
+        Ok(())
+    }
+
+    /// Measures the advance and line height of every glyph with the selected
+    /// font, if the font is monospaced. These fonts can skip the placement
+    /// calculation for simple text.
+    /// Measures the line height and the ascent of the font selected into the
+    /// Device Context.
+    fn line_metrics(dc: &DeviceContext) -> Result<(i32, i32)> {
+        let space = [' ' as WCHAR];
+        let mut size = SIZE::new();
+        if unsafe{ GetTextExtentPoint32W(dc.0, space.as_ptr(), 1, &mut size) } == 0 {
+            return Err(Error::SystemError("GetTextExtentPoint32W failed!".into()));
+        }
+        let mut metrics = TEXTMETRICW::new();
+        if unsafe{ GetTextMetricsW(dc.0, &mut metrics) } == 0 {
+            return Err(Error::SystemError("GetTextMetricsW failed!".into()));
+        }
+        Ok((size.cy, metrics.tmAscent))
+    }
+
+    fn mono_metrics(dc: &DeviceContext, ttf: &TtfFile) -> Option<(i32, i32)> {
+        if !ttf.is_fixed_pitch() {
+            return None;
+        }
+        let space = [' ' as WCHAR];
+        let mut size = SIZE::new();
+        let ok = unsafe{ GetTextExtentPoint32W(dc.0, space.as_ptr(), 1, &mut size) };
+        if ok != 0 { Some((size.cx, size.cy)) } else { None }
+    }
+
     fn ensure_buffer_size(&mut self, width: usize, height: usize) -> Result<()> {
-        if self.buff_w >= width && self.buff_h >= height {
+        if self.buffer.width() >= width && self.buffer.height() >= height {
             // Already enough
             return Ok(());
         }
-        // Calculate new size
-        let width = std::cmp::max(width, self.buff_w);
-        let height = std::cmp::max(height, self.buff_h);
+        // Calculate new size, absurd extents get clipped
+        let width = std::cmp::max(width, self.buffer.width()).min(MAX_BITMAP_SIZE);
+        let height = std::cmp::max(height, self.buffer.height()).min(MAX_BITMAP_SIZE);
         // Need to resize
-        let mut info = BITMAPINFO::new();
-        info.bmiHeader.biWidth = width as _;
-        info.bmiHeader.biHeight = height as _;
-        info.bmiHeader.biPlanes = 1;
-        info.bmiHeader.biBitCount = 32;
-        info.bmiHeader.biCompression = BI_RGB;
-        info.bmiHeader.biSizeImage = 0;
-        info.bmiHeader.biXPelsPerMeter = 0;
-        info.bmiHeader.biYPelsPerMeter = 0;
-        info.bmiHeader.biClrUsed = 0;
-        info.bmiHeader.biClrImportant = 0;
-        let mut bits: PVOID = std::ptr::null_mut();
-        let bitmap = GdiObject(unsafe{ CreateDIBSection(self.dc.0, &info, DIB_RGB_COLORS, &mut bits, std::ptr::null_mut(), 0) });
-        if bitmap.is_err() {
-            return Err(Error::SystemError("Failed to create Bitmap!".into()));
-        }
+        let buffer = DibSection::create(&self.context.dc, width, height)?;
         // Select the bitmap for the Device Context
-        if !self.dc.select(&bitmap) {
-            return Err(Error::SystemError("Failed to assign font to Device Context!".into()));
+        if !self.context.dc.select(&buffer.bitmap) {
+            return Err(Error::SystemError("Failed to assign Bitmap to Device Context!".into()));
         }
         // Succeeded, delete old bitmap and swap
-        self.bitmap = bitmap;
-        self.buff_w = width;
-        self.buff_h = height;
-        self.buffer = unsafe{ std::slice::from_raw_parts_mut(bits as _, width * height) };
+        self.buffer = buffer;
         Ok(())
     }
 
+    /// Selects the font and the bitmap of the face into the Device Context,
+    /// which might have been used by another face since.
+    fn select(&self) -> bool {
+        self.context.dc.select(&self.font) && self.context.dc.select(&self.buffer.bitmap)
+    }
+
+    /// Rotates a vector of unrotated text by the escapement. The y axis
+    /// points down, so the rotation is clockwise in device coordinates.
+    fn rotate(&self, x: f64, y: f64) -> (f64, f64) {
+        if self.escapement == 0 {
+            return (x, y);
+        }
+        let (sin, cos) = (self.escapement as f64 / 10.0).to_radians().sin_cos();
+        (x * cos + y * sin, y * cos - x * sin)
+    }
+
+    /// Returns the smallest and largest corner of the bounding box of a
+    /// rectangle of unrotated text with its top left corner at the origin,
+    /// after the rotation.
+    fn rotated_bounds(&self, width: i32, height: i32) -> ((i32, i32), (i32, i32)) {
+        let corners = [(0, 0), (width, 0), (0, height), (width, height)];
+        let mut min = (f64::INFINITY, f64::INFINITY);
+        let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for (x, y) in corners.iter() {
+            let (x, y) = self.rotate(*x as f64, *y as f64);
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        ((min.0.floor() as i32, min.1.floor() as i32), (max.0.ceil() as i32, max.1.ceil() as i32))
+    }
+
+    /// Rotates the position of a shaped glyph and its caret by the escapement.
+    fn rotate_glyph(&self, mut glyph: GlyphPositioning) -> GlyphPositioning {
+        if self.escapement == 0 {
+            return glyph;
+        }
+        let (x, y) = self.rotate(glyph.x as f64 + glyph.x_fraction as f64, glyph.y as f64);
+        glyph.x = x.floor() as i32;
+        glyph.y = y.round() as i32;
+        glyph.x_fraction = (x - x.floor()) as f32;
+        let (caret_x, caret_y) = self.rotate(glyph.caret_x as f64, glyph.caret_y as f64);
+        glyph.caret_x = caret_x.round() as i32;
+        glyph.caret_y = caret_y.round() as i32;
+        glyph
+    }
+
+    /// Rotates the positions the unrotated shaping reports by the escapement,
+    /// then moves them into the bounding box of the rotated text, so they
+    /// aren't negative. Returns the size of the box.
+    fn shape_rotated(&self, f: &mut dyn FnMut(GlyphPositioning),
+        shape: impl FnOnce(&mut dyn FnMut(GlyphPositioning)) -> Result<(i32, i32)>) -> Result<(i32, i32)> {
+        if self.escapement == 0 {
+            return shape(f);
+        }
+        // The box is only known after every glyph got positioned
+        let mut glyphs = Vec::new();
+        let (width, height) = shape(&mut |g| glyphs.push(self.rotate_glyph(g)))?;
+        let ((min_x, min_y), (max_x, max_y)) = self.rotated_bounds(width, height);
+        for mut glyph in glyphs {
+            glyph.x -= min_x;
+            glyph.y -= min_y;
+            glyph.caret_x -= min_x;
+            glyph.caret_y -= min_y;
+            f(glyph);
+        }
+        Ok((max_x - min_x, max_y - min_y))
+    }
+
     fn tightest_bounds(&self) -> Bounds {
         let mut result = Bounds::default();
+        let buff_w = self.buffer.width();
+        let buff_h = self.buffer.height();
 
         // Find left bound
         result.left = 0;
-        'outer1: for x in 0..self.buff_w {
-            for y in 0..self.buff_h {
-                if self.buffer[y * self.buff_w + x] != 0 {
+        'outer1: for x in 0..buff_w {
+            for y in 0..buff_h {
+                if !self.is_background(self.buffer.pixel(x, y)) {
                     break 'outer1;
                 }
             }
             result.left = x + 1;
         }
         // Find right bound
-        result.right = self.buff_w;
-        'outer2: for x in (0..self.buff_w).rev() {
-            for y in 0..self.buff_h {
-                if self.buffer[y * self.buff_w + x] != 0 {
+        result.right = buff_w;
+        'outer2: for x in (0..buff_w).rev() {
+            for y in 0..buff_h {
+                if !self.is_background(self.buffer.pixel(x, y)) {
                     break 'outer2;
                 }
             }
@@ -262,19 +1200,19 @@ impl Win32ScaledFontFace {
         }
         // Find top bound
         result.top = 0;
-        'outer3: for y in 0..self.buff_h {
-            for x in 0..self.buff_w {
-                if self.buffer[y * self.buff_w + x] != 0 {
+        'outer3: for y in 0..buff_h {
+            for x in 0..buff_w {
+                if !self.is_background(self.buffer.pixel(x, y)) {
                     break 'outer3;
                 }
             }
             result.top = y + 1;
         }
         // Find bottom bound
-        result.bottom = self.buff_h;
-        'outer4: for y in (0..self.buff_h).rev() {
-            for x in 0..self.buff_w {
-                if self.buffer[y * self.buff_w + x] != 0 {
+        result.bottom = buff_h;
+        'outer4: for y in (0..buff_h).rev() {
+            for x in 0..buff_w {
+                if !self.is_background(self.buffer.pixel(x, y)) {
                     break 'outer4;
                 }
             }
@@ -284,78 +1222,160 @@ impl Win32ScaledFontFace {
         result
     }
 
-    pub fn rasterize_glyph(&mut self, codepoint: char) -> Result<RasterizedGlyph> {
-        // Convert to UTF16
-        let utf16str = utf8_to_utf16(&format!("{}", codepoint));
+    /// Draws the given UTF-16 units, or glyph indices if `by_index` is true,
+    /// into the buffer. Returns the tightest bounds of the drawn pixels in the
+    /// buffer along with the offset of their top left corner from the drawing
+    /// position, or none if nothing got drawn.
+    fn draw(&mut self, codepoint: char, units: &[WCHAR], by_index: bool) -> Result<Option<(Bounds, i32, i32)>> {
+        if !self.select() {
+            return Err(Error::SystemError("Failed to select the Font into the Device Context!".into()));
+        }
         // Get coordinates
         let mut size = SIZE::new();
-        if unsafe{ GetTextExtentPoint32W(self.dc.0, utf16str.as_ptr(), utf16str.len() as _, &mut size) } == 0 {
+        if by_index {
+            if unsafe{ GetTextExtentPointI(self.context.dc.0, units.as_ptr() as _, units.len() as _, &mut size) } == 0 {
+                return Err(Error::SystemError("GetTextExtentPointI failed!".into()));
+            }
+        }
+        else if unsafe{ GetTextExtentPoint32W(self.context.dc.0, units.as_ptr(), units.len() as _, &mut size) } == 0 {
             return Err(Error::GlyphNotFound(codepoint));
         }
-        let required_width = size.cx as usize;
-        let required_height = size.cy as usize;
+        // Rotated text is drawn from an origin that keeps it inside the bitmap
+        let ((min_x, min_y), (max_x, max_y)) = self.rotated_bounds(size.cx.max(0), size.cy.max(0));
+        let (origin_x, origin_y) = (-min_x, -min_y);
+        let required_width = (max_x - min_x) as usize;
+        let required_height = (max_y - min_y) as usize;
         // Ensure buffer size
         self.ensure_buffer_size(required_width, required_height)?;
         // Set clear behavior
-        if unsafe{ SetBkMode(self.dc.0, TRANSPARENT) } == 0 {
+        if unsafe{ SetBkMode(self.context.dc.0, TRANSPARENT) } == 0 {
             return Err(Error::SystemError("SetBkMode failed!".into()));
         }
         // Clear the bitmap
-        unsafe{ PatBlt(self.dc.0, 0, 0, self.buff_w as INT, self.buff_h as INT, BLACKNESS) };
-        // Set text color
-        if unsafe{ SetTextColor(self.dc.0, 0x00ffffff) } == CLR_INVALID {
+        let buff_w = self.buffer.width();
+        let buff_h = self.buffer.height();
+        let background = Self::to_pixel(self.colors.background);
+        for pixel in self.buffer.pixels_mut() {
+            *pixel = background;
+        }
+        // Set text color, COLORREFs are stored in reverse order
+        let [r, g, b] = self.colors.text;
+        if unsafe{ SetTextColor(self.context.dc.0, Self::to_pixel([b, g, r])) } == CLR_INVALID {
             return Err(Error::SystemError("SetTextColor failed!".into()));
         }
         // Render to bitmap
-        if unsafe{ TextOutW(self.dc.0, 0, 0, utf16str.as_ptr(), utf16str.len() as _) } == 0 {
-            return Err(Error::SystemError("TextOutW failed!".into()));
-        }
-        // Invert the rows for easier copy (the buffer contents is upside down)
-        for y in 0..(self.buff_h / 2) {
-            let y_inv = self.buff_h - y - 1;
-            for x in 0..self.buff_w {
-                self.buffer.swap(
-                    y * self.buff_w + x,
-                    y_inv * self.buff_w + x);
+        let options = if by_index { ETO_GLYPH_INDEX } else { 0 };
+        if unsafe{ ExtTextOutW(self.context.dc.0, origin_x, origin_y, options, std::ptr::null(),
+            units.as_ptr(), units.len() as _, std::ptr::null()) } == 0 {
+            return Err(Error::SystemError("ExtTextOutW failed!".into()));
+        }
+        // The buffer contents is upside down, which is what flipped faces store
+        if !self.flip_y {
+            let pixels = self.buffer.pixels_mut();
+            for y in 0..(buff_h / 2) {
+                let y_inv = buff_h - y - 1;
+                for x in 0..buff_w {
+                    pixels.swap(
+                        y * buff_w + x,
+                        y_inv * buff_w + x);
+                }
             }
         }
         // Calculate the tightest bounds
         let bounds = self.tightest_bounds();
         if bounds.left > bounds.right {
+            // The canvas must be empty
+            return Ok(None);
+        }
+        // Upside down the top of the ink is at the bottom of the bounds
+        let top = if self.flip_y { buff_h - bounds.bottom } else { bounds.top };
+        Ok(Some((bounds, bounds.left as i32 - origin_x, top as i32 - origin_y)))
+    }
+
+    /// Copies the coverage of the buffer within the bounds into an 8-bit
+    /// grayscale bitmap, to the given position.
+    fn copy_coverage(&self, bounds: &Bounds, dst: &mut [u8], dst_width: usize, x: usize, y: usize) {
+        for by in 0..(bounds.bottom - bounds.top) {
+            let dst_offs = (y + by) * dst_width + x;
+            for bx in 0..(bounds.right - bounds.left) {
+                let pixel = self.buffer.pixel(bounds.left + bx, bounds.top + by);
+                dst[dst_offs + bx] = self.coverage(pixel);
+            }
+        }
+    }
+
+    /// Rasterizes the given UTF-16 units, or glyph indices if `by_index` is
+    /// true.
+    fn rasterize(&mut self, codepoint: char, units: &[WCHAR], by_index: bool) -> Result<RasterizedGlyph> {
+        let (bounds, x_offset, y_offset) = match self.draw(codepoint, units, by_index)? {
+            Some(drawn) => drawn,
             // The canvas must be empty, return empty canvas
-            return Ok(RasterizedGlyph{
+            None => return Ok(RasterizedGlyph{
                 character: codepoint,
                 x_offset: 0,
                 y_offset: 0,
                 width: 0,
                 height: 0,
+                bit_depth: BitDepth::Eight,
+                cell: GlyphBox::default(),
+                baseline: 0,
                 data: vec![0u8; 0].into_boxed_slice(),
-            });
-        }
+            }),
+        };
         let bounds_width = bounds.right - bounds.left;
         let bounds_height = bounds.bottom - bounds.top;
         // Create the resulting buffer
-        let mut data = vec![0u8; (bounds_width * bounds_height) as usize].into_boxed_slice();
+        let len = bounds_width.checked_mul(bounds_height)
+            .ok_or_else(|| Error::SystemError("Glyph bitmap size overflows!".into()))?;
+        let mut data = vec![0u8; len].into_boxed_slice();
         // Copy the data to the buffer
-        for y in 0..bounds_height {
-            let y_buff_offs = (y + bounds.top) * self.buff_w;
-            let y_res_offs = y * bounds_width;
-            for x in 0..bounds_width {
-                let pixel = self.buffer[y_buff_offs + bounds.left + x];
-                data[y_res_offs + x] = (pixel & 0xff) as u8;
-            }
-        }
+        self.copy_coverage(&bounds, &mut data, bounds_width, 0, 0);
         // We succeeded
         Ok(RasterizedGlyph{
             character: codepoint,
-            x_offset: bounds.left as i32,
-            y_offset: bounds.top as i32,
+            x_offset,
+            y_offset,
             width: bounds_width,
             height: bounds_height,
+            bit_depth: BitDepth::Eight,
+            cell: GlyphBox::default(),
+            baseline: 0,
             data,
         })
     }
 
+    /// Converts an RGB color to a pixel of the bitmap.
+    fn to_pixel([r, g, b]: [u8; 3]) -> COLORREF {
+        (r as COLORREF) << 16 | (g as COLORREF) << 8 | b as COLORREF
+    }
+
+    /// Returns true if the pixel has the background color.
+    fn is_background(&self, pixel: COLORREF) -> bool {
+        pixel & 0x00ffffff == Self::to_pixel(self.colors.background)
+    }
+
+    /// Converts a pixel of text drawn over the background to coverage.
+    fn coverage(&self, pixel: COLORREF) -> u8 {
+        // How far each channel got from the background towards the text color.
+        // With ClearType they hold the coverage of the subpixels.
+        let text = self.colors.text;
+        let background = self.colors.background;
+        let (sum, count) = [16, 8, 0].iter().enumerate()
+            .filter(|(i, _)| text[*i] != background[*i])
+            .map(|(i, shift)| {
+                let channel = ((pixel >> shift) & 0xff) as i32;
+                let range = text[i] as i32 - background[i] as i32;
+                ((channel - background[i] as i32) * 255 / range).clamp(0, 255)
+            })
+            .fold((0, 0), |(sum, count), c| (sum + c, count + 1));
+        let coverage = if count == 0 { 0 } else { (sum / count) as u8 };
+        match self.quality {
+            // Make sure the edges are hard
+            RenderQuality::Aliased => if coverage >= 0x80 { 0xff } else { 0 },
+            RenderQuality::Antialiased | RenderQuality::ClearType => coverage,
+        }
+    }
+
     fn translate_flags(flags: ShapeOptions) -> DWORD {
         let mut result: DWORD = 0;
         if flags.contains(ShapeOptions::USE_KERNING) {
@@ -364,24 +1384,135 @@ impl Win32ScaledFontFace {
         result
     }
 
-    pub fn shape_text<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions, mut f: F) -> (i32, i32) {
-        // Encode in UTF16
-        let text16 = utf8_to_utf16(text);
+    /// Shapes the text using DirectWrite. Returns None if shaping failed, in
+    /// which case the callback is not invoked at all.
+    #[cfg(feature = "directwrite")]
+    fn shape_text_dwrite(&self, shaper: &DWriteShaper, text: &str, options: ShapeOptions,
+        f: &mut dyn FnMut(GlyphPositioning)) -> Option<(i32, i32)> {
+        // Shape every line before reporting anything
+        let mut lines = Vec::new();
+        for line in text.split('\n') {
+            let line16: Vec<WCHAR> = line.encode_utf16().map(|u| u as WCHAR).collect();
+            lines.push((line, shaper.shape_line(&line16, options)?));
+        }
+        let line_height = self.line_height();
+        let line_count = lines.len();
+
+        let mut max_w = 0;
+        let mut index = 0;
+        let mut yoff = 0;
+        for (i, (line, (placements, width))) in lines.into_iter().enumerate() {
+            let mut unit = 0;
+            for ch in line.chars() {
+                let p = placements[unit];
+                unit += ch.len_utf16();
+                index += 1;
+                if !p.cluster_start {
+                    // Merged into the glyph of the preceding character
+                    continue;
+                }
+                f(GlyphPositioning{
+                    character: ch,
+                    index: index - 1,
+                    cluster: index - 1,
+                    glyph_id: p.glyph_id,
+                    x: p.x.floor() as i32,
+                    y: yoff + p.y.round() as i32,
+                    x_fraction: p.x - p.x.floor(),
+                    caret_x: p.caret_x.round() as i32,
+                    caret_y: yoff,
+                });
+            }
+            let width = width.round() as i32;
+            max_w = std::cmp::max(max_w, width);
+            if i + 1 < line_count {
+                // Report the newline that got split off
+                f(GlyphPositioning{
+                    character: '\n',
+                    index,
+                    cluster: index,
+                    glyph_id: 0,
+                    x: width,
+                    y: yoff,
+                    x_fraction: 0.0,
+                    caret_x: width,
+                    caret_y: yoff,
+                });
+                index += 1;
+                yoff += line_height;
+            }
+        }
+        Some((max_w, yoff + line_height))
+    }
+
+    /// Lays out a single line of printable ASCII text in a monospaced font
+    /// from the cached advance, without calling into GDI. The result is the
+    /// same as the placement GDI would calculate. Returns None if the text
+    /// needs the full placement, in which case the callback is not invoked.
+    fn shape_text_monospace(&self, text: &str, options: ShapeOptions,
+        f: &mut dyn FnMut(GlyphPositioning)) -> Option<(i32, i32)> {
+        let (advance, line_height) = self.mono?;
+        if options.contains(ShapeOptions::USE_KERNING) || !text.bytes().all(|b| (b' '..=b'~').contains(&b)) {
+            return None;
+        }
+        // Marks are positioned relative to their base glyph
+        if self.ttf.has_mark_to_base() {
+            return None;
+        }
+        let glyph_ids = text.chars()
+            .map(|c| self.ttf.glyph_index(c))
+            .collect::<Option<Vec<_>>>()?;
+        if options.contains(ShapeOptions::USE_LIGATURES)
+            && (0..glyph_ids.len()).any(|i| self.ttf.ligature(&glyph_ids[i..]).is_some()) {
+            return None;
+        }
+        for (i, (ch, glyph_id)) in text.chars().zip(glyph_ids).enumerate() {
+            let x = i as i32 * advance;
+            f(GlyphPositioning{
+                character: ch,
+                index: i,
+                cluster: i,
+                glyph_id,
+                x,
+                y: 0,
+                x_fraction: 0.0,
+                caret_x: x,
+                caret_y: 0,
+            });
+        }
+        if text.is_empty() {
+            return Some((0, 0));
+        }
+        Some((text.len() as i32 * advance, line_height))
+    }
+
+    /// Lays out the UTF-16 text in `scratch.text16` with the placement GDI
+    /// calculates. Unpaired surrogates are reported as U+FFFD.
+    fn shape_text_gdi(&self, scratch: &mut ShapeScratch, options: ShapeOptions,
+        f: &mut dyn FnMut(GlyphPositioning)) -> Result<(i32, i32)> {
+        // The API expects the number of UTF-16 units, without the terminator
+        let count = scratch.text16.len();
+        scratch.text16.push(0);
+        let len = scratch.text16.len();
         // Prepare parameters
         let flags = Self::translate_flags(options);
         let mut results = GCP_RESULTSW::new();
-        let mut glyphs = vec![0i16; text16.len()].into_boxed_slice();
-        let mut dx = vec![0i32; text16.len()].into_boxed_slice();
-        let mut order = vec![0u32; text16.len()].into_boxed_slice();
-        let mut caret_pos = vec![0i32; text16.len()].into_boxed_slice();
+        let ShapeScratch{ text16, glyphs, dx, order, caret_pos, glyph_ids } = scratch;
+        reset_buffer(glyphs, len);
+        reset_buffer(dx, len);
+        reset_buffer(order, len);
+        reset_buffer(caret_pos, len);
         results.lpGlyphs = glyphs.as_mut_ptr();
-        results.nGlyphs = text.len() as DWORD;
+        results.nGlyphs = count as DWORD;
         results.lpDx = dx.as_mut_ptr();
         results.lpOrder = order.as_mut_ptr();
         results.lpCaretPos = caret_pos.as_mut_ptr();
         // Invoke placement calculation
-        let res = unsafe{ GetCharacterPlacementW(self.dc.0,
-            text16.as_ptr(), text.len() as INT, 0, &mut results, flags) };
+        if !self.select() {
+            return Err(Error::SystemError("Failed to select the Font into the Device Context!".into()));
+        }
+        let res = unsafe{ GetCharacterPlacementW(self.context.dc.0,
+            text16.as_ptr(), count as INT, 0, &mut results, flags) };
         // The resulting dimensions
         let _res_w = (res & 0x0000ffff) as usize;
         let res_h = ((res & 0xffff0000) >> 16) as usize;
@@ -391,48 +1522,238 @@ impl Win32ScaledFontFace {
         let mut max_w = 0;
         let mut max_h = 0;
 
+        // The glyph of every character in order, for ligature substitution. A
+        // character can take up two UTF-16 units, it's placed at the first one.
+        glyph_ids.clear();
+        glyph_ids.extend(chars_with_units(&text16[..count])
+            .map(|(_, units)| glyphs[order[units.start] as usize] as u16));
+        let use_ligatures = options.contains(ShapeOptions::USE_LIGATURES);
+        // GDI only kerns with the legacy 'kern' table, fonts without one are
+        // kerned with the 'GPOS' table here
+        let use_gpos_kerning = options.contains(ShapeOptions::USE_KERNING) && !self.ttf.has_kern_table();
+        // For converting design units to pixels
+        let design_scale = self.em_size / self.ttf.units_per_em() as f64;
+        let to_pixels = |units: i32| (units as f64 * design_scale).round() as i32;
+        // Horizontal distances are stretched along with the glyphs
+        let to_pixels_x = |units: i32| (units as f64 * design_scale * self.stretch).round() as i32;
+
         // Cursor
         let mut xoff = 0;
         let mut yoff = 0;
         // Loop through characters, move cursor along
-        let mut chs = text.chars();
         let mut caret_neg = 0;
         let mut prev_newline = false;
-        for i in 0..results.nGlyphs {
-            // Get the advance width
-            let order = unsafe{ *results.lpOrder.offset(i as isize) };
-            let offs = unsafe{ *results.lpDx.offset(order as isize) };
-            let caret_offs = unsafe{ *results.lpCaretPos.offset(order as isize) };
-            if let Some(ch) = chs.next() {
-                if prev_newline {
-                    caret_neg = caret_offs;
-                    prev_newline = false;
-                }
-                let gp = GlyphPositioning{
-                    character: ch,
-                    index: i as usize,
-                    x: xoff,
-                    y: yoff,
-                    caret_x: caret_offs - caret_neg,
-                    caret_y: yoff,
-                };
-                f(gp);
+        // The number of upcoming characters merged into a ligature
+        let mut merged = 0;
+        // The last base glyph and its position, marks are attached to it
+        let mut base: Option<(u16, i32, i32)> = None;
+        // The kerning added on the current line so far
+        let mut kerning = 0;
+        for (i, (ch, units)) in chars_with_units(&text16[..count]).enumerate() {
+            let (glyph, offs) = char_placement(order, dx, units);
+            let caret_offs = caret_pos[glyph];
+            if merged > 0 {
+                // This character is part of the preceding ligature
+                merged -= 1;
                 xoff += offs;
-                if ch == '\n' {
-                    prev_newline = true;
-                    xoff = 0;
-                    yoff += line_height;
+                max_w = std::cmp::max(max_w, xoff);
+                continue;
+            }
+            if prev_newline {
+                caret_neg = caret_offs;
+                prev_newline = false;
+            }
+            let mut glyph_id = glyph_ids[i];
+            if use_ligatures {
+                if let Some(ligature) = self.ttf.ligature(&glyph_ids[i..]) {
+                    glyph_id = ligature.glyph;
+                    merged = ligature.components.len();
                 }
             }
+            // Position marks relative to their base glyph
+            let mut x = xoff;
+            let mut y = yoff;
+            let attachment = base.and_then(|(b, bx, by)|
+                self.ttf.mark_to_base(b, glyph_id).map(|offs| (offs, bx, by)));
+            if let Some(((dx, dy), bx, by)) = attachment {
+                // Design units point up, but we point down
+                x = bx + to_pixels_x(dx);
+                y = by - to_pixels(dy);
+            }
             else {
-                xoff += offs;
+                if use_gpos_kerning {
+                    let adjustment = base
+                        .and_then(|(b, _, _)| self.ttf.pair_adjustment(b, glyph_id))
+                        .map_or(0, |units| to_pixels_x(units as i32));
+                    kerning += adjustment;
+                    xoff += adjustment;
+                    x = xoff;
+                }
+                base = Some((glyph_id, xoff, yoff));
+            }
+            let gp = GlyphPositioning{
+                character: ch,
+                index: i,
+                cluster: i,
+                glyph_id,
+                x,
+                y,
+                // GDI only places glyphs on whole pixels
+                x_fraction: 0.0,
+                caret_x: caret_offs - caret_neg + kerning,
+                caret_y: yoff,
+            };
+            f(gp);
+            xoff += offs;
+            if ch == '\n' {
+                prev_newline = true;
+                xoff = 0;
+                kerning = 0;
+                base = None;
+                yoff += line_height;
             }
             // TODO: Max logic is not enough for text going left!
             // We need to track the max offset from 0, 0 into any direction!
             max_w = std::cmp::max(max_w, xoff);
             max_h = std::cmp::max(max_h, yoff + line_height);
         }
-        (max_w, max_h)
+        Ok((max_w, max_h))
+    }
+
+    /// Lays out the text before the rotation by the escapement.
+    fn shape_text_unrotated(&self, text: &str, options: ShapeOptions,
+        f: &mut dyn FnMut(GlyphPositioning)) -> Result<(i32, i32)> {
+        #[cfg(feature = "directwrite")]
+        {
+            if let Some(shaper) = &self.dwrite {
+                if let Some(result) = self.shape_text_dwrite(shaper, text, options, f) {
+                    return Ok(result);
+                }
+            }
+        }
+        if let Some(result) = self.shape_text_monospace(text, options, f) {
+            return Ok(result);
+        }
+        // Take the buffers, so a nested call from the callback gets its own
+        let mut scratch = self.scratch.take();
+        // Encode in UTF16
+        scratch.text16.clear();
+        scratch.text16.extend(text.encode_utf16().map(|u| u as WCHAR));
+        let result = self.shape_text_gdi(&mut scratch, options, f);
+        self.scratch.replace(scratch);
+        result
+    }
+
+    /// Lays out UTF-16 text before the rotation by the escapement.
+    fn shape_text_utf16_unrotated(&self, units: &[u16], options: ShapeOptions,
+        f: &mut dyn FnMut(GlyphPositioning)) -> Result<(i32, i32)> {
+        // The other paths work on strings, only GDI takes UTF-16 directly
+        #[cfg(feature = "directwrite")]
+        let prefer_string = self.dwrite.is_some();
+        #[cfg(not(feature = "directwrite"))]
+        let prefer_string = false;
+        if prefer_string || (self.mono.is_some() && units.iter().all(|u| (0x20..=0x7e).contains(u))) {
+            return self.shape_text_unrotated(&String::from_utf16_lossy(units), options, f);
+        }
+        let mut scratch = self.scratch.take();
+        scratch.text16.clear();
+        scratch.text16.extend(units.iter().map(|u| *u as WCHAR));
+        let result = self.shape_text_gdi(&mut scratch, options, f);
+        self.scratch.replace(scratch);
+        result
+    }
+}
+
+impl ScaledFaceBackend for Win32ScaledFontFace {
+    fn em_size(&self) -> f64 {
+        self.em_size
+    }
+
+    fn line_height(&self) -> i32 {
+        self.metrics.0
+    }
+
+    fn ascent(&self) -> i32 {
+        self.metrics.1
+    }
+
+    fn rasterize_glyph(&mut self, codepoint: char) -> Result<RasterizedGlyph> {
+        // Convert to UTF16
+        let utf16str = utf8_to_utf16(&format!("{}", codepoint));
+        self.rasterize(codepoint, &utf16str, false)
+    }
+
+    fn rasterize_glyph_index(&mut self, glyph_id: u16) -> Result<RasterizedGlyph> {
+        self.rasterize('\0', &[glyph_id as WCHAR], true)
+    }
+
+    fn set_size(&mut self, pts: f64, dpi: f64, quality: RenderQuality) -> Result<()> {
+        let em_size = Self::em_size(pts, dpi);
+        let font = Self::create_font(&self.context, &self.face_name, em_size, quality, self.escapement,
+            self.stretch)?;
+        if !self.context.dc.select(&font) {
+            return Err(Error::SystemError("Failed to assign Font to Device Context!".into()));
+        }
+        let metrics = Self::check_selected_face(&self.context.dc, &self.face_name)
+            .and_then(|_| Self::line_metrics(&self.context.dc));
+        let metrics = match metrics {
+            Ok(metrics) => metrics,
+            Err(err) => {
+                // Deselect the new font, so it can be deleted
+                self.context.dc.select(&self.font);
+                return Err(err);
+            },
+        };
+        // The old font got deselected, so it can be deleted
+        self.font = font;
+        self.metrics = metrics;
+        self.em_size = em_size;
+        self.quality = quality;
+        #[cfg(feature = "directwrite")]
+        {
+            self.dwrite = if self.stretch == 1.0 {
+                DWriteShaper::create(&self.context.dc, em_size as f32).ok()
+            }
+            else {
+                None
+            };
+        }
+        self.mono = Self::mono_metrics(&self.context.dc, &self.ttf);
+        Ok(())
+    }
+
+    fn rasterize_glyph_into(&mut self, codepoint: char, dst: &mut [u8], dst_width: usize,
+        x: usize, y: usize) -> Result<GlyphBox> {
+        let utf16str = utf8_to_utf16(&format!("{}", codepoint));
+        let (bounds, x_offset, y_offset) = match self.draw(codepoint, &utf16str, false)? {
+            Some(drawn) => drawn,
+            None => return Ok(GlyphBox::default()),
+        };
+        let width = bounds.right - bounds.left;
+        let height = bounds.bottom - bounds.top;
+        crate::check_fits(dst.len(), dst_width, x, y, width, height)?;
+        self.copy_coverage(&bounds, dst, dst_width, x, y);
+        Ok(GlyphBox{
+            x: x_offset,
+            y: y_offset,
+            width: width as i32,
+            height: height as i32,
+        })
+    }
+
+    fn shape_text<F: FnMut(GlyphPositioning)>(&self, text: &str, options: ShapeOptions, mut f: F) -> Result<(i32, i32)> {
+        self.shape_rotated(&mut f, |f| self.shape_text_unrotated(text, options, f))
+    }
+
+    fn shape_text_utf16<F: FnMut(GlyphPositioning)>(&self, units: &[u16], options: ShapeOptions, mut f: F) -> Result<(i32, i32)> {
+        self.shape_rotated(&mut f, |f| self.shape_text_utf16_unrotated(units, options, f))
+    }
+}
+
+impl Drop for Win32ScaledFontFace {
+    fn drop(&mut self) {
+        // The Device Context might be shared, so it could outlive the face
+        self.context.release();
     }
 }
 
@@ -444,3 +1765,27 @@ struct Bounds {
     right : usize,
     bottom: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surrogate_pairs_are_placed_as_one_character() {
+        // U+1F600 takes 4 bytes in UTF-8 and 2 units in UTF-16
+        let text = "a\u{1F600}b";
+        let text16: Vec<WCHAR> = text.encode_utf16().map(|u| u as WCHAR).collect();
+        let chars: Vec<_> = chars_with_units(&text16).collect();
+        assert_eq!(chars, vec![('a', 0..1), ('\u{1F600}', 1..3), ('b', 3..4)]);
+        // The pair placed as a single glyph
+        let order = [0, 1, 1, 2];
+        let dx = [5, 7, 9, 0];
+        let placements: Vec<_> = chars.iter().map(|(_, u)| char_placement(&order, &dx, u.clone())).collect();
+        assert_eq!(placements, vec![(0, 5), (1, 7), (2, 9)]);
+        // The trailing unit placed as a separate glyph
+        let order = [0, 1, 2, 3];
+        let dx = [5, 7, 3, 9];
+        let placements: Vec<_> = chars.iter().map(|(_, u)| char_placement(&order, &dx, u.clone())).collect();
+        assert_eq!(placements, vec![(0, 5), (1, 10), (3, 9)]);
+    }
+}
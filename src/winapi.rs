@@ -7,6 +7,8 @@
 
 pub type BYTE      = u8;
 pub type INT       = i32;
+#[allow(non_camel_case_types)]
+pub type LONG_PTR  = isize;
 pub type UINT      = u32;
 pub type LONG      = i32;
 pub type BOOL      = INT;
@@ -30,8 +32,9 @@ pub type HGDIOBJ   = HANDLE;
 pub type HFONT     = HANDLE;
 
 pub type COLORREF  = DWORD;
+pub type LPARAM    = LONG_PTR;
 
-/// Kernel32 bindings.
+// Kernel32 bindings
 #[link(name = "kernel32")]
 extern "system" {
     // https://docs.microsoft.com/en-us/windows/win32/api/stringapiset/nf-stringapiset-multibytetowidechar
@@ -45,7 +48,7 @@ extern "system" {
     ) -> INT;
 }
 
-/// Gdi32 bindings.
+// Gdi32 bindings
 #[link(name = "gdi32")]
 extern "system" {
     // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-createcompatibledc
@@ -81,6 +84,11 @@ extern "system" {
         h  : HGDIOBJ,
     ) -> HGDIOBJ;
 
+    // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-getstockobject
+    pub fn GetStockObject(
+        i: INT
+    ) -> HGDIOBJ;
+
     // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-deleteobject
     pub fn DeleteObject(
         ho: HGDIOBJ
@@ -126,29 +134,61 @@ extern "system" {
         psizl   : LPSIZE ,
     ) -> BOOL;
 
+    // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-gettextextentpointi
+    pub fn GetTextExtentPointI(
+        hdc  : HDC        ,
+        pgiIn: *const WORD,
+        cgi  : INT        ,
+        psize: LPSIZE     ,
+    ) -> BOOL;
+
+    // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-gettextmetricsw
+    pub fn GetTextMetricsW(
+        hdc : HDC          ,
+        lptm: LPTEXTMETRICW,
+    ) -> BOOL;
+
+    // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-gettextfacew
+    pub fn GetTextFaceW(
+        hdc   : HDC   ,
+        c     : INT   ,
+        lpName: LPWSTR,
+    ) -> INT;
+
+    // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-getfontdata
+    pub fn GetFontData(
+        hdc     : HDC  ,
+        dwTable : DWORD,
+        dwOffset: DWORD,
+        pvBuffer: PVOID,
+        cjBuffer: DWORD,
+    ) -> DWORD;
+
+    // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-enumfontfamiliesexw
+    pub fn EnumFontFamiliesExW(
+        hdc      : HDC          ,
+        lpLogfont: LPLOGFONTW   ,
+        lpProc   : FONTENUMPROCW,
+        lParam   : LPARAM       ,
+        dwFlags  : DWORD        ,
+    ) -> INT;
+
     // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-settextcolor
     pub fn SetTextColor(
         hdc  : HDC     ,
         color: COLORREF,
     ) -> COLORREF;
 
-    // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-textoutw
-    pub fn TextOutW(
-        hdc     : HDC    ,
-        x       : INT    ,
-        y       : INT    ,
-        lpString: LPCWSTR,
-        c       : INT    ,
-    ) -> BOOL;
-
-    // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-patblt
-    pub fn PatBlt(
-        hdc: HDC  ,
-        x  : INT  ,
-        y  : INT  ,
-        w  : INT  ,
-        h  : INT  ,
-        rop: DWORD,
+    // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-exttextoutw
+    pub fn ExtTextOutW(
+        hdc     : HDC         ,
+        x       : INT         ,
+        y       : INT         ,
+        options : UINT        ,
+        lprect  : *const VOID ,
+        lpString: LPCWSTR     ,
+        c       : UINT        ,
+        lpDx    : *const INT  ,
     ) -> BOOL;
 
     // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/nf-wingdi-setbkmode
@@ -169,21 +209,29 @@ extern "system" {
 }
 
 // Used constants from Win32
-pub const CP_UTF8            : UINT     = 65001;
-pub const BLACKNESS          : DWORD    = 66;
-pub const CLR_INVALID        : COLORREF = 4294967295;
-pub const TRANSPARENT        : INT      = 1;
-pub const FW_NORMAL          : INT      = 400;
-pub const DEFAULT_CHARSET    : DWORD    = 1;
-pub const OUT_DEFAULT_PRECIS : DWORD    = 0;
-pub const CLIP_DEFAULT_PRECIS: DWORD    = 0;
-pub const ANTIALIASED_QUALITY: DWORD    = 4;
-pub const DEFAULT_PITCH      : DWORD    = 0;
-pub const FF_DONTCARE        : DWORD    = 0;
-pub const DIB_RGB_COLORS     : UINT     = 0;
-pub const BI_RGB             : DWORD    = 0;
-pub const FR_PRIVATE         : DWORD    = 0x10;
-pub const GCP_USEKERNING     : DWORD    = 0x0008;
+pub const CP_UTF8               : UINT     = 65001;
+pub const CLR_INVALID           : COLORREF = 4294967295;
+pub const TRANSPARENT           : INT      = 1;
+pub const FW_NORMAL             : INT      = 400;
+pub const DEFAULT_CHARSET       : DWORD    = 1;
+pub const OUT_DEFAULT_PRECIS    : DWORD    = 0;
+pub const CLIP_DEFAULT_PRECIS   : DWORD    = 0;
+pub const DEFAULT_QUALITY       : DWORD    = 0;
+pub const NONANTIALIASED_QUALITY: DWORD    = 3;
+pub const ANTIALIASED_QUALITY   : DWORD    = 4;
+pub const CLEARTYPE_QUALITY     : DWORD    = 5;
+pub const DEFAULT_PITCH         : DWORD    = 0;
+pub const FF_DONTCARE           : DWORD    = 0;
+pub const DIB_RGB_COLORS        : UINT     = 0;
+pub const BI_RGB                : DWORD    = 0;
+pub const FR_PRIVATE            : DWORD    = 0x10;
+pub const GCP_USEKERNING        : DWORD    = 0x0008;
+pub const ETO_GLYPH_INDEX       : UINT     = 0x0010;
+pub const TRUETYPE_FONTTYPE     : DWORD    = 0x0004;
+pub const GDI_ERROR             : DWORD    = 0xFFFFFFFF;
+pub const LF_FACESIZE           : usize    = 32;
+pub const LF_FULLFACESIZE       : usize    = 64;
+pub const SYSTEM_FONT           : INT      = 13;
 
 // https://docs.microsoft.com/en-us/previous-versions/dd145106(v=vs.85)
 #[repr(C)]
@@ -257,6 +305,84 @@ impl RGBQUAD {
     }
 }
 
+// https://docs.microsoft.com/en-us/windows/win32/api/wingdi/ns-wingdi-textmetricw
+#[allow(non_snake_case)]
+#[repr(C)]
+pub struct TEXTMETRICW {
+    pub tmHeight          : LONG ,
+    pub tmAscent          : LONG ,
+    pub tmDescent         : LONG ,
+    pub tmInternalLeading : LONG ,
+    pub tmExternalLeading : LONG ,
+    pub tmAveCharWidth    : LONG ,
+    pub tmMaxCharWidth    : LONG ,
+    pub tmWeight          : LONG ,
+    pub tmOverhang        : LONG ,
+    pub tmDigitizedAspectX: LONG ,
+    pub tmDigitizedAspectY: LONG ,
+    pub tmFirstChar       : WCHAR,
+    pub tmLastChar        : WCHAR,
+    pub tmDefaultChar     : WCHAR,
+    pub tmBreakChar       : WCHAR,
+    pub tmItalic          : BYTE ,
+    pub tmUnderlined      : BYTE ,
+    pub tmStruckOut       : BYTE ,
+    pub tmPitchAndFamily  : BYTE ,
+    pub tmCharSet         : BYTE ,
+}
+pub type LPTEXTMETRICW = *mut TEXTMETRICW;
+
+impl TEXTMETRICW {
+    pub fn new() -> Self {
+        unsafe{ std::mem::zeroed() }
+    }
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/wingdi/ns-wingdi-logfontw
+#[allow(non_snake_case)]
+#[repr(C)]
+pub struct LOGFONTW {
+    pub lfHeight        : LONG                ,
+    pub lfWidth         : LONG                ,
+    pub lfEscapement    : LONG                ,
+    pub lfOrientation   : LONG                ,
+    pub lfWeight        : LONG                ,
+    pub lfItalic        : BYTE                ,
+    pub lfUnderline     : BYTE                ,
+    pub lfStrikeOut     : BYTE                ,
+    pub lfCharSet       : BYTE                ,
+    pub lfOutPrecision  : BYTE                ,
+    pub lfClipPrecision : BYTE                ,
+    pub lfQuality       : BYTE                ,
+    pub lfPitchAndFamily: BYTE                ,
+    pub lfFaceName      : [WCHAR; LF_FACESIZE],
+}
+pub type LPLOGFONTW = *mut LOGFONTW;
+
+impl LOGFONTW {
+    pub fn new() -> Self {
+        unsafe{ std::mem::zeroed() }
+    }
+}
+
+// https://docs.microsoft.com/en-us/windows/win32/api/wingdi/ns-wingdi-enumlogfontexw
+#[allow(non_snake_case)]
+#[repr(C)]
+pub struct ENUMLOGFONTEXW {
+    pub elfLogFont : LOGFONTW                ,
+    pub elfFullName: [WCHAR; LF_FULLFACESIZE],
+    pub elfStyle   : [WCHAR; LF_FACESIZE]    ,
+    pub elfScript  : [WCHAR; LF_FACESIZE]    ,
+}
+
+// https://docs.microsoft.com/en-us/previous-versions/dd162618(v=vs.85)
+pub type FONTENUMPROCW = Option<unsafe extern "system" fn(
+    *const LOGFONTW   ,
+    *const TEXTMETRICW,
+    DWORD             ,
+    LPARAM            ,
+) -> INT>;
+
 // https://docs.microsoft.com/en-us/windows/win32/api/wingdi/ns-wingdi-gcp_resultsw
 #[allow(non_snake_case)]
 #[repr(C)]